@@ -0,0 +1,33 @@
+// Compares the `thumbnail` operation with a direct `resize_exact` of the same dimensions.
+// Run with `cargo bench --bench thumbnail`.
+use combostew::operations::transformations::thumbnail::thumbnail;
+use criterion::{criterion_group, criterion_main, Criterion};
+use image::{DynamicImage, FilterType, GenericImageView};
+
+fn setup_image() -> DynamicImage {
+    image::open("resources/botanical.jpg").expect("Unable to open the benchmark image.")
+}
+
+fn bench_thumbnail(c: &mut Criterion) {
+    let image = setup_image();
+    let (width, height) = image.dimensions();
+    let (nwidth, nheight) = (width / 8, height / 8);
+
+    let mut group = c.benchmark_group("thumbnail botanical.jpg");
+    group.sample_size(10);
+
+    group.bench_function("resize_exact gaussian", |b| {
+        b.iter(|| image.resize_exact(nwidth, nheight, FilterType::Gaussian))
+    });
+    group.bench_function("resize_exact triangle", |b| {
+        b.iter(|| image.resize_exact(nwidth, nheight, FilterType::Triangle))
+    });
+    group.bench_function("thumbnail triangle", |b| {
+        b.iter(|| thumbnail(&image, (nwidth, nheight), FilterType::Triangle))
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_thumbnail);
+criterion_main!(benches);
@@ -0,0 +1,205 @@
+// Analysis of the pixel values of an image, e.g. to inspect its exposure before adjusting it.
+use image::{DynamicImage, ImageBuffer, Pixel};
+
+// The amount of pixels with each of the 256 values of an 8-bit channel.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Histogram {
+    bins: [u64; 256],
+}
+
+impl Histogram {
+    pub fn from_values<I: IntoIterator<Item = u8>>(values: I) -> Self {
+        let mut histogram = Histogram::default();
+
+        for value in values {
+            histogram.bins[usize::from(value)] += 1;
+        }
+
+        histogram
+    }
+
+    pub fn bins(&self) -> &[u64; 256] {
+        &self.bins
+    }
+
+    pub fn count(&self, value: u8) -> u64 {
+        self.bins[usize::from(value)]
+    }
+
+    pub fn total(&self) -> u64 {
+        self.bins.iter().sum()
+    }
+
+    // The amount of pixels with a value at or below each value.
+    pub fn cumulative(&self) -> [u64; 256] {
+        let mut cumulative = [0; 256];
+        let mut sum = 0;
+
+        for (value, count) in self.bins.iter().enumerate() {
+            sum += count;
+            cumulative[value] = sum;
+        }
+
+        cumulative
+    }
+
+    pub fn min(&self) -> Option<u8> {
+        self.bins
+            .iter()
+            .position(|&count| count > 0)
+            .map(|v| v as u8)
+    }
+
+    pub fn max(&self) -> Option<u8> {
+        self.bins
+            .iter()
+            .rposition(|&count| count > 0)
+            .map(|v| v as u8)
+    }
+
+    pub fn mean(&self) -> Option<f64> {
+        let total = self.total();
+
+        if total == 0 {
+            return None;
+        }
+
+        let sum: u64 = self
+            .bins
+            .iter()
+            .enumerate()
+            .map(|(value, count)| value as u64 * count)
+            .sum();
+
+        Some(sum as f64 / total as f64)
+    }
+
+    // The lowest and highest value which remain after ignoring `fraction` of the pixels at
+    // both ends of the histogram; for a `fraction` of 0, these are the minimum and maximum.
+    // None if the histogram is empty, or if `fraction` isn't smaller than 0.5.
+    pub fn clipped_range(&self, fraction: f64) -> Option<(u8, u8)> {
+        if !(0.0..0.5).contains(&fraction) {
+            return None;
+        }
+
+        let cumulative = self.cumulative();
+        let total = cumulative[255];
+        let clipped = (total as f64 * fraction) as u64;
+
+        let low = cumulative.iter().position(|&sum| sum > clipped)?;
+        let high = (0..256)
+            .rev()
+            .find(|&value| total - cumulative[value] + self.bins[value] > clipped)?;
+
+        Some((low as u8, high as u8))
+    }
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Histogram { bins: [0; 256] }
+    }
+}
+
+// A histogram for each channel of the image, in the order of the channels of its buffer: for
+// example blue, green and red for a BGR image. The alpha channel, if any, is the last one.
+pub fn histograms(image: &DynamicImage) -> Vec<Histogram> {
+    match image {
+        DynamicImage::ImageLuma8(buffer) => channel_histograms(buffer),
+        DynamicImage::ImageLumaA8(buffer) => channel_histograms(buffer),
+        DynamicImage::ImageRgb8(buffer) => channel_histograms(buffer),
+        DynamicImage::ImageRgba8(buffer) => channel_histograms(buffer),
+        DynamicImage::ImageBgr8(buffer) => channel_histograms(buffer),
+        DynamicImage::ImageBgra8(buffer) => channel_histograms(buffer),
+    }
+}
+
+// A histogram of the luminance of the pixels, as computed by `grayscale`.
+pub fn luminance_histogram(image: &DynamicImage) -> Histogram {
+    match image {
+        DynamicImage::ImageLuma8(buffer) => buffer_luminance_histogram(buffer),
+        DynamicImage::ImageLumaA8(buffer) => buffer_luminance_histogram(buffer),
+        DynamicImage::ImageRgb8(buffer) => buffer_luminance_histogram(buffer),
+        DynamicImage::ImageRgba8(buffer) => buffer_luminance_histogram(buffer),
+        DynamicImage::ImageBgr8(buffer) => buffer_luminance_histogram(buffer),
+        DynamicImage::ImageBgra8(buffer) => buffer_luminance_histogram(buffer),
+    }
+}
+
+pub(crate) fn channel_histograms<P>(buffer: &ImageBuffer<P, Vec<u8>>) -> Vec<Histogram>
+where
+    P: Pixel<Subpixel = u8> + 'static,
+{
+    let channels = usize::from(P::CHANNEL_COUNT);
+    let mut histograms = vec![Histogram::default(); channels];
+
+    for pixel in buffer.pixels() {
+        for (histogram, &value) in histograms.iter_mut().zip(pixel.channels()) {
+            histogram.bins[usize::from(value)] += 1;
+        }
+    }
+
+    histograms
+}
+
+pub(crate) fn buffer_luminance_histogram<P>(buffer: &ImageBuffer<P, Vec<u8>>) -> Histogram
+where
+    P: Pixel<Subpixel = u8> + 'static,
+{
+    Histogram::from_values(buffer.pixels().map(|pixel| pixel.to_luma()[0]))
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{Bgra, GrayImage, Luma};
+
+    use super::*;
+
+    #[test]
+    fn histograms_per_channel() {
+        let buffer = ImageBuffer::from_fn(2, 2, |x, y| Bgra([x as u8, 10, 20 * y as u8, 255]));
+        let image = DynamicImage::ImageBgra8(buffer);
+
+        let histograms = histograms(&image);
+
+        assert_eq!(4, histograms.len());
+        assert_eq!((2, 2), (histograms[0].count(0), histograms[0].count(1)));
+        assert_eq!(4, histograms[1].count(10));
+        assert_eq!((2, 2), (histograms[2].count(0), histograms[2].count(20)));
+        assert_eq!(4, histograms[3].count(255));
+        assert!(histograms.iter().all(|histogram| histogram.total() == 4));
+    }
+
+    #[test]
+    fn luminance_of_gray_pixels() {
+        let image = DynamicImage::ImageRgb8(ImageBuffer::from_pixel(3, 1, image::Rgb([7, 7, 7])));
+
+        assert_eq!(3, luminance_histogram(&image).count(7));
+    }
+
+    #[test]
+    fn statistics() {
+        let histogram = Histogram::from_values(vec![2, 4, 4, 6]);
+
+        assert_eq!(Some(2), histogram.min());
+        assert_eq!(Some(6), histogram.max());
+        assert_eq!(Some(4.0), histogram.mean());
+        assert_eq!(3, histogram.cumulative()[4]);
+        assert_eq!(4, histogram.cumulative()[255]);
+
+        let empty = Histogram::default();
+        assert_eq!((None, None, None), (empty.min(), empty.max(), empty.mean()));
+        assert_eq!(None, empty.clipped_range(0.0));
+    }
+
+    #[test]
+    fn clipped_range() {
+        let image = GrayImage::from_fn(100, 1, |x, _| Luma([x as u8 + 50]));
+        let histogram = &channel_histograms(&image)[0];
+
+        assert_eq!(Some((50, 149)), histogram.clipped_range(0.0));
+        assert_eq!(Some((55, 144)), histogram.clipped_range(0.05));
+        assert_eq!(Some((99, 100)), histogram.clipped_range(0.499));
+        assert_eq!(None, histogram.clipped_range(0.5));
+    }
+}
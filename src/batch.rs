@@ -0,0 +1,754 @@
+use std::collections::HashSet;
+use std::fs;
+#[cfg(feature = "parallel")]
+use std::num::NonZeroUsize;
+use std::path::{Component, Path, PathBuf};
+
+use crate::config::Config;
+use crate::error::Error;
+use crate::io;
+use crate::operations::engine::{ImageEngine, Program};
+use crate::operations::registry::Registry;
+use crate::processor::encoding_format::EncodingFormatDecider;
+
+// The set of input images of a batch.
+#[derive(Clone, Debug)]
+pub enum BatchInput {
+    // Images are processed in the given order.
+    Paths(Vec<PathBuf>),
+    // All files (not recursively) within the directory, sorted by path.
+    Directory(PathBuf),
+    // All files matching the glob pattern, sorted by path.
+    Glob(String),
+}
+
+// A directory entry or glob match which couldn't be read.
+#[derive(Debug)]
+pub struct InputError {
+    // The unreadable path; for a directory entry, the directory.
+    pub path: PathBuf,
+    pub error: Error,
+}
+
+impl BatchInput {
+    // Resolves the input into the list of files which should be processed.
+    //
+    // Only an invalid glob pattern or a directory which can't be read fail as a whole; entries
+    // which can't be read are listed as errors, after the files.
+    pub fn paths(&self) -> Result<Vec<Result<PathBuf, InputError>>, Error> {
+        match self {
+            BatchInput::Paths(paths) => Ok(paths.iter().cloned().map(Ok).collect()),
+            BatchInput::Directory(dir) => {
+                let mut paths = Vec::new();
+                let mut failures = Vec::new();
+
+                for entry in fs::read_dir(dir)? {
+                    match entry {
+                        Ok(entry) if entry.path().is_file() => paths.push(entry.path()),
+                        Ok(_) => {}
+                        Err(err) => failures.push(InputError {
+                            path: dir.clone(),
+                            error: Error::Io(err),
+                        }),
+                    }
+                }
+
+                Ok(sorted_inputs(paths, failures))
+            }
+            BatchInput::Glob(pattern) => {
+                let matches = glob::glob(pattern).map_err(Error::InvalidGlobPattern)?;
+                let mut paths = Vec::new();
+                let mut failures = Vec::new();
+
+                for path in matches {
+                    match path {
+                        Ok(path) if path.is_file() => paths.push(path),
+                        Ok(_) => {}
+                        Err(err) => failures.push(InputError {
+                            path: err.path().to_path_buf(),
+                            error: Error::Io(err.into()),
+                        }),
+                    }
+                }
+
+                Ok(sorted_inputs(paths, failures))
+            }
+        }
+    }
+}
+
+fn sorted_inputs(
+    mut paths: Vec<PathBuf>,
+    mut failures: Vec<InputError>,
+) -> Vec<Result<PathBuf, InputError>> {
+    paths.sort();
+    failures.sort_by(|a, b| a.path.cmp(&b.path));
+
+    paths
+        .into_iter()
+        .map(Ok)
+        .chain(failures.into_iter().map(Err))
+        .collect()
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum TemplatePart {
+    Literal(String),
+    Stem,
+    Extension,
+    FileName,
+    Index,
+}
+
+// Template used to name the output files of a batch.
+//
+// Supported placeholders are:
+// * `{stem}`: the file name of the input without its extension
+// * `{ext}`: the extension of the input (without the leading dot)
+// * `{name}`: the complete file name of the input
+// * `{index}`: the position of the input within the batch, starting at 0
+//
+// Literal braces can be written as `{{` and `}}`.
+// For example, `{stem}_thumb.{ext}` names the output of `photo.jpg` `photo_thumb.jpg`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct NamingTemplate {
+    parts: Vec<TemplatePart>,
+}
+
+impl NamingTemplate {
+    pub fn parse(template: &str) -> Result<NamingTemplate, Error> {
+        let mut parts = Vec::new();
+        let mut literal = String::new();
+        let mut chars = template.chars();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.as_str().starts_with('{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.as_str().starts_with('}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '{' => {
+                    let rest = chars.as_str();
+                    let end = rest.find('}').ok_or_else(|| {
+                        Error::InvalidNamingTemplate(format!(
+                            "unclosed placeholder in '{}'",
+                            template
+                        ))
+                    })?;
+
+                    let part = match &rest[..end] {
+                        "stem" => TemplatePart::Stem,
+                        "ext" => TemplatePart::Extension,
+                        "name" => TemplatePart::FileName,
+                        "index" => TemplatePart::Index,
+                        unknown => {
+                            return Err(Error::InvalidNamingTemplate(format!(
+                                "unknown placeholder '{{{}}}', expected one of {{stem}}, {{ext}}, {{name}} or {{index}}",
+                                unknown
+                            )))
+                        }
+                    };
+
+                    if !literal.is_empty() {
+                        parts.push(TemplatePart::Literal(std::mem::take(&mut literal)));
+                    }
+
+                    parts.push(part);
+                    chars = rest[end + 1..].chars();
+                }
+                '}' => {
+                    return Err(Error::InvalidNamingTemplate(format!(
+                        "unmatched '}}' in '{}'",
+                        template
+                    )))
+                }
+                c if std::path::is_separator(c) => {
+                    return Err(Error::InvalidNamingTemplate(format!(
+                        "path separator '{}' in '{}', the template names a file within the \
+                         output directory",
+                        c, template
+                    )))
+                }
+                c => literal.push(c),
+            }
+        }
+
+        if !literal.is_empty() {
+            parts.push(TemplatePart::Literal(literal));
+        }
+
+        if parts.is_empty() {
+            return Err(Error::InvalidNamingTemplate("template is empty".into()));
+        }
+
+        Ok(NamingTemplate { parts })
+    }
+
+    // Renders the output file name for the input at `path`, which is the `index`-th input of
+    // the batch.
+    pub fn render(&self, path: &Path, index: usize) -> Result<String, Error> {
+        let invalid = || Error::InvalidPath(path.to_path_buf());
+        let component = |c: Option<&std::ffi::OsStr>| -> Result<String, Error> {
+            c.map_or(Ok(String::new()), |v| {
+                v.to_str().map(String::from).ok_or_else(invalid)
+            })
+        };
+
+        let mut name = String::new();
+
+        for part in &self.parts {
+            match part {
+                TemplatePart::Literal(literal) => name.push_str(literal),
+                TemplatePart::Stem => name.push_str(&component(path.file_stem())?),
+                TemplatePart::Extension => name.push_str(&component(path.extension())?),
+                TemplatePart::FileName => name.push_str(&component(path.file_name())?),
+                TemplatePart::Index => name.push_str(&index.to_string()),
+            }
+        }
+
+        // The name must be a single file name, so the output stays within the output directory.
+        let mut components = Path::new(&name).components();
+
+        match (components.next(), components.next()) {
+            (Some(Component::Normal(_)), None) => Ok(name),
+            _ => Err(Error::InvalidNamingTemplate(format!(
+                "'{}' is not a file name",
+                name
+            ))),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Batch {
+    pub input: BatchInput,
+
+    // Directory to which the outputs are written; created if it doesn't exist yet.
+    pub output_dir: PathBuf,
+
+    pub naming_template: NamingTemplate,
+
+    // Operations which aren't built in, and which the program may use.
+    pub registry: Registry,
+}
+
+// Outcome of processing a single input of a batch.
+#[derive(Debug)]
+pub struct BatchEntry {
+    pub input: PathBuf,
+
+    // The path of the written output, or the reason why this input failed.
+    pub result: Result<PathBuf, Error>,
+}
+
+// Per-file outcome of a batch, in the order of the inputs.
+#[derive(Debug, Default)]
+pub struct BatchReport {
+    pub entries: Vec<BatchEntry>,
+}
+
+impl BatchReport {
+    pub fn successes(&self) -> impl Iterator<Item = &BatchEntry> {
+        self.entries.iter().filter(|entry| entry.result.is_ok())
+    }
+
+    pub fn failures(&self) -> impl Iterator<Item = &BatchEntry> {
+        self.entries.iter().filter(|entry| entry.result.is_err())
+    }
+
+    pub fn is_success(&self) -> bool {
+        self.failures().next().is_none()
+    }
+}
+
+// Applies `program` to every input of the batch.
+//
+// Errors which affect the batch as a whole (e.g. an invalid glob pattern, or an output directory
+// which can't be created) are returned directly. Errors of individual inputs are recorded in the
+// report, and don't stop the remaining inputs from being processed.
+pub fn process_batch(
+    batch: &Batch,
+    program: &Program,
+    config: &Config,
+) -> Result<BatchReport, Error> {
+    let jobs = plan(batch)?;
+
+    let entries = jobs
+        .into_iter()
+        .map(|job| job.run(program, &batch.registry, config))
+        .collect();
+
+    Ok(BatchReport { entries })
+}
+
+// Applies `program` to every input of the batch, like `process_batch`, but spreads the inputs
+// over a pool of `threads` worker threads. If `threads` is None, one thread per logical CPU core
+// is used.
+//
+// The entries of the report are in the order of the inputs, regardless of the order in which the
+// workers finish, and output path collisions are always attributed to the later inputs; the
+// report is thus identical to the report of `process_batch`.
+#[cfg(feature = "parallel")]
+pub fn process_batch_parallel(
+    batch: &Batch,
+    program: &Program,
+    config: &Config,
+    threads: Option<NonZeroUsize>,
+) -> Result<BatchReport, Error> {
+    use rayon::prelude::*;
+
+    let jobs = plan(batch)?;
+
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(threads.map_or(0, NonZeroUsize::get))
+        .build()
+        .map_err(Error::ThreadPool)?;
+
+    let entries = pool.install(|| {
+        jobs.into_par_iter()
+            .map(|job| job.run(program, &batch.registry, config))
+            .collect()
+    });
+
+    Ok(BatchReport { entries })
+}
+
+// A single input of a batch, together with its output path.
+// If the output path could not be determined, the output holds the reason instead.
+pub(crate) struct Job {
+    input: PathBuf,
+    output: Result<PathBuf, Error>,
+}
+
+impl Job {
+    pub(crate) fn run(self, program: &Program, registry: &Registry, config: &Config) -> BatchEntry {
+        let Job { input, output } = self;
+        let result = output.and_then(|output| {
+            process_file(&input, &output, program, registry, config).map(|_| output)
+        });
+
+        BatchEntry { input, result }
+    }
+}
+
+// Resolves the inputs and output paths of a batch and creates the output directory.
+//
+// Paths are compared in their canonical form, so e.g. `dir/../out.png` and `out.png` are the
+// same output.
+pub(crate) fn plan(batch: &Batch) -> Result<Vec<Job>, Error> {
+    let inputs = batch.input.paths()?;
+
+    fs::create_dir_all(&batch.output_dir)?;
+
+    // Inputs which don't exist can't be overwritten; they fail once they're imported.
+    let canonical_inputs: HashSet<PathBuf> = inputs
+        .iter()
+        .filter_map(|input| fs::canonicalize(input.as_ref().ok()?).ok())
+        .collect();
+
+    let mut seen = HashSet::new();
+
+    let jobs = inputs
+        .into_iter()
+        .enumerate()
+        .map(|(index, input)| {
+            let input = match input {
+                Ok(input) => input,
+                Err(InputError { path, error }) => {
+                    return Job {
+                        input: path,
+                        output: Err(error),
+                    }
+                }
+            };
+
+            let output = batch
+                .naming_template
+                .render(&input, index)
+                .map(|name| batch.output_dir.join(name))
+                .and_then(|output| {
+                    let canonical = canonical_output(&output)?;
+
+                    // An output may not replace any input, since it could still have to be
+                    // read. The first input claims an output path; later inputs would
+                    // overwrite it.
+                    if canonical_inputs.contains(&canonical) {
+                        Err(Error::OutputPathIsInput(output))
+                    } else if seen.insert(canonical) {
+                        Ok(output)
+                    } else {
+                        Err(Error::OutputPathCollision(output))
+                    }
+                });
+
+            Job { input, output }
+        })
+        .collect();
+
+    Ok(jobs)
+}
+
+// The canonical form of an output path, which doesn't need to exist yet; its directory does.
+fn canonical_output(output: &Path) -> Result<PathBuf, Error> {
+    if let Ok(canonical) = fs::canonicalize(output) {
+        return Ok(canonical);
+    }
+
+    let invalid = || Error::InvalidPath(output.to_path_buf());
+    let directory = output.parent().ok_or_else(invalid)?;
+    let name = output.file_name().ok_or_else(invalid)?;
+
+    Ok(fs::canonicalize(directory)?.join(name))
+}
+
+fn process_file(
+    input: &Path,
+    output: &Path,
+    program: &Program,
+    registry: &Registry,
+    config: &Config,
+) -> Result<(), Error> {
+    let image = io::import(Some(input), config)?.image;
+
+    let mut engine = ImageEngine::new(image);
+    engine.set_registry(registry);
+    let image = engine.ignite(program.clone())?;
+
+    let output = output
+        .to_str()
+        .ok_or_else(|| Error::InvalidPath(output.to_path_buf()))?;
+
+    let config = Config {
+        output: Some(output.to_string()),
+        ..config.clone()
+    };
+
+    io::export(image, &EncodingFormatDecider, &config)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::{
+        FormatEncodingSettings, JPEGEncodingSettings, PNGEncodingSettings, PNMEncodingSettings,
+    };
+    use crate::operations::engine::Statement;
+    use crate::operations::Operation;
+    use image::GenericImageView;
+
+    use super::*;
+
+    fn resources() -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("resources")
+    }
+
+    // Each test uses its own output directory, since tests run in parallel.
+    fn setup_output_dir(name: &str) -> PathBuf {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("target")
+            .join("batch_test_output")
+            .join(name);
+
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn setup_dummy_config() -> Config {
+        Config {
+            tool_name: env!("CARGO_PKG_NAME"),
+            licenses: vec![],
+            forced_input_format: None,
+            forced_output_format: None,
+            disable_automatic_color_type_adjustment: false,
+            encoding_settings: FormatEncodingSettings {
+                jpeg_settings: JPEGEncodingSettings::new_result((false, None))
+                    .expect("Invalid jpeg settings"),
+                png_settings: PNGEncodingSettings::new_result((false, None), (false, None))
+                    .expect("Invalid png settings"),
+                pnm_settings: PNMEncodingSettings::new(false),
+            },
+            output: None,
+            application_specific: vec![],
+        }
+    }
+
+    fn program() -> Program {
+        vec![Statement::Operation(Operation::Resize(4, 4))]
+    }
+
+    #[test]
+    fn template_render() {
+        let template = NamingTemplate::parse("{index}-{stem}_thumb.{ext}").unwrap();
+        let name = template.render(Path::new("dir/photo.jpg"), 3).unwrap();
+
+        assert_eq!("3-photo_thumb.jpg", name);
+    }
+
+    #[test]
+    fn template_render_name_and_escapes() {
+        let template = NamingTemplate::parse("{{{name}}}.png").unwrap();
+        let name = template.render(Path::new("photo.jpg"), 0).unwrap();
+
+        assert_eq!("{photo.jpg}.png", name);
+    }
+
+    #[test]
+    fn template_unknown_placeholder() {
+        let err = NamingTemplate::parse("{stem}.{extension}").unwrap_err();
+
+        assert!(err.to_string().contains("{extension}"));
+    }
+
+    #[test]
+    fn template_unclosed_placeholder() {
+        assert!(NamingTemplate::parse("{stem.png").is_err());
+        assert!(NamingTemplate::parse("stem}.png").is_err());
+        assert!(NamingTemplate::parse("").is_err());
+    }
+
+    #[test]
+    fn template_path_separators() {
+        assert!(NamingTemplate::parse("../x").is_err());
+        assert!(NamingTemplate::parse("../{name}").is_err());
+        assert!(NamingTemplate::parse("a/b").is_err());
+        assert!(NamingTemplate::parse("/tmp/{stem}.{ext}").is_err());
+    }
+
+    #[test]
+    fn template_render_not_a_file_name() {
+        let path = Path::new("photo");
+
+        for template in &["{ext}", "..", ".{ext}", "{ext}."] {
+            let template = NamingTemplate::parse(template).unwrap();
+
+            assert!(matches!(
+                template.render(path, 0),
+                Err(Error::InvalidNamingTemplate(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn directory_input_is_sorted_and_reports_per_file() {
+        let output_dir = setup_output_dir("directory");
+        let batch = Batch {
+            input: BatchInput::Directory(resources()),
+            output_dir: output_dir.clone(),
+            naming_template: NamingTemplate::parse("{stem}_thumb.png").unwrap(),
+            registry: Registry::default(),
+        };
+
+        let report = process_batch(&batch, &program(), &setup_dummy_config()).unwrap();
+
+        let inputs: Vec<_> = report.entries.iter().map(|e| e.input.clone()).collect();
+        let mut sorted = inputs.clone();
+        sorted.sort();
+        assert_eq!(sorted, inputs);
+
+        // credits.txt is not an image, but doesn't stop the other files from being processed.
+        let failures: Vec<_> = report.failures().collect();
+        assert_eq!(1, failures.len());
+        assert_eq!(Some("credits.txt".as_ref()), failures[0].input.file_name());
+        assert!(!report.is_success());
+
+        let output = output_dir.join("botanical_thumb.png");
+        assert!(report
+            .successes()
+            .any(|e| e.result.as_ref().ok() == Some(&output)));
+        assert_eq!((4, 4), image::open(output).unwrap().dimensions());
+
+        fs::remove_dir_all(output_dir).unwrap();
+    }
+
+    #[test]
+    fn glob_input() {
+        let output_dir = setup_output_dir("glob");
+        let pattern = resources().join("*.bmp");
+        let batch = Batch {
+            input: BatchInput::Glob(pattern.to_str().unwrap().to_string()),
+            output_dir: output_dir.clone(),
+            naming_template: NamingTemplate::parse("{index}.{ext}").unwrap(),
+            registry: Registry::default(),
+        };
+
+        let report = process_batch(&batch, &program(), &setup_dummy_config()).unwrap();
+
+        assert!(report.is_success());
+        assert_eq!(2, report.entries.len());
+        assert!(output_dir.join("0.bmp").exists());
+        assert!(output_dir.join("1.bmp").exists());
+
+        fs::remove_dir_all(output_dir).unwrap();
+    }
+
+    #[test]
+    fn unreadable_inputs_are_listed_after_the_files() {
+        let unreadable = |path: &str| InputError {
+            path: PathBuf::from(path),
+            error: Error::Io(std::io::ErrorKind::PermissionDenied.into()),
+        };
+
+        let inputs = sorted_inputs(
+            vec![PathBuf::from("b.png"), PathBuf::from("a.png")],
+            vec![unreadable("d"), unreadable("c")],
+        );
+        let paths: Vec<_> = inputs
+            .iter()
+            .map(|input| match input {
+                Ok(path) => (path.to_str().unwrap(), true),
+                Err(err) => (err.path.to_str().unwrap(), false),
+            })
+            .collect();
+
+        assert_eq!(
+            vec![("a.png", true), ("b.png", true), ("c", false), ("d", false)],
+            paths
+        );
+    }
+
+    #[test]
+    fn invalid_glob_pattern() {
+        let batch = Batch {
+            input: BatchInput::Glob("[".into()),
+            output_dir: setup_output_dir("invalid_glob"),
+            naming_template: NamingTemplate::parse("{name}").unwrap(),
+            registry: Registry::default(),
+        };
+
+        let err = process_batch(&batch, &program(), &setup_dummy_config()).unwrap_err();
+
+        assert!(matches!(err, Error::InvalidGlobPattern(_)));
+    }
+
+    #[test]
+    fn output_collision_is_reported_for_later_inputs() {
+        let output_dir = setup_output_dir("collision");
+        let batch = Batch {
+            input: BatchInput::Paths(vec![
+                resources().join("rainbow_8x6.bmp"),
+                resources().join("blackwhite_2x2.bmp"),
+            ]),
+            output_dir: output_dir.clone(),
+            naming_template: NamingTemplate::parse("out.png").unwrap(),
+            registry: Registry::default(),
+        };
+
+        let report = process_batch(&batch, &program(), &setup_dummy_config()).unwrap();
+
+        assert!(report.entries[0].result.is_ok());
+        assert!(matches!(
+            report.entries[1].result,
+            Err(Error::OutputPathCollision(_))
+        ));
+
+        fs::remove_dir_all(output_dir).unwrap();
+    }
+
+    #[test]
+    fn output_is_input_is_reported() {
+        let output_dir = setup_output_dir("output_is_input");
+        fs::create_dir_all(&output_dir).unwrap();
+        fs::copy(
+            resources().join("blackwhite_2x2.bmp"),
+            output_dir.join("input.bmp"),
+        )
+        .unwrap();
+
+        // the same file as the output, but spelled differently
+        let input = output_dir
+            .join("..")
+            .join("output_is_input")
+            .join("input.bmp");
+        let batch = Batch {
+            input: BatchInput::Paths(vec![input, resources().join("rainbow_8x6.bmp")]),
+            output_dir: output_dir.clone(),
+            naming_template: NamingTemplate::parse("{name}").unwrap(),
+            registry: Registry::default(),
+        };
+
+        let report = process_batch(&batch, &program(), &setup_dummy_config()).unwrap();
+
+        assert!(matches!(
+            report.entries[0].result,
+            Err(Error::OutputPathIsInput(_))
+        ));
+        assert!(report.entries[1].result.is_ok());
+        assert_eq!(
+            (2, 2),
+            image::open(output_dir.join("input.bmp"))
+                .unwrap()
+                .dimensions()
+        );
+
+        fs::remove_dir_all(output_dir).unwrap();
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn parallel_report_equals_sequential_report() {
+        fn summarize(report: BatchReport) -> Vec<(PathBuf, Result<PathBuf, String>)> {
+            report
+                .entries
+                .into_iter()
+                .map(|e| (e.input, e.result.map_err(|err| err.to_string())))
+                .collect()
+        }
+
+        let output_dir = setup_output_dir("parallel");
+        // All inputs but the first collide, and credits.txt can't be decoded.
+        let batch = Batch {
+            input: BatchInput::Directory(resources()),
+            output_dir: output_dir.clone(),
+            naming_template: NamingTemplate::parse("{index}_{stem}.png").unwrap(),
+            registry: Registry::default(),
+        };
+        let colliding = Batch {
+            naming_template: NamingTemplate::parse("thumb.png").unwrap(),
+            ..batch.clone()
+        };
+
+        for batch in &[batch, colliding] {
+            let sequential = process_batch(batch, &program(), &setup_dummy_config()).unwrap();
+            let sequential = summarize(sequential);
+
+            for threads in &[1, 4] {
+                let parallel = process_batch_parallel(
+                    batch,
+                    &program(),
+                    &setup_dummy_config(),
+                    NonZeroUsize::new(*threads),
+                )
+                .unwrap();
+
+                assert_eq!(sequential, summarize(parallel));
+            }
+        }
+
+        fs::remove_dir_all(output_dir).unwrap();
+    }
+
+    #[test]
+    fn failing_program_is_reported() {
+        let output_dir = setup_output_dir("failing_program");
+        let batch = Batch {
+            input: BatchInput::Paths(vec![resources().join("blackwhite_2x2.bmp")]),
+            output_dir: output_dir.clone(),
+            naming_template: NamingTemplate::parse("{stem}.png").unwrap(),
+            registry: Registry::default(),
+        };
+        let program = vec![Statement::Operation(Operation::Crop(0, 0, 10, 10))];
+
+        let report = process_batch(&batch, &program, &setup_dummy_config()).unwrap();
+
+        assert!(matches!(
+            report.entries[0].result,
+            Err(Error::CropOutOfBounds { .. })
+        ));
+        assert!(!output_dir.join("blackwhite_2x2.png").exists());
+
+        fs::remove_dir_all(output_dir).unwrap();
+    }
+}
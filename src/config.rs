@@ -1,12 +1,19 @@
+use crate::error::Error;
+use crate::operations::transformations::dither::Dither;
+
 // Currently uses String instead of &str for easier initial development (i.e. no manual lifetimes).
 // It should be replaced by &str where possible.
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct Config {
     pub tool_name: &'static str,
 
     // Display license of this software or its dependencies.
     pub licenses: Vec<SelectedLicenses>,
 
+    // Format of the input image (enforced). If None, the format is determined from the content
+    // of the input, and from its extension as fallback.
+    pub forced_input_format: Option<String>,
+
     // Format to which an image will be converted (enforced).
     pub forced_output_format: Option<String>,
 
@@ -23,25 +30,27 @@ pub struct Config {
 }
 
 // TODO{}: This is suboptimal, since depending crates can't define their own types...
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub enum ConfigItem {
     OptionStringItem(Option<String>),
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub enum SelectedLicenses {
     ThisSoftware,
     Dependencies,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct FormatEncodingSettings {
     pub jpeg_settings: JPEGEncodingSettings,
 
+    pub png_settings: PNGEncodingSettings,
+
     pub pnm_settings: PNMEncodingSettings,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct JPEGEncodingSettings {
     // Valid values are actually 1...100 (inclusive)
     pub quality: u8,
@@ -52,16 +61,20 @@ impl JPEGEncodingSettings {
 
     // Param:
     // * quality: (present?, value)
-    pub fn new_result(quality: (bool, Option<&str>)) -> Result<JPEGEncodingSettings, String> {
+    pub fn new_result(quality: (bool, Option<&str>)) -> Result<JPEGEncodingSettings, Error> {
         let proposed_quality = match quality.1 {
-            Some(v) => v
-                .parse::<u8>()
-                .map_err(|_| "JPEG Encoding Settings error: QUALITY is not a valid number.".into()),
+            Some(v) => v.parse::<u8>().map_err(|_| Error::InvalidEncodingSettings {
+                setting: EncodingSetting::JPEGQuality,
+                value: Some(v.to_string()),
+            }),
             None if !quality.0 => Ok(JPEGEncodingSettings::JPEG_ENCODING_QUALITY_DEFAULT),
-            None => Err("JPEG Encoding Settings error: Unreachable".into()),
+            None => Err(Error::InvalidEncodingSettings {
+                setting: EncodingSetting::JPEGQuality,
+                value: None,
+            }),
         };
 
-        fn within_range(v: u8) -> Result<JPEGEncodingSettings, String> {
+        fn within_range(v: u8) -> Result<JPEGEncodingSettings, Error> {
             // Upper bound is exclusive with .. syntax.
             // When the `range_contains` feature will be stabilised Range.contains(&v)
             // should be used instead.
@@ -71,7 +84,10 @@ impl JPEGEncodingSettings {
 
                 Ok(res)
             } else {
-                Err("JPEG Encoding Settings error: --jpeg-encoding-quality requires a number between 1 and 100.".into())
+                Err(Error::InvalidEncodingSettings {
+                    setting: EncodingSetting::JPEGQuality,
+                    value: Some(v.to_string()),
+                })
             }
         }
 
@@ -79,15 +95,175 @@ impl JPEGEncodingSettings {
     }
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
+pub struct PNGEncodingSettings {
+    // Trades encoding speed for output size.
+    pub compression: PNGCompression,
+
+    // Filter applied to each scanline before compression.
+    pub filter: PNGFilterType,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PNGCompression {
+    Fast,
+    Default,
+    Best,
+    Huffman,
+    Rle,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PNGFilterType {
+    NoFilter,
+    Sub,
+    Up,
+    Avg,
+    Paeth,
+}
+
+impl PNGEncodingSettings {
+    // Param:
+    // * compression: (present?, value); one of: fast, default, best, huffman, rle
+    // * filter: (present?, value); one of: none, sub, up, avg, paeth
+    pub fn new_result(
+        compression: (bool, Option<&str>),
+        filter: (bool, Option<&str>),
+    ) -> Result<PNGEncodingSettings, Error> {
+        let compression = match compression.1 {
+            Some(v) => match v.to_lowercase().as_str() {
+                "fast" => Ok(PNGCompression::Fast),
+                "default" => Ok(PNGCompression::Default),
+                "best" => Ok(PNGCompression::Best),
+                "huffman" => Ok(PNGCompression::Huffman),
+                "rle" => Ok(PNGCompression::Rle),
+                _ => Err(Error::InvalidEncodingSettings {
+                    setting: EncodingSetting::PNGCompression,
+                    value: Some(v.to_string()),
+                }),
+            },
+            None if !compression.0 => Ok(PNGCompression::Default),
+            None => Err(Error::InvalidEncodingSettings {
+                setting: EncodingSetting::PNGCompression,
+                value: None,
+            }),
+        }?;
+
+        let filter = match filter.1 {
+            Some(v) => match v.to_lowercase().as_str() {
+                "none" => Ok(PNGFilterType::NoFilter),
+                "sub" => Ok(PNGFilterType::Sub),
+                "up" => Ok(PNGFilterType::Up),
+                "avg" => Ok(PNGFilterType::Avg),
+                "paeth" => Ok(PNGFilterType::Paeth),
+                _ => Err(Error::InvalidEncodingSettings {
+                    setting: EncodingSetting::PNGFilter,
+                    value: Some(v.to_string()),
+                }),
+            },
+            None if !filter.0 => Ok(PNGFilterType::Sub),
+            None => Err(Error::InvalidEncodingSettings {
+                setting: EncodingSetting::PNGFilter,
+                value: None,
+            }),
+        }?;
+
+        Ok(PNGEncodingSettings {
+            compression,
+            filter,
+        })
+    }
+}
+
+#[derive(Clone, Debug)]
 pub struct PNMEncodingSettings {
     // Use ascii for PBM, PGM or PPM. Not compatible with PAM.
     pub ascii: bool,
+
+    // How images are converted to black and white for PBM.
+    pub bitmap_conversion: BitmapConversion,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BitmapConversion {
+    // Pixels with a luminance at or above the threshold become white, the others black.
+    Threshold(u8),
+    Dither(Dither),
 }
 
 impl PNMEncodingSettings {
+    const PBM_THRESHOLD_DEFAULT: u8 = 128;
+
     pub fn new(ascii: bool) -> PNMEncodingSettings {
-        PNMEncodingSettings { ascii }
+        PNMEncodingSettings {
+            ascii,
+            bitmap_conversion: BitmapConversion::Threshold(
+                PNMEncodingSettings::PBM_THRESHOLD_DEFAULT,
+            ),
+        }
+    }
+
+    // Param:
+    // * bitmap_conversion: (present?, value); a threshold between 0 and 255, or one of:
+    //   floyd-steinberg, bayer
+    pub fn new_result(
+        ascii: bool,
+        bitmap_conversion: (bool, Option<&str>),
+    ) -> Result<PNMEncodingSettings, Error> {
+        let bitmap_conversion = match bitmap_conversion.1 {
+            Some(v) => v
+                .parse::<u8>()
+                .map(BitmapConversion::Threshold)
+                .or_else(|_| Dither::try_from_str(v).map(BitmapConversion::Dither))
+                .map_err(|_| Error::InvalidEncodingSettings {
+                    setting: EncodingSetting::PBMConversion,
+                    value: Some(v.to_string()),
+                }),
+            None if !bitmap_conversion.0 => Ok(BitmapConversion::Threshold(
+                PNMEncodingSettings::PBM_THRESHOLD_DEFAULT,
+            )),
+            None => Err(Error::InvalidEncodingSettings {
+                setting: EncodingSetting::PBMConversion,
+                value: None,
+            }),
+        }?;
+
+        Ok(PNMEncodingSettings {
+            ascii,
+            bitmap_conversion,
+        })
+    }
+}
+
+// The encoding settings which can be given as a string, e.g. from a command line flag.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EncodingSetting {
+    JPEGQuality,
+    PNGCompression,
+    PNGFilter,
+    PBMConversion,
+}
+
+impl EncodingSetting {
+    pub fn flag(self) -> &'static str {
+        match self {
+            EncodingSetting::JPEGQuality => "--jpeg-encoding-quality",
+            EncodingSetting::PNGCompression => "--png-compression",
+            EncodingSetting::PNGFilter => "--png-filter",
+            EncodingSetting::PBMConversion => "--pbm-conversion",
+        }
+    }
+
+    // The values which are accepted for the setting.
+    pub fn expected(self) -> &'static str {
+        match self {
+            EncodingSetting::JPEGQuality => "a number between 1 and 100",
+            EncodingSetting::PNGCompression => "one of: fast, default, best, huffman or rle",
+            EncodingSetting::PNGFilter => "one of: none, sub, up, avg or paeth",
+            EncodingSetting::PBMConversion => {
+                "a threshold between 0 and 255, floyd-steinberg or bayer"
+            }
+        }
     }
 }
 
@@ -118,4 +294,61 @@ mod tests {
         let value: &str = "101";
         assert!(JPEGEncodingSettings::new_result((true, Some(value))).is_err())
     }
+
+    #[test]
+    fn png_defaults() {
+        let settings = PNGEncodingSettings::new_result((false, None), (false, None)).unwrap();
+
+        assert_eq!(PNGCompression::Default, settings.compression);
+        assert_eq!(PNGFilterType::Sub, settings.filter);
+    }
+
+    #[test]
+    fn png_valid_values() {
+        let settings =
+            PNGEncodingSettings::new_result((true, Some("best")), (true, Some("Paeth"))).unwrap();
+
+        assert_eq!(PNGCompression::Best, settings.compression);
+        assert_eq!(PNGFilterType::Paeth, settings.filter);
+    }
+
+    #[test]
+    fn png_invalid_compression() {
+        assert!(PNGEncodingSettings::new_result((true, Some("9")), (false, None)).is_err())
+    }
+
+    #[test]
+    fn png_invalid_filter() {
+        assert!(PNGEncodingSettings::new_result((false, None), (true, Some("mixed"))).is_err())
+    }
+
+    #[test]
+    fn pnm_bitmap_conversion() {
+        let parse = |value| {
+            PNMEncodingSettings::new_result(false, (true, Some(value)))
+                .map(|settings| settings.bitmap_conversion)
+        };
+
+        assert_eq!(
+            BitmapConversion::Threshold(128),
+            PNMEncodingSettings::new(false).bitmap_conversion
+        );
+        assert_eq!(BitmapConversion::Threshold(100), parse("100").unwrap());
+        assert_eq!(
+            BitmapConversion::Dither(Dither::FloydSteinberg),
+            parse("floyd-steinberg").unwrap()
+        );
+        assert_eq!(
+            BitmapConversion::Dither(Dither::Bayer),
+            parse("Bayer").unwrap()
+        );
+        assert!(parse("256").is_err());
+        assert!(PNMEncodingSettings::new_result(false, (true, None)).is_err());
+    }
+
+    #[test]
+    fn png_present_without_value() {
+        assert!(PNGEncodingSettings::new_result((true, None), (false, None)).is_err());
+        assert!(PNGEncodingSettings::new_result((false, None), (true, None)).is_err());
+    }
 }
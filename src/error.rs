@@ -0,0 +1,489 @@
+use std::error;
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+use crate::config::EncodingSetting;
+use crate::operations::parse::ParseError;
+use crate::operations::transformations::tone::Levels;
+use crate::operations::OpArgKind;
+
+#[derive(Debug)]
+pub enum Error {
+    // Import & export
+    Decode(image::ImageError),
+    Encode(image::ImageError),
+    Io(io::Error),
+    EmptyInput,
+    MissingExtension,
+    MissingOutputPath,
+    UnsupportedInputFormat(String),
+    UnknownInputFormat,
+    UnsupportedOutputFormat(String),
+    InvalidEncodingSettings {
+        setting: EncodingSetting,
+        // None if the setting was given without a value.
+        value: Option<String>,
+    },
+    InvalidPath(PathBuf),
+
+    // Batch processing
+    InvalidGlobPattern(glob::PatternError),
+    InvalidNamingTemplate(String),
+    OutputPathCollision(PathBuf),
+    OutputPathIsInput(PathBuf),
+    #[cfg(feature = "parallel")]
+    ThreadPool(rayon::ThreadPoolBuildError),
+
+    // Operations
+    UnknownOperation {
+        name: String,
+        // The name of an existing operation which is close to `name`.
+        suggestion: Option<String>,
+    },
+    OperationArgumentMismatch {
+        name: String,
+        expected: OpArgKind,
+        given: OpArgKind,
+    },
+    UnknownSamplingFilter(String),
+    UnknownAnchor(String),
+    UnknownDither(String),
+    UnknownQuantizeMethod(String),
+    Parse(ParseError),
+    AutoLevelsInvalidClip(f32),
+    CropInvalidSelection {
+        selection: (u32, u32, u32, u32),
+    },
+    CropOutOfBounds {
+        selection: (u32, u32, u32, u32),
+        dimensions: (u32, u32),
+    },
+    CurveInvalidPoints(Vec<(f32, f32)>),
+    DitherInvalidLevels(u32),
+    GammaInvalid(f32),
+    LevelsInvalid(Levels),
+    OverlayImage {
+        path: PathBuf,
+        source: Box<Error>,
+    },
+    OverlayInvalidOpacity(f32),
+    PadOutOfRange {
+        dimensions: (u32, u32),
+        margins: (u32, u32, u32, u32),
+    },
+    PadCanvasTooSmall {
+        dimensions: (u32, u32),
+        canvas: (u32, u32),
+    },
+    QuantizeInvalidColors(u32),
+    ResizeInvalidPercentage(f32),
+    ResizeInvalidTarget(String),
+    RotateInvalidAngle(f32),
+    InvalidOperationName(String),
+    OperationAlreadyRegistered(String),
+    CustomOperation {
+        name: String,
+        source: Box<dyn error::Error + Send + Sync>,
+    },
+
+    // Engine
+    Cancelled {
+        // Amount of statements which completed before the run was cancelled.
+        completed: usize,
+    },
+
+    // History
+    HistoryDisabled,
+    HistoryMemoryLimitExceeded {
+        required: usize,
+        limit: usize,
+    },
+    UnknownSnapshot(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Decode(_) => write!(f, "Unable to decode image."),
+            Error::Encode(_) => write!(f, "Unable to encode image."),
+            Error::Io(_) => write!(f, "An I/O error occurred."),
+            Error::EmptyInput => write!(
+                f,
+                "Stdin was empty. To display the help page, use the `--help` flag."
+            ),
+            Error::MissingExtension => write!(
+                f,
+                "No extension was found, unable to determine the output format."
+            ),
+            Error::MissingOutputPath => write!(
+                f,
+                "No output path was given, unable to determine the output format."
+            ),
+            Error::UnsupportedInputFormat(format) => {
+                write!(f, "The input image format '{}' is not supported.", format)
+            }
+            Error::UnknownInputFormat => write!(
+                f,
+                "Unable to determine the input image format from its content or extension."
+            ),
+            Error::UnsupportedOutputFormat(format) => write!(
+                f,
+                "No supported image output format was found, input: {}.",
+                format
+            ),
+            Error::InvalidEncodingSettings {
+                setting,
+                value: Some(value),
+            } => write!(
+                f,
+                "Encoding settings error: {} requires {}, but was: {}.",
+                setting.flag(),
+                setting.expected(),
+                value
+            ),
+            Error::InvalidEncodingSettings {
+                setting,
+                value: None,
+            } => write!(
+                f,
+                "Encoding settings error: {} requires a value, {}.",
+                setting.flag(),
+                setting.expected()
+            ),
+            Error::InvalidPath(path) => write!(
+                f,
+                "The path '{}' is not valid unicode.",
+                path.display()
+            ),
+            Error::InvalidGlobPattern(_) => write!(f, "Invalid glob pattern."),
+            Error::InvalidNamingTemplate(msg) => write!(f, "Invalid naming template: {}", msg),
+            Error::OutputPathCollision(path) => write!(
+                f,
+                "The output path '{}' is used for more than one input.",
+                path.display()
+            ),
+            Error::OutputPathIsInput(path) => write!(
+                f,
+                "The output path '{}' is also an input, which would be overwritten.",
+                path.display()
+            ),
+            #[cfg(feature = "parallel")]
+            Error::ThreadPool(_) => write!(f, "Unable to create the worker pool."),
+            Error::UnknownOperation { name, suggestion } => {
+                write!(f, "No suitable operation was found for: {}.", name)?;
+
+                match suggestion {
+                    Some(suggestion) => write!(f, " Did you mean `{}`?", suggestion),
+                    None => Ok(()),
+                }
+            }
+            Error::OperationArgumentMismatch {
+                name,
+                expected,
+                given,
+            } => write!(
+                f,
+                "Operation: {} -- Expected arguments of the shape {}, but the given arguments have \
+                 the shape {}.",
+                name, expected, given
+            ),
+            Error::UnknownSamplingFilter(name) => write!(f, "No such sampling filter: {}", name),
+            Error::UnknownAnchor(name) => write!(f, "No such anchor: {}", name),
+            Error::UnknownDither(name) => write!(f, "No such dithering method: {}", name),
+            Error::UnknownQuantizeMethod(name) => {
+                write!(f, "No such quantization method: {}", name)
+            }
+            Error::Parse(_) => write!(f, "Unable to parse the script."),
+            Error::AutoLevelsInvalidClip(clip) => write!(
+                f,
+                "Operation: auto-levels -- The clipped percentage must be at least 0 and \
+                 below 50, but was: {}.",
+                clip
+            ),
+            Error::CropInvalidSelection {
+                selection: (lx, ly, rx, ry),
+            } => write!(
+                f,
+                "Operation: crop -- Top selection coordinates are smaller than bottom selection coordinates. \
+                 Required top selection < bottom selection but given coordinates are: \
+                 [top anchor: (x={}, y={}), bottom anchor: (x={}, y={})].",
+                lx, ly, rx, ry
+            ),
+            Error::CropOutOfBounds {
+                selection: (lx, ly, rx, ry),
+                dimensions: (dim_x, dim_y),
+            } => write!(
+                f,
+                "Operation: crop -- Top or bottom selection coordinates out of bounds: selection is \
+                 [top anchor: (x={}, y={}), bottom anchor: (x={}, y={})] but max selection range is: \
+                 (x={}, y={}).",
+                lx, ly, rx, ry, dim_x, dim_y
+            ),
+            Error::CurveInvalidPoints(points) => write!(
+                f,
+                "Operation: curve -- A curve needs at least two control points between 0 and 1, \
+                 with increasing x coordinates, but was given: {:?}.",
+                points
+            ),
+            Error::DitherInvalidLevels(levels) => write!(
+                f,
+                "Operation: dither -- The amount of levels must be between 2 and 6, but was: {}.",
+                levels
+            ),
+            Error::GammaInvalid(gamma) => write!(
+                f,
+                "Operation: gamma -- Gamma must be larger than 0, but was: {}.",
+                gamma
+            ),
+            Error::LevelsInvalid(levels) => write!(
+                f,
+                "Operation: levels -- Black and white points must be between 0 and 1, the input \
+                 black point must be below the input white point, and the midtone must be \
+                 larger than 0, but were: input ({}, {}), midtone {}, output ({}, {}).",
+                levels.input_black,
+                levels.input_white,
+                levels.midtone,
+                levels.output_black,
+                levels.output_white
+            ),
+            Error::OverlayImage { path, .. } => write!(
+                f,
+                "Operation: overlay -- Unable to load the overlay image '{}'.",
+                path.display()
+            ),
+            Error::OverlayInvalidOpacity(opacity) => write!(
+                f,
+                "Operation: overlay -- Opacity must be between 0 and 1, but was: {}.",
+                opacity
+            ),
+            Error::PadOutOfRange {
+                dimensions: (width, height),
+                margins: (top, right, bottom, left),
+            } => write!(
+                f,
+                "Operation: pad -- The margins (top={}, right={}, bottom={}, left={}) are too \
+                 large for an image of {}x{}.",
+                top, right, bottom, left, width, height
+            ),
+            Error::PadCanvasTooSmall {
+                dimensions: (width, height),
+                canvas: (canvas_width, canvas_height),
+            } => write!(
+                f,
+                "Operation: pad-to -- The image of {}x{} doesn't fit within the padded \
+                 dimensions {}x{}.",
+                width, height, canvas_width, canvas_height
+            ),
+            Error::QuantizeInvalidColors(colors) => write!(
+                f,
+                "Operation: quantize -- The amount of colors must be between 1 and 256, but \
+                 was: {}.",
+                colors
+            ),
+            Error::ResizeInvalidPercentage(percentage) => write!(
+                f,
+                "Operation: resize-percent -- Percentage must be larger than 0, but was: {}.",
+                percentage
+            ),
+            Error::ResizeInvalidTarget(name) => write!(
+                f,
+                "Operation: {} -- The target width and height must be larger than 0.",
+                name
+            ),
+            Error::RotateInvalidAngle(degrees) => write!(
+                f,
+                "Operation: rotate -- The angle must be a finite amount of degrees, but was: {}.",
+                degrees
+            ),
+            Error::InvalidOperationName(name) => write!(
+                f,
+                "The operation name '{}' is not a single word, or is a keyword.",
+                name
+            ),
+            Error::OperationAlreadyRegistered(name) => write!(
+                f,
+                "An operation with the name '{}' already exists.",
+                name
+            ),
+            Error::CustomOperation { name, .. } => {
+                write!(f, "Operation: {} -- The operation failed.", name)
+            }
+            Error::Cancelled { completed } => write!(
+                f,
+                "The program was cancelled after {} statement(s).",
+                completed
+            ),
+            Error::HistoryDisabled => write!(
+                f,
+                "Snapshots require the engine to be created with a history."
+            ),
+            Error::HistoryMemoryLimitExceeded { required, limit } => write!(
+                f,
+                "Unable to keep a snapshot of {} bytes within the history memory limit of {} bytes.",
+                required, limit
+            ),
+            Error::UnknownSnapshot(name) => write!(f, "No such snapshot: {}", name),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::Decode(err) | Error::Encode(err) => Some(err),
+            Error::Io(err) => Some(err),
+            Error::Parse(err) => Some(err),
+            Error::InvalidGlobPattern(err) => Some(err),
+            Error::OverlayImage { source, .. } => Some(source.as_ref()),
+            Error::CustomOperation { source, .. } => Some(source.as_ref()),
+            #[cfg(feature = "parallel")]
+            Error::ThreadPool(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Error::Io(err)
+    }
+}
+
+impl From<ParseError> for Error {
+    fn from(err: ParseError) -> Self {
+        Error::Parse(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::error::Error as StdError;
+
+    use super::*;
+
+    // Asserts that `message` is shown exactly once when the error and all of its sources are
+    // displayed.
+    fn assert_message_once(err: &Error, message: &str) {
+        let mut chain = vec![err.to_string()];
+        let mut source = err.source();
+
+        while let Some(err) = source {
+            chain.push(err.to_string());
+            source = err.source();
+        }
+
+        let chain = chain.join(": ");
+        assert_eq!(1, chain.matches(message).count(), "{}", chain);
+    }
+
+    fn io_error() -> io::Error {
+        io::Error::new(io::ErrorKind::NotFound, "gone")
+    }
+
+    #[test]
+    fn source_decode() {
+        let err = Error::Decode(image::ImageError::FormatError("bad header".into()));
+
+        assert_eq!("Unable to decode image.", err.to_string());
+        assert_message_once(&err, "bad header");
+    }
+
+    #[test]
+    fn source_encode() {
+        let err = Error::Encode(image::ImageError::FormatError("bad header".into()));
+
+        assert_eq!("Unable to encode image.", err.to_string());
+        assert_message_once(&err, "bad header");
+    }
+
+    #[test]
+    fn source_io() {
+        let err = Error::from(io_error());
+
+        assert_eq!("gone", err.source().unwrap().to_string());
+        assert_message_once(&err, "gone");
+    }
+
+    #[test]
+    fn source_parse() {
+        let parse_err = crate::operations::parse::parse_script("nope").unwrap_err();
+        let err = Error::from(parse_err.clone());
+
+        assert_eq!(parse_err.to_string(), err.source().unwrap().to_string());
+        assert_message_once(&err, &parse_err.to_string());
+    }
+
+    #[test]
+    fn source_invalid_glob_pattern() {
+        let pattern_err = glob::Pattern::new("[").unwrap_err();
+        let message = pattern_err.to_string();
+        let err = Error::InvalidGlobPattern(pattern_err);
+
+        assert_message_once(&err, &message);
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn source_thread_pool() {
+        // Only the first pool can become the global one.
+        let _ = rayon::ThreadPoolBuilder::new().build_global();
+        let pool_err = rayon::ThreadPoolBuilder::new().build_global().unwrap_err();
+        let message = pool_err.to_string();
+        let err = Error::ThreadPool(pool_err);
+
+        assert_message_once(&err, &message);
+    }
+
+    #[test]
+    fn source_overlay_image() {
+        let err = Error::OverlayImage {
+            path: PathBuf::from("top.png"),
+            source: Box::new(Error::from(io_error())),
+        };
+
+        assert_eq!(
+            "Operation: overlay -- Unable to load the overlay image 'top.png'.",
+            err.to_string()
+        );
+        assert_eq!("An I/O error occurred.", err.source().unwrap().to_string());
+        assert_message_once(&err, "gone");
+    }
+
+    #[test]
+    fn source_custom_operation() {
+        let err = Error::CustomOperation {
+            name: "halve".to_string(),
+            source: "image too small".into(),
+        };
+
+        assert_eq!("Operation: halve -- The operation failed.", err.to_string());
+        assert_message_once(&err, "image too small");
+    }
+
+    #[test]
+    fn no_source() {
+        let err = Error::CropOutOfBounds {
+            selection: (0, 0, 3, 3),
+            dimensions: (2, 2),
+        };
+
+        assert!(err.source().is_none());
+        assert!(err.to_string().contains("(x=2, y=2)"));
+    }
+
+    #[test]
+    fn invalid_encoding_settings() {
+        let err = Error::InvalidEncodingSettings {
+            setting: EncodingSetting::PNGFilter,
+            value: Some("mixed".to_string()),
+        };
+
+        assert!(err.source().is_none());
+        assert_eq!(
+            "Encoding settings error: --png-filter requires one of: none, sub, up, avg or \
+             paeth, but was: mixed.",
+            err.to_string()
+        );
+    }
+}
@@ -1,17 +1,74 @@
 use std::env::args;
+use std::fs;
 use std::io::stdin;
 use std::io::Read;
 use std::path::Path;
 
 use crate::config::Config;
+use crate::error::Error;
 use crate::processor::conversion::ConversionProcessor;
 use crate::processor::encoding_format::EncodingFormatDecider;
 use image;
 
 use crate::processor::ProcessWithConfig;
 
-pub fn import<P: AsRef<Path>>(maybe_path: Option<P>) -> Result<image::DynamicImage, String> {
-    maybe_path.map_or_else(import_from_input_stream_sync, import_from_file_sync)
+// How the format of an imported image was determined.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FormatSource {
+    // The format was given explicitly, by `Config::forced_input_format`.
+    Override,
+    // The format was recognized by the magic bytes at the start of the image data.
+    Content,
+    // The content wasn't recognized, so the format was derived from the file extension.
+    Extension,
+}
+
+pub struct ImportedImage {
+    pub image: image::DynamicImage,
+    pub format: image::ImageFormat,
+    pub format_source: FormatSource,
+}
+
+// Imports an image from the file at `maybe_path`, or from stdin if no path is given.
+//
+// Unless `config.forced_input_format` is set, the format is determined from the content of the
+// image first, and from the file extension only if the content isn't recognized.
+pub fn import<P: AsRef<Path>>(
+    maybe_path: Option<P>,
+    config: &Config,
+) -> Result<ImportedImage, Error> {
+    let forced_format = config
+        .forced_input_format
+        .as_ref()
+        .map(|format| input_format_from_str(format))
+        .transpose()?;
+
+    match maybe_path {
+        Some(path) => import_from_file_sync(path.as_ref(), forced_format),
+        None => import_from_input_stream_sync(forced_format),
+    }
+}
+
+// Imports an image from the file at `path`, detecting its format as `import` does without an
+// override.
+pub fn import_path<P: AsRef<Path>>(path: P) -> Result<ImportedImage, Error> {
+    import_from_file_sync(path.as_ref(), None)
+}
+
+fn input_format_from_str(identifier: &str) -> Result<image::ImageFormat, Error> {
+    match identifier.to_lowercase().as_str() {
+        "bmp" => Ok(image::ImageFormat::BMP),
+        "gif" => Ok(image::ImageFormat::GIF),
+        "hdr" => Ok(image::ImageFormat::HDR),
+        "ico" => Ok(image::ImageFormat::ICO),
+        "jpeg" | "jpg" => Ok(image::ImageFormat::JPEG),
+        "png" => Ok(image::ImageFormat::PNG),
+        "pbm" | "pgm" | "ppm" | "pam" | "pnm" => Ok(image::ImageFormat::PNM),
+        "tga" => Ok(image::ImageFormat::TGA),
+        "tif" | "tiff" => Ok(image::ImageFormat::TIFF),
+        "webp" => Ok(image::ImageFormat::WEBP),
+        _ => Err(Error::UnsupportedInputFormat(identifier.to_string())),
+    }
 }
 
 // TODO{foresterre}: Currently the method we use to read from the input stream is full blocking.
@@ -19,7 +76,9 @@ pub fn import<P: AsRef<Path>>(maybe_path: Option<P>) -> Result<image::DynamicIma
 //  Perhaps we would like to read the stdin with tokio-io (async).
 //  Then we can display an error and the help page instead if the 'Complete' event has been received,
 //  but the buffer is empty.
-fn import_from_input_stream_sync() -> Result<image::DynamicImage, String> {
+fn import_from_input_stream_sync(
+    forced_format: Option<image::ImageFormat>,
+) -> Result<ImportedImage, Error> {
     if cfg!(windows) {
         let program_name = args().nth(0).unwrap_or_default();
 
@@ -43,34 +102,202 @@ fn import_from_input_stream_sync() -> Result<image::DynamicImage, String> {
          signal has been received (usually you can send it by pressing Ctrl+D in your terminal)."
     );
 
-    stdin().lock().read_to_end(&mut buffer).map_err(|err| {
-        format!(
-            "Unable to read from the stdin. Message: {}",
-            err.to_string()
-        )
-    })?;
+    stdin().lock().read_to_end(&mut buffer)?;
 
     if buffer.is_empty() {
-        return Err("Stdin was empty. To display the help page, use the `--help` flag.".to_string());
+        return Err(Error::EmptyInput);
     }
 
     // Uses stderr because stdout is used to redirect the output image if no file is defined.
     eprintln!("Read {} bytes. Continuing.", buffer.len());
 
-    image::load_from_memory(&buffer).map_err(|err| err.to_string())
+    decode(&buffer, None, forced_format)
+}
+
+fn import_from_file_sync(
+    path: &Path,
+    forced_format: Option<image::ImageFormat>,
+) -> Result<ImportedImage, Error> {
+    let buffer = fs::read(path)?;
+
+    decode(&buffer, Some(path), forced_format)
 }
 
-fn import_from_file_sync<P: AsRef<Path>>(path: P) -> Result<image::DynamicImage, String> {
-    image::open(path).map_err(|err| err.to_string())
+fn decode(
+    buffer: &[u8],
+    path: Option<&Path>,
+    forced_format: Option<image::ImageFormat>,
+) -> Result<ImportedImage, Error> {
+    let (format, format_source) = match forced_format {
+        Some(format) => (format, FormatSource::Override),
+        None => detect_format(buffer, path)?,
+    };
+
+    let image = image::load_from_memory_with_format(buffer, format).map_err(|err| match err {
+        image::ImageError::IoError(err) => Error::Io(err),
+        err => Error::Decode(err),
+    })?;
+
+    Ok(ImportedImage {
+        image,
+        format,
+        format_source,
+    })
+}
+
+// Not all formats can be recognized by their content (e.g. TGA has no magic bytes), so the
+// extension is used as fallback.
+fn detect_format(
+    buffer: &[u8],
+    path: Option<&Path>,
+) -> Result<(image::ImageFormat, FormatSource), Error> {
+    if let Ok(format) = image::guess_format(buffer) {
+        return Ok((format, FormatSource::Content));
+    }
+
+    path.and_then(|path| image::ImageFormat::from_path(path).ok())
+        .map(|format| (format, FormatSource::Extension))
+        .ok_or(Error::UnknownInputFormat)
 }
 
 pub fn export(
     image: &image::DynamicImage,
     format_decider: &EncodingFormatDecider,
     config: &Config,
-) -> Result<(), String> {
-    format_decider.process(&config).and_then(|format| {
-        let conversion_processor = ConversionProcessor::new(&image, format);
-        conversion_processor.process(&config)
+) -> Result<(), Error> {
+    format_decider.process(config).and_then(|format| {
+        let conversion_processor = ConversionProcessor::new(image, format);
+        conversion_processor.process(config)
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::config::{
+        FormatEncodingSettings, JPEGEncodingSettings, PNGEncodingSettings, PNMEncodingSettings,
+    };
+
+    use super::*;
+
+    fn resource(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("resources")
+            .join(name)
+    }
+
+    fn setup_dummy_config(forced_input_format: Option<&str>) -> Config {
+        Config {
+            tool_name: env!("CARGO_PKG_NAME"),
+            licenses: vec![],
+            forced_input_format: forced_input_format.map(String::from),
+            forced_output_format: None,
+            disable_automatic_color_type_adjustment: false,
+            encoding_settings: FormatEncodingSettings {
+                jpeg_settings: JPEGEncodingSettings::new_result((false, None))
+                    .expect("Invalid jpeg settings"),
+                png_settings: PNGEncodingSettings::new_result((false, None), (false, None))
+                    .expect("Invalid png settings"),
+                pnm_settings: PNMEncodingSettings::new(false),
+            },
+            output: None,
+            application_specific: vec![],
+        }
+    }
+
+    // Copies a resource to `target/`, under a different file name.
+    fn setup_renamed_copy(resource_name: &str, name: &str) -> PathBuf {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("target")
+            .join(name);
+        fs::copy(resource(resource_name), &path).expect("Unable to copy test image.");
+        path
+    }
+
+    #[test]
+    fn detect_by_content() {
+        let imported = import(Some(resource("bwlines.png")), &setup_dummy_config(None)).unwrap();
+
+        assert_eq!(image::ImageFormat::PNG, imported.format);
+        assert_eq!(FormatSource::Content, imported.format_source);
+    }
+
+    #[test]
+    fn content_takes_precedence_over_extension() {
+        let path = setup_renamed_copy("bwlines.png", "io_png_with_jpg_extension.jpg");
+        let imported = import(Some(&path), &setup_dummy_config(None)).unwrap();
+
+        assert_eq!(image::ImageFormat::PNG, imported.format);
+        assert_eq!(FormatSource::Content, imported.format_source);
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn without_extension() {
+        let path = setup_renamed_copy("rainbow_8x6.bmp", "io_bmp_without_extension");
+        let imported = import(Some(&path), &setup_dummy_config(None)).unwrap();
+
+        assert_eq!(image::ImageFormat::BMP, imported.format);
+        assert_eq!((8, 6), image::GenericImageView::dimensions(&imported.image));
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn fallback_to_extension() {
+        // Uncompressed true color TGA of 1x1 pixels: a header of 18 bytes, followed by the
+        // pixel (BGR). TGA has no magic bytes.
+        let mut tga = vec![0, 0, 2, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 1, 0, 24, 0];
+        tga.extend_from_slice(&[10, 20, 30]);
+
+        let (format, source) = detect_format(&tga, Some(Path::new("image.tga"))).unwrap();
+
+        assert_eq!(image::ImageFormat::TGA, format);
+        assert_eq!(FormatSource::Extension, source);
+
+        let imported = decode(&tga, Some(Path::new("image.tga")), None).unwrap();
+        assert_eq!(
+            image::Rgba([30, 20, 10, 255]),
+            image::GenericImageView::get_pixel(&imported.image, 0, 0)
+        );
+
+        assert!(matches!(
+            detect_format(&tga, None),
+            Err(Error::UnknownInputFormat)
+        ));
+    }
+
+    #[test]
+    fn forced_format() {
+        let config = setup_dummy_config(Some("PNG"));
+        let imported = import(Some(resource("bwlines.png")), &config).unwrap();
+
+        assert_eq!(image::ImageFormat::PNG, imported.format);
+        assert_eq!(FormatSource::Override, imported.format_source);
+    }
+
+    #[test]
+    fn forced_format_mismatch() {
+        let config = setup_dummy_config(Some("bmp"));
+        let result = import(Some(resource("bwlines.png")), &config);
+
+        assert!(matches!(result, Err(Error::Decode(_))));
+    }
+
+    #[test]
+    fn forced_format_unsupported() {
+        let config = setup_dummy_config(Some("svg"));
+        let result = import(Some(resource("bwlines.png")), &config);
+
+        assert!(matches!(result, Err(Error::UnsupportedInputFormat(_))));
+    }
+
+    #[test]
+    fn missing_file() {
+        let result = import(Some(resource("missing.png")), &setup_dummy_config(None));
+
+        assert!(matches!(result, Err(Error::Io(_))));
+    }
+}
@@ -1,6 +1,11 @@
 pub use image;
 
+pub use error::Error;
+
+pub mod analysis;
+pub mod batch;
 pub mod config;
+pub mod error;
 pub mod io;
 pub mod operations;
 pub mod processor;
@@ -1,18 +1,46 @@
 /// This version of the operations module will use an AST like structure.
 /// Instead of evaluating a program, we apply 'a language' on an image.
 use std::collections::HashMap;
-use std::error::Error;
+use std::time::Instant;
 
 use image::DynamicImage;
 use image::GenericImageView;
 
+use crate::error::Error;
+use crate::io;
+use crate::operations::history::{History, State};
+use crate::operations::observer::{Flow, NoObserver, Observer, Progress};
+use crate::operations::profile::{ImageProfile, Profile};
+use crate::operations::registry::Registry;
+use crate::operations::transformations::dither::{self, Dither};
+use crate::operations::transformations::quantize::{self, QuantizeMethod};
+use crate::operations::transformations::resize::{self, ResizeMode};
+use crate::operations::transformations::tone::{self, Curve, Levels};
+use crate::operations::transformations::{normalize, overlay, pad, rotate, thumbnail};
 use crate::operations::wrapper::filter_type::FilterTypeWrap;
 use crate::operations::Operation;
 
 #[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum EnvironmentKind {
+    #[cfg_attr(feature = "serde", serde(rename = "pad-fill-color"))]
+    OptPadFillColor,
+    #[cfg_attr(feature = "serde", serde(rename = "quantize-dither"))]
+    OptQuantizeDither,
+    #[cfg_attr(feature = "serde", serde(rename = "quantize-method"))]
+    OptQuantizeMethod,
+    #[cfg_attr(feature = "serde", serde(rename = "resize-sampling-filter"))]
     OptResizeSamplingFilter,
+    #[cfg_attr(feature = "serde", serde(rename = "resize-preserve-aspect-ratio"))]
     OptResizePreserveAspectRatio,
+    #[cfg_attr(feature = "serde", serde(rename = "resize-shrink-only"))]
+    OptResizeShrinkOnly,
+    #[cfg_attr(feature = "serde", serde(rename = "rotate-sampling-filter"))]
+    OptRotateSamplingFilter,
+    #[cfg_attr(feature = "serde", serde(rename = "rotate-expand-canvas"))]
+    OptRotateExpandCanvas,
+    #[cfg_attr(feature = "serde", serde(rename = "rotate-fill-color"))]
+    OptRotateFillColor,
 }
 
 trait EnvironmentKey {
@@ -20,25 +48,92 @@ trait EnvironmentKey {
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(tag = "option", content = "value")
+)]
 pub enum EnvironmentItem {
+    // RGBA color used to fill the areas which are added by the pad operations.
+    #[cfg_attr(feature = "serde", serde(rename = "pad-fill-color"))]
+    PadFillColor([u8; 4]),
+    // Dithering applied when an image is mapped to the palette of the quantize operation.
+    #[cfg_attr(feature = "serde", serde(rename = "quantize-dither"))]
+    QuantizeDither(Dither),
+    #[cfg_attr(feature = "serde", serde(rename = "quantize-method"))]
+    QuantizeMethod(QuantizeMethod),
+    #[cfg_attr(feature = "serde", serde(rename = "resize-sampling-filter"))]
     OptResizeSamplingFilter(FilterTypeWrap),
+    #[cfg_attr(feature = "serde", serde(rename = "resize-preserve-aspect-ratio"))]
     PreserveAspectRatio,
+    // Resize operations leave an image which would become wider or taller as it is.
+    #[cfg_attr(feature = "serde", serde(rename = "resize-shrink-only"))]
+    ResizeShrinkOnly,
+    #[cfg_attr(feature = "serde", serde(rename = "rotate-sampling-filter"))]
+    OptRotateSamplingFilter(FilterTypeWrap),
+    #[cfg_attr(feature = "serde", serde(rename = "rotate-expand-canvas"))]
+    RotateExpandCanvas,
+    // RGBA color used to fill the areas which are not covered by a rotated image.
+    #[cfg_attr(feature = "serde", serde(rename = "rotate-fill-color"))]
+    RotateFillColor([u8; 4]),
 }
 
 impl EnvironmentItem {
+    pub fn pad_fill_color(&self) -> Option<[u8; 4]> {
+        match self {
+            EnvironmentItem::PadFillColor(color) => Some(*color),
+            _ => None,
+        }
+    }
+
+    pub fn quantize_dither(&self) -> Option<Dither> {
+        match self {
+            EnvironmentItem::QuantizeDither(dither) => Some(*dither),
+            _ => None,
+        }
+    }
+
+    pub fn quantize_method(&self) -> Option<QuantizeMethod> {
+        match self {
+            EnvironmentItem::QuantizeMethod(method) => Some(*method),
+            _ => None,
+        }
+    }
+
     pub fn resize_sampling_filter(&self) -> Option<FilterTypeWrap> {
         match self {
             EnvironmentItem::OptResizeSamplingFilter(k) => Some(k.clone()),
             _ => None,
         }
     }
+
+    pub fn rotate_sampling_filter(&self) -> Option<FilterTypeWrap> {
+        match self {
+            EnvironmentItem::OptRotateSamplingFilter(k) => Some(k.clone()),
+            _ => None,
+        }
+    }
+
+    pub fn rotate_fill_color(&self) -> Option<[u8; 4]> {
+        match self {
+            EnvironmentItem::RotateFillColor(color) => Some(*color),
+            _ => None,
+        }
+    }
 }
 
 impl EnvironmentKey for EnvironmentItem {
     fn key(&self) -> EnvironmentKind {
         match self {
+            EnvironmentItem::PadFillColor(_) => EnvironmentKind::OptPadFillColor,
+            EnvironmentItem::QuantizeDither(_) => EnvironmentKind::OptQuantizeDither,
+            EnvironmentItem::QuantizeMethod(_) => EnvironmentKind::OptQuantizeMethod,
             EnvironmentItem::OptResizeSamplingFilter(_) => EnvironmentKind::OptResizeSamplingFilter,
             EnvironmentItem::PreserveAspectRatio => EnvironmentKind::OptResizePreserveAspectRatio,
+            EnvironmentItem::ResizeShrinkOnly => EnvironmentKind::OptResizeShrinkOnly,
+            EnvironmentItem::OptRotateSamplingFilter(_) => EnvironmentKind::OptRotateSamplingFilter,
+            EnvironmentItem::RotateExpandCanvas => EnvironmentKind::OptRotateExpandCanvas,
+            EnvironmentItem::RotateFillColor(_) => EnvironmentKind::OptRotateFillColor,
         }
     }
 }
@@ -75,10 +170,18 @@ impl Environment {
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(tag = "type", content = "value")
+)]
 pub enum Statement {
+    #[cfg_attr(feature = "serde", serde(rename = "operation"))]
     Operation(Operation),
+    #[cfg_attr(feature = "serde", serde(rename = "set"))]
     RegisterEnvironmentItem(EnvironmentItem),
+    #[cfg_attr(feature = "serde", serde(rename = "del"))]
     DeregisterEnvironmentItem(EnvironmentKind),
 }
 
@@ -88,6 +191,9 @@ pub type Program = Vec<Statement>;
 pub struct ImageEngine {
     environment: Box<Environment>,
     image: Box<DynamicImage>,
+    history: Option<History>,
+    profile: Option<Profile>,
+    registry: Registry,
 }
 
 impl ImageEngine {
@@ -95,30 +201,218 @@ impl ImageEngine {
         Self {
             environment: Box::from(Environment::default()),
             image: Box::from(image),
+            history: None,
+            profile: None,
+            registry: Registry::default(),
+        }
+    }
+
+    // Creates an engine which keeps a history of the states in between statements, so they can
+    // be undone, redone or saved as a named snapshot.
+    // The images kept by the history take at most `memory_limit` bytes; if needed, the oldest
+    // undo steps are dropped to stay within this limit. A step of which the image can't fit at
+    // all isn't kept, and discards the other undo and redo steps, since they can't be reached
+    // without it.
+    pub fn with_history(image: DynamicImage, memory_limit: usize) -> Self {
+        Self {
+            history: Some(History::new(memory_limit)),
+            ..Self::new(image)
+        }
+    }
+
+    pub fn image(&self) -> &DynamicImage {
+        &self.image
+    }
+
+    // Makes the operations of `registry` available to the programs run by this engine.
+    pub fn set_registry(&mut self, registry: &Registry) {
+        self.registry = registry.clone();
+    }
+
+    fn set_state(&mut self, state: State) {
+        if let Some(image) = state.image {
+            *self.image = image;
+        }
+
+        *self.environment = state.environment;
+    }
+
+    // Reverts the last statement. Returns false if there is nothing to undo.
+    pub fn undo(&mut self) -> bool {
+        let history = match self.history.as_mut() {
+            Some(history) => history,
+            None => return false,
+        };
+
+        match history.undo(&self.image, &self.environment) {
+            Some(previous) => {
+                self.set_state(previous);
+                true
+            }
+            None => false,
         }
     }
 
-    pub fn ignite(&mut self, statements: Program) -> Result<&DynamicImage, Box<dyn Error>> {
-        for stmt in statements {
-            match self.process_statement(stmt) {
-                Ok(_) => continue,
-                Err(err) => return Err(err),
+    // Re-applies the last undone statement. Returns false if there is nothing to redo.
+    pub fn redo(&mut self) -> bool {
+        let history = match self.history.as_mut() {
+            Some(history) => history,
+            None => return false,
+        };
+
+        match history.redo(&self.image, &self.environment) {
+            Some(next) => {
+                self.set_state(next);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn can_undo(&self) -> bool {
+        matches!(&self.history, Some(history) if history.can_undo())
+    }
+
+    pub fn can_redo(&self) -> bool {
+        matches!(&self.history, Some(history) if history.can_redo())
+    }
+
+    // Saves the current image and environment under `name`, replacing an existing snapshot with
+    // the same name.
+    pub fn snapshot(&mut self, name: &str) -> Result<(), Error> {
+        let history = self.history.as_mut().ok_or(Error::HistoryDisabled)?;
+
+        history.snapshot(name, &self.image, &self.environment)
+    }
+
+    // Restores the snapshot saved under `name`. Restoring can itself be undone.
+    pub fn restore_snapshot(&mut self, name: &str) -> Result<(), Error> {
+        let history = self.history.as_mut().ok_or(Error::HistoryDisabled)?;
+
+        let snapshot = history.snapshot_state(name)?;
+        let current = history.capture(&self.image, &self.environment, true);
+        history.record(current);
+        self.set_state(snapshot);
+
+        Ok(())
+    }
+
+    // Returns whether a snapshot with the given name existed.
+    pub fn remove_snapshot(&mut self, name: &str) -> bool {
+        match self.history.as_mut() {
+            Some(history) => history.remove_snapshot(name),
+            None => false,
+        }
+    }
+
+    // Starts recording the time each statement takes and the images before and after it.
+    // Any previously recorded profile is discarded.
+    pub fn enable_profiling(&mut self) {
+        self.profile = Some(Profile::default());
+    }
+
+    pub fn disable_profiling(&mut self) {
+        self.profile = None;
+    }
+
+    // The statements profiled so far, or None if profiling isn't enabled.
+    pub fn profile(&self) -> Option<&Profile> {
+        self.profile.as_ref()
+    }
+
+    // Returns the statements profiled so far and starts a new profile.
+    pub fn take_profile(&mut self) -> Option<Profile> {
+        self.profile.as_mut().map(std::mem::take)
+    }
+
+    pub fn ignite(&mut self, statements: Program) -> Result<&DynamicImage, Error> {
+        self.ignite_with_observer(statements, &mut NoObserver)
+    }
+
+    // Like `ignite`, but notifies `observer` before and after each statement, which allows it to
+    // report progress or to cancel the run.
+    pub fn ignite_with_observer(
+        &mut self,
+        statements: Program,
+        observer: &mut dyn Observer,
+    ) -> Result<&DynamicImage, Error> {
+        let start = Instant::now();
+        let total = statements.len();
+
+        for (index, stmt) in statements.into_iter().enumerate() {
+            let progress = |elapsed| Progress {
+                index,
+                total,
+                elapsed,
+            };
+
+            if observer.before_statement(&stmt, progress(start.elapsed())) == Flow::Cancel {
+                return Err(Error::Cancelled { completed: index });
+            }
+
+            let statement_start = Instant::now();
+            self.process_statement(stmt.clone())?;
+            let duration = statement_start.elapsed();
+
+            if observer.after_statement(&stmt, progress(start.elapsed()), duration) == Flow::Cancel
+            {
+                return Err(Error::Cancelled {
+                    completed: index + 1,
+                });
             }
         }
 
         Ok(&self.image)
     }
 
-    pub fn process_statement(&mut self, statement: Statement) -> Result<(), Box<dyn Error>> {
-        match statement {
+    pub fn process_statement(&mut self, statement: Statement) -> Result<(), Error> {
+        // Statements which only change the environment don't need a copy of the image to be
+        // undone.
+        let keep_image = matches!(statement, Statement::Operation(_));
+        let previous = self
+            .history
+            .as_ref()
+            .and_then(|history| history.capture(&self.image, &self.environment, keep_image));
+
+        let profiled = self.profile.as_ref().map(|_| {
+            (
+                statement.clone(),
+                ImageProfile::of(&self.image),
+                Instant::now(),
+            )
+        });
+
+        let result = match statement {
             Statement::Operation(op) => self.process_operation(op),
             Statement::RegisterEnvironmentItem(item) => self.process_register_env(item),
             Statement::DeregisterEnvironmentItem(key) => self.process_deregister_env(key),
+        };
+
+        if let (Ok(()), Some((statement, input, start))) = (&result, profiled) {
+            let duration = start.elapsed();
+            let output = ImageProfile::of(&self.image);
+
+            if let Some(profile) = self.profile.as_mut() {
+                profile.record(statement, duration, input, output);
+            }
         }
+
+        // Failing statements leave the image untouched, so they don't count as a step.
+        if let (Ok(()), Some(history)) = (&result, self.history.as_mut()) {
+            history.record(previous);
+        }
+
+        result
     }
 
-    pub fn process_operation(&mut self, operation: Operation) -> Result<(), Box<dyn Error>> {
+    pub fn process_operation(&mut self, operation: Operation) -> Result<(), Error> {
         match operation {
+            Operation::AutoLevels(clip) => {
+                Verify::auto_levels_clip_within_range(clip)?;
+
+                *self.image = normalize::auto_levels(&self.image, clip);
+                Ok(())
+            }
             Operation::Blur(sigma) => {
                 *self.image = self.image.blur(sigma);
                 Ok(())
@@ -136,12 +430,48 @@ impl ImageEngine {
                 // 2. verify that the selection is within the bounds of the image
                 Verify::crop_selection_box_can_exist(lx, ly, rx, ry)
                     .and_then(|_| {
-                        Verify::crop_selection_within_image_bounds(&self.image, lx, ly, rx, ry)
+                        Verify::crop_selection_within_image_bounds(
+                            self.image.dimensions(),
+                            lx,
+                            ly,
+                            rx,
+                            ry,
+                        )
                     })
                     .map(|_| {
                         *self.image = self.image.crop(lx, ly, rx - lx, ry - ly);
                     })
             }
+            Operation::Custom(name, arg) => {
+                let operation = self.registry.resolve(&name, &arg)?;
+
+                *self.image = operation
+                    .apply(&self.image, &arg)
+                    .map_err(|source| Error::CustomOperation { name, source })?;
+
+                Ok(())
+            }
+            Operation::Curve(points) => {
+                let curve = Verify::curve_from_points(&points)?;
+
+                *self.image = tone::curve(&self.image, &curve);
+                Ok(())
+            }
+            Operation::Dither(method, levels) => {
+                Verify::dither_levels_within_range(levels)?;
+
+                let palette = dither::uniform_palette(levels);
+                *self.image = dither::map_to_palette(&self.image, &palette, Some(method));
+                Ok(())
+            }
+            Operation::Equalize => {
+                *self.image = normalize::equalize(&self.image);
+                Ok(())
+            }
+            Operation::EqualizeLuminance => {
+                *self.image = normalize::equalize_luminance(&self.image);
+                Ok(())
+            }
             // We need to ensure here that Filter3x3's `it` (&[f32]) has length 9.
             // Otherwise it will panic, see: https://docs.rs/image/0.19.0/src/image/dynimage.rs.html#349
             // This check already happens within the `parse` module.
@@ -157,6 +487,12 @@ impl ImageEngine {
                 *self.image = self.image.flipv();
                 Ok(())
             }
+            Operation::Gamma(gamma) => {
+                Verify::gamma_is_positive(gamma)?;
+
+                *self.image = tone::gamma(&self.image, gamma);
+                Ok(())
+            }
             Operation::GrayScale => {
                 *self.image = self.image.grayscale();
                 Ok(())
@@ -169,26 +505,124 @@ impl ImageEngine {
                 self.image.invert();
                 Ok(())
             }
-            Operation::Resize(new_x, new_y) => {
-                const DEFAULT_RESIZE_FILTER: image::FilterType = image::FilterType::Gaussian;
+            Operation::Levels(input_black, input_white, midtone, output_black, output_white) => {
+                let levels = Levels {
+                    input_black,
+                    input_white,
+                    midtone,
+                    output_black,
+                    output_white,
+                };
+                Verify::levels_are_valid(levels)?;
 
-                let filter = self
+                *self.image = tone::levels(&self.image, levels);
+                Ok(())
+            }
+            Operation::Overlay(path, placement, opacity) => {
+                Verify::overlay_opacity_within_range(opacity)?;
+
+                let top = io::import_path(&path).map_err(|err| Error::OverlayImage {
+                    path: path.into(),
+                    source: Box::new(err),
+                })?;
+
+                *self.image = overlay::overlay(&self.image, &top.image, placement, opacity);
+                Ok(())
+            }
+            Operation::Pad(top, right, bottom, left) => {
+                let margins = (top, right, bottom, left);
+                let canvas = Verify::pad_margins_within_range(self.image.dimensions(), margins)?;
+
+                self.pad(canvas, (left, top))
+            }
+            Operation::PadTo(width, height, anchor) => {
+                let dimensions = self.image.dimensions();
+                Verify::pad_canvas_contains_image(dimensions, (width, height))?;
+
+                let offset = pad::anchor_offset(anchor, (width, height), dimensions);
+                self.pad((width, height), offset)
+            }
+            Operation::Quantize(colors) => {
+                Verify::quantize_colors_within_range(colors)?;
+
+                let method = self
                     .environment
-                    .get(EnvironmentKind::OptResizeSamplingFilter)
-                    .and_then(|item| item.resize_sampling_filter())
-                    .map(image::FilterType::from)
-                    .unwrap_or(DEFAULT_RESIZE_FILTER);
+                    .get(EnvironmentKind::OptQuantizeMethod)
+                    .and_then(EnvironmentItem::quantize_method)
+                    .unwrap_or(QuantizeMethod::MedianCut);
+                let dither = self
+                    .environment
+                    .get(EnvironmentKind::OptQuantizeDither)
+                    .and_then(EnvironmentItem::quantize_dither);
 
-                *self.image = if self
+                *self.image = quantize::quantize(&self.image, colors as usize, method, dither);
+                Ok(())
+            }
+            Operation::Resize(new_x, new_y) => {
+                let mode = if self
                     .environment
                     .get(EnvironmentKind::OptResizePreserveAspectRatio)
                     .is_some()
                 {
-                    self.image.resize(new_x, new_y, filter)
+                    ResizeMode::Fit(new_x, new_y)
                 } else {
-                    self.image.resize_exact(new_x, new_y, filter)
+                    ResizeMode::Exact(new_x, new_y)
                 };
 
+                self.resize(mode)
+            }
+            Operation::ResizeFill(new_x, new_y) => {
+                Verify::resize_target_is_positive("resize-fill", &[new_x, new_y])?;
+                self.resize(ResizeMode::Fill(new_x, new_y))
+            }
+            Operation::ResizeFit(new_x, new_y) => {
+                Verify::resize_target_is_positive("resize-fit", &[new_x, new_y])?;
+                self.resize(ResizeMode::Fit(new_x, new_y))
+            }
+            Operation::ResizeHeight(new_y) => {
+                Verify::resize_target_is_positive("resize-height", &[new_y])?;
+                self.resize(ResizeMode::Height(new_y))
+            }
+            Operation::ResizePercent(percentage) => {
+                Verify::resize_percentage_is_positive(percentage)?;
+                self.resize(ResizeMode::Percentage(percentage))
+            }
+            Operation::ResizeWidth(new_x) => {
+                Verify::resize_target_is_positive("resize-width", &[new_x])?;
+                self.resize(ResizeMode::Width(new_x))
+            }
+            Operation::Rotate(degrees) => {
+                Verify::rotate_angle_is_finite(degrees)?;
+
+                const DEFAULT_ROTATE_FILTER: image::FilterType = image::FilterType::Triangle;
+                const DEFAULT_ROTATE_FILL_COLOR: [u8; 4] = [0, 0, 0, 0];
+
+                let filter = self
+                    .environment
+                    .get(EnvironmentKind::OptRotateSamplingFilter)
+                    .and_then(|item| item.rotate_sampling_filter())
+                    .map(image::FilterType::from)
+                    .unwrap_or(DEFAULT_ROTATE_FILTER);
+
+                let fill = self
+                    .environment
+                    .get(EnvironmentKind::OptRotateFillColor)
+                    .and_then(|item| item.rotate_fill_color())
+                    .unwrap_or(DEFAULT_ROTATE_FILL_COLOR);
+
+                let expand_canvas = self
+                    .environment
+                    .get(EnvironmentKind::OptRotateExpandCanvas)
+                    .is_some();
+
+                *self.image = rotate::rotate(
+                    &self.image,
+                    degrees,
+                    filter,
+                    expand_canvas,
+                    image::Rgba(fill),
+                );
+
                 Ok(())
             }
             Operation::Rotate90 => {
@@ -203,6 +637,22 @@ impl ImageEngine {
                 *self.image = self.image.rotate270();
                 Ok(())
             }
+            Operation::Thumbnail(new_x, new_y) => {
+                Verify::resize_target_is_positive("thumbnail", &[new_x, new_y])?;
+
+                const DEFAULT_THUMBNAIL_FILTER: image::FilterType = image::FilterType::Triangle;
+
+                let filter = self.resize_sampling_filter(DEFAULT_THUMBNAIL_FILTER);
+                let dimensions = resize::output_dimensions(
+                    self.image.dimensions(),
+                    ResizeMode::Fit(new_x, new_y),
+                    self.resize_shrink_only(),
+                );
+
+                *self.image = thumbnail::thumbnail(&self.image, dimensions, filter);
+
+                Ok(())
+            }
             Operation::Unsharpen(sigma, threshold) => {
                 *self.image = self.image.unsharpen(sigma, threshold);
                 Ok(())
@@ -210,13 +660,52 @@ impl ImageEngine {
         }
     }
 
-    pub fn process_register_env(&mut self, item: EnvironmentItem) -> Result<(), Box<dyn Error>> {
+    fn pad(&mut self, canvas: (u32, u32), offset: (u32, u32)) -> Result<(), Error> {
+        const DEFAULT_PAD_FILL_COLOR: [u8; 4] = [0, 0, 0, 0];
+
+        let fill = self
+            .environment
+            .get(EnvironmentKind::OptPadFillColor)
+            .and_then(|item| item.pad_fill_color())
+            .unwrap_or(DEFAULT_PAD_FILL_COLOR);
+
+        *self.image = pad::pad(&self.image, canvas, offset, image::Rgba(fill));
+
+        Ok(())
+    }
+
+    fn resize(&mut self, mode: ResizeMode) -> Result<(), Error> {
+        const DEFAULT_RESIZE_FILTER: image::FilterType = image::FilterType::Gaussian;
+
+        let filter = self.resize_sampling_filter(DEFAULT_RESIZE_FILTER);
+        let shrink_only = self.resize_shrink_only();
+
+        *self.image = resize::resize(&self.image, mode, filter, shrink_only);
+
+        Ok(())
+    }
+
+    fn resize_sampling_filter(&mut self, default: image::FilterType) -> image::FilterType {
+        self.environment
+            .get(EnvironmentKind::OptResizeSamplingFilter)
+            .and_then(|item| item.resize_sampling_filter())
+            .map(image::FilterType::from)
+            .unwrap_or(default)
+    }
+
+    fn resize_shrink_only(&mut self) -> bool {
+        self.environment
+            .get(EnvironmentKind::OptResizeShrinkOnly)
+            .is_some()
+    }
+
+    pub fn process_register_env(&mut self, item: EnvironmentItem) -> Result<(), Error> {
         self.environment.insert_or_update(item);
 
         Ok(())
     }
 
-    pub fn process_deregister_env(&mut self, key: EnvironmentKind) -> Result<(), Box<dyn Error>> {
+    pub fn process_deregister_env(&mut self, key: EnvironmentKind) -> Result<(), Error> {
         let success = self.environment.remove(key);
 
         if success.is_none() {
@@ -230,42 +719,137 @@ impl ImageEngine {
     }
 }
 
-struct Verify;
+// Checks which are shared by the engine and `validate`.
+pub(crate) struct Verify;
 
 impl Verify {
-    fn crop_selection_box_can_exist(
+    pub(crate) fn auto_levels_clip_within_range(clip: f32) -> Result<(), Error> {
+        if (0.0..50.0).contains(&clip) {
+            Ok(())
+        } else {
+            Err(Error::AutoLevelsInvalidClip(clip))
+        }
+    }
+
+    pub(crate) fn crop_selection_box_can_exist(
         lx: u32,
         ly: u32,
         rx: u32,
         ry: u32,
-    ) -> Result<(), Box<dyn Error>> {
+    ) -> Result<(), Error> {
         if (rx <= lx) || (ry <= ly) {
-            Err(format!(
-                "Operation: crop -- Top selection coordinates are smaller than bottom selection coordinates. \
-            Required top selection < bottom selection but given coordinates are: [top anchor: (x={}, y={}), bottom anchor: (x={}, y={})].",
-                lx, ly, rx, ry
-            ).into())
+            Err(Error::CropInvalidSelection {
+                selection: (lx, ly, rx, ry),
+            })
         } else {
             Ok(())
         }
     }
 
-    fn crop_selection_within_image_bounds(
-        image: &DynamicImage,
+    pub(crate) fn crop_selection_within_image_bounds(
+        (dim_x, dim_y): (u32, u32),
         lx: u32,
         ly: u32,
         rx: u32,
         ry: u32,
-    ) -> Result<(), Box<dyn Error>> {
-        let (dim_x, dim_y) = image.dimensions();
-
+    ) -> Result<(), Error> {
         match (lx <= dim_x, ly <= dim_y, rx <= dim_x, ry <= dim_y) {
             (true, true, true, true) => Ok(()),
-            _ => {
-                println!("error expected");
-                Err(format!("Operation: crop -- Top or bottom selection coordinates out of bounds: selection is [top anchor: \
-                (x={}, y={}), bottom anchor: (x={}, y={})] but max selection range is: (x={}, y={}).", lx, ly, rx, ry, dim_x, dim_y).into())
-            }
+            _ => Err(Error::CropOutOfBounds {
+                selection: (lx, ly, rx, ry),
+                dimensions: (dim_x, dim_y),
+            }),
+        }
+    }
+
+    pub(crate) fn curve_from_points(points: &[(f32, f32)]) -> Result<Curve, Error> {
+        Curve::new(points).ok_or_else(|| Error::CurveInvalidPoints(points.to_vec()))
+    }
+
+    pub(crate) fn dither_levels_within_range(levels: u32) -> Result<(), Error> {
+        if (2..=6).contains(&levels) {
+            Ok(())
+        } else {
+            Err(Error::DitherInvalidLevels(levels))
+        }
+    }
+
+    pub(crate) fn gamma_is_positive(gamma: f32) -> Result<(), Error> {
+        if gamma.is_finite() && gamma > 0.0 {
+            Ok(())
+        } else {
+            Err(Error::GammaInvalid(gamma))
+        }
+    }
+
+    pub(crate) fn levels_are_valid(levels: Levels) -> Result<(), Error> {
+        if levels.is_valid() {
+            Ok(())
+        } else {
+            Err(Error::LevelsInvalid(levels))
+        }
+    }
+
+    pub(crate) fn overlay_opacity_within_range(opacity: f32) -> Result<(), Error> {
+        if (0.0..=1.0).contains(&opacity) {
+            Ok(())
+        } else {
+            Err(Error::OverlayInvalidOpacity(opacity))
+        }
+    }
+
+    // Returns the dimensions of the padded image.
+    pub(crate) fn pad_margins_within_range(
+        dimensions: (u32, u32),
+        margins: (u32, u32, u32, u32),
+    ) -> Result<(u32, u32), Error> {
+        pad::margins_dimensions(dimensions, margins).ok_or(Error::PadOutOfRange {
+            dimensions,
+            margins,
+        })
+    }
+
+    pub(crate) fn pad_canvas_contains_image(
+        dimensions: (u32, u32),
+        canvas: (u32, u32),
+    ) -> Result<(), Error> {
+        if dimensions.0 <= canvas.0 && dimensions.1 <= canvas.1 {
+            Ok(())
+        } else {
+            Err(Error::PadCanvasTooSmall { dimensions, canvas })
+        }
+    }
+
+    pub(crate) fn quantize_colors_within_range(colors: u32) -> Result<(), Error> {
+        if (1..=256).contains(&colors) {
+            Ok(())
+        } else {
+            Err(Error::QuantizeInvalidColors(colors))
+        }
+    }
+
+    pub(crate) fn resize_percentage_is_positive(percentage: f32) -> Result<(), Error> {
+        if percentage.is_finite() && percentage > 0.0 {
+            Ok(())
+        } else {
+            Err(Error::ResizeInvalidPercentage(percentage))
+        }
+    }
+
+    // A zero target width or height would leave an image without pixels.
+    pub(crate) fn resize_target_is_positive(name: &str, target: &[u32]) -> Result<(), Error> {
+        if target.iter().all(|&side| side > 0) {
+            Ok(())
+        } else {
+            Err(Error::ResizeInvalidTarget(name.to_string()))
+        }
+    }
+
+    pub(crate) fn rotate_angle_is_finite(degrees: f32) -> Result<(), Error> {
+        if degrees.is_finite() {
+            Ok(())
+        } else {
+            Err(Error::RotateInvalidAngle(degrees))
         }
     }
 }
@@ -366,7 +950,12 @@ mod environment_tests {
 mod tests {
     use image::GenericImageView;
 
+    use crate::analysis;
     use crate::operations::mod_test_includes::*;
+    use crate::operations::parse::{parse_script, parse_script_with_registry};
+    use crate::operations::placement::Anchor;
+    use crate::operations::registry::ImageOperation;
+    use crate::operations::{OpArg, OpArgKind};
 
     use super::*;
 
@@ -736,6 +1325,27 @@ mod tests {
         assert!(done.is_err());
     }
 
+    #[test]
+    fn test_crop_err_out_of_image_bounds_reports_selection() {
+        let img: DynamicImage = setup_test_image("resources/blackwhite_2x2.bmp");
+
+        let operation = Operation::Crop(0, 0, 1, 3);
+
+        let mut operator = ImageEngine::new(img);
+        let done = operator.ignite(vec![Statement::Operation(operation)]);
+
+        match done {
+            Err(Error::CropOutOfBounds {
+                selection,
+                dimensions,
+            }) => {
+                assert_eq!((0, 0, 1, 3), selection);
+                assert_eq!((2, 2), dimensions);
+            }
+            _ => panic!("Expected a CropOutOfBounds error."),
+        }
+    }
+
     #[test]
     fn test_filter3x3() {
         let img: DynamicImage = setup_default_test_image();
@@ -993,6 +1603,314 @@ mod tests {
         output_test_image_for_manual_inspection(&img_result, "target/test_scale_250x500.png")
     }
 
+    #[test]
+    fn test_rotate_keep_bounds() {
+        let img: DynamicImage = setup_default_test_image();
+        let cmp: DynamicImage = setup_default_test_image();
+        let operation = Operation::Rotate(1.7);
+
+        let (xa, ya) = img.dimensions();
+        let mut operator = ImageEngine::new(img);
+        let done = operator.ignite(vec![Statement::Operation(operation)]);
+
+        assert!(done.is_ok());
+
+        let img_result = done.unwrap();
+
+        assert_eq!((xa, ya), img_result.dimensions());
+        assert_ne!(cmp.raw_pixels(), img_result.raw_pixels());
+
+        output_test_image_for_manual_inspection(img_result, "target/test_rotate_1_7.png")
+    }
+
+    #[test]
+    fn test_rotate_expand_canvas_with_fill_color() {
+        let img: DynamicImage = setup_default_test_image();
+        let operations = vec![
+            Statement::RegisterEnvironmentItem(EnvironmentItem::RotateExpandCanvas),
+            Statement::RegisterEnvironmentItem(EnvironmentItem::RotateFillColor([0, 255, 0, 255])),
+            Statement::Operation(Operation::Rotate(45.0)),
+        ];
+
+        let mut operator = ImageEngine::new(img);
+        let done = operator.ignite(operations);
+
+        assert!(done.is_ok());
+
+        let img_result = done.unwrap();
+
+        // 217x447 => (217 + 447) * cos(45°) = 469.5..
+        assert_eq!((470, 470), img_result.dimensions());
+        assert_eq!(image::Rgba([0, 255, 0, 255]), img_result.get_pixel(0, 0));
+
+        output_test_image_for_manual_inspection(img_result, "target/test_rotate_45_expand.png")
+    }
+
+    #[test]
+    fn test_rotate_err_non_finite_angle() {
+        let img: DynamicImage = setup_default_test_image();
+        let mut operator = ImageEngine::new(img);
+
+        for &degrees in [f32::NAN, f32::INFINITY, f32::NEG_INFINITY].iter() {
+            assert!(matches!(
+                operator.process_operation(Operation::Rotate(degrees)),
+                Err(Error::RotateInvalidAngle(_))
+            ));
+        }
+
+        assert_eq!((217, 447), operator.image().dimensions());
+    }
+
+    #[test]
+    fn test_thumbnail_tiny_target() {
+        for &(dimensions, target, expected) in [
+            ((40, 30), (1, 1), (1, 1)),
+            ((1000, 2), (10, 10), (10, 1)),
+            ((2, 1000), (1, 1), (1, 1)),
+        ]
+        .iter()
+        {
+            let img = DynamicImage::new_rgb8(dimensions.0, dimensions.1);
+            let mut operator = ImageEngine::new(img);
+
+            operator
+                .process_operation(Operation::Thumbnail(target.0, target.1))
+                .unwrap();
+
+            assert_eq!(expected, operator.image().dimensions());
+        }
+    }
+
+    #[test]
+    fn test_resize_err_zero_target() {
+        let img: DynamicImage = setup_default_test_image();
+        let mut operator = ImageEngine::new(img);
+
+        for operation in vec![
+            Operation::ResizeFill(10, 0),
+            Operation::ResizeFit(0, 10),
+            Operation::ResizeWidth(0),
+            Operation::ResizeHeight(0),
+            Operation::Thumbnail(0, 10),
+        ] {
+            assert!(matches!(
+                operator.process_operation(operation),
+                Err(Error::ResizeInvalidTarget(_))
+            ));
+        }
+
+        assert_eq!((217, 447), operator.image().dimensions());
+    }
+
+    #[test]
+    fn test_pad_margins() {
+        let img: DynamicImage = setup_default_test_image();
+        let operations = vec![
+            Statement::RegisterEnvironmentItem(EnvironmentItem::PadFillColor([255, 0, 0, 255])),
+            Statement::Operation(Operation::Pad(1, 2, 3, 4)),
+        ];
+
+        let mut operator = ImageEngine::new(img.clone());
+        let img_result = operator.ignite(operations).unwrap();
+
+        assert_eq!((217 + 6, 447 + 4), img_result.dimensions());
+        assert_eq!(image::Rgba([255, 0, 0, 255]), img_result.get_pixel(3, 1));
+        assert_eq!(img.get_pixel(0, 0), img_result.get_pixel(4, 1));
+        assert_eq!(img.get_pixel(216, 446), img_result.get_pixel(220, 447));
+        assert_eq!(
+            image::Rgba([255, 0, 0, 255]),
+            img_result.get_pixel(221, 447)
+        );
+    }
+
+    #[test]
+    fn test_pad_to_letterbox() {
+        let img: DynamicImage = setup_default_test_image();
+        let operations = vec![
+            Statement::RegisterEnvironmentItem(EnvironmentItem::PadFillColor([0, 0, 255, 255])),
+            Statement::Operation(Operation::ResizeFit(100, 100)),
+            Statement::Operation(Operation::PadTo(100, 100, Anchor::Center)),
+        ];
+
+        let mut operator = ImageEngine::new(img);
+        let img_result = operator.ignite(operations).unwrap();
+
+        // 217x447 fits within 100x100 as 48x100, which is centered at x=26
+        assert_eq!((100, 100), img_result.dimensions());
+        assert_eq!(image::Rgba([0, 0, 255, 255]), img_result.get_pixel(25, 50));
+        assert_ne!(image::Rgba([0, 0, 255, 255]), img_result.get_pixel(26, 50));
+        assert_ne!(image::Rgba([0, 0, 255, 255]), img_result.get_pixel(73, 50));
+        assert_eq!(image::Rgba([0, 0, 255, 255]), img_result.get_pixel(74, 50));
+
+        output_test_image_for_manual_inspection(img_result, "target/test_pad_to_letterbox.png")
+    }
+
+    #[test]
+    fn test_tone_adjustments() {
+        let img: DynamicImage = setup_default_test_image();
+        let operations = vec![
+            Statement::Operation(Operation::Gamma(1.5)),
+            Statement::Operation(Operation::Levels(0.1, 0.9, 0.8, 0.05, 0.95)),
+            Statement::Operation(Operation::Curve(vec![(0.0, 0.0), (0.5, 0.6), (1.0, 1.0)])),
+        ];
+
+        let mut operator = ImageEngine::new(img.clone());
+        let img_result = operator.ignite(operations).unwrap();
+
+        assert_eq!(img.dimensions(), img_result.dimensions());
+        assert_eq!(img.color(), img_result.color());
+        assert_ne!(img.raw_pixels(), img_result.raw_pixels());
+
+        output_test_image_for_manual_inspection(img_result, "target/test_tone_adjustments.png")
+    }
+
+    #[test]
+    fn test_quantize_with_options() {
+        let img: DynamicImage = setup_default_test_image();
+        let program = parse_script(
+            "set quantize method k-means; set quantize dither floyd-steinberg; quantize 4",
+        )
+        .unwrap();
+
+        let mut operator = ImageEngine::new(img.clone());
+        let img_result = operator.ignite(program).unwrap();
+
+        let mut colors: Vec<_> = img_result.to_rgb().pixels().map(|pixel| pixel.0).collect();
+        colors.sort_unstable();
+        colors.dedup();
+
+        assert_eq!(img.dimensions(), img_result.dimensions());
+        assert!(colors.len() <= 4);
+
+        assert!(matches!(
+            operator.process_operation(Operation::Quantize(0)),
+            Err(Error::QuantizeInvalidColors(0))
+        ));
+        assert!(matches!(
+            operator.process_operation(Operation::Quantize(257)),
+            Err(Error::QuantizeInvalidColors(257))
+        ));
+    }
+
+    #[test]
+    fn test_dither() {
+        let img: DynamicImage = setup_default_test_image();
+        let program = parse_script("dither bayer 2").unwrap();
+
+        let mut operator = ImageEngine::new(img.clone());
+        let img_result = operator.ignite(program).unwrap();
+
+        assert_eq!(img.dimensions(), img_result.dimensions());
+        assert!(img_result.to_rgb().pixels().all(|pixel| pixel
+            .0
+            .iter()
+            .all(|&channel| channel == 0 || channel == 255)));
+
+        for &levels in [1, 7].iter() {
+            assert!(matches!(
+                operator.process_operation(Operation::Dither(Dither::Bayer, levels)),
+                Err(Error::DitherInvalidLevels(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_tone_adjustments_err_invalid_arguments() {
+        let img: DynamicImage = setup_default_test_image();
+        let mut operator = ImageEngine::new(img);
+
+        assert!(matches!(
+            operator.process_operation(Operation::Gamma(0.0)),
+            Err(Error::GammaInvalid(_))
+        ));
+        assert!(matches!(
+            operator.process_operation(Operation::Levels(0.9, 0.1, 1.0, 0.0, 1.0)),
+            Err(Error::LevelsInvalid(_))
+        ));
+        assert!(matches!(
+            operator.process_operation(Operation::Curve(vec![(0.5, 0.5)])),
+            Err(Error::CurveInvalidPoints(_))
+        ));
+    }
+
+    #[test]
+    fn test_normalize_exposure() {
+        let img: DynamicImage = setup_default_test_image();
+        let operations = vec![
+            Statement::Operation(Operation::Contrast(-50.0)),
+            Statement::Operation(Operation::AutoLevels(0.5)),
+            Statement::Operation(Operation::EqualizeLuminance),
+            Statement::Operation(Operation::Equalize),
+        ];
+
+        let mut operator = ImageEngine::new(img.clone());
+        let img_result = operator.ignite(operations).unwrap();
+
+        let histograms = analysis::histograms(img_result);
+        assert_eq!(img.color(), img_result.color());
+        assert!(histograms
+            .iter()
+            .all(|h| h.min() == Some(0) && h.max() == Some(255)));
+
+        output_test_image_for_manual_inspection(img_result, "target/test_normalize_exposure.png")
+    }
+
+    #[test]
+    fn test_auto_levels_err_clip_out_of_range() {
+        let img: DynamicImage = setup_default_test_image();
+        let mut operator = ImageEngine::new(img);
+
+        assert!(matches!(
+            operator.process_operation(Operation::AutoLevels(50.0)),
+            Err(Error::AutoLevelsInvalidClip(_))
+        ));
+        assert!(matches!(
+            operator.process_operation(Operation::AutoLevels(-1.0)),
+            Err(Error::AutoLevelsInvalidClip(_))
+        ));
+    }
+
+    #[test]
+    fn test_pad_to_err_canvas_too_small() {
+        let img: DynamicImage = setup_default_test_image();
+
+        let mut operator = ImageEngine::new(img);
+        let done = operator.ignite(vec![Statement::Operation(Operation::PadTo(
+            300,
+            300,
+            Anchor::Center,
+        ))]);
+
+        assert!(matches!(
+            done,
+            Err(Error::PadCanvasTooSmall {
+                dimensions: (217, 447),
+                canvas: (300, 300),
+            })
+        ));
+    }
+
+    #[test]
+    fn test_rotate_sampling_filter_nearest() {
+        let img: DynamicImage = setup_default_test_image();
+
+        let mut engine = ImageEngine::new(img);
+        let mut engine2 = engine.clone();
+
+        let cmp_left = engine.ignite(vec![
+            Statement::RegisterEnvironmentItem(EnvironmentItem::OptRotateSamplingFilter(
+                FilterTypeWrap::Inner(image::FilterType::Nearest),
+            )),
+            Statement::Operation(Operation::Rotate(30.0)),
+        ]);
+        let cmp_right = engine2.ignite(vec![Statement::Operation(Operation::Rotate(30.0))]);
+
+        assert_ne!(
+            cmp_left.unwrap().raw_pixels(),
+            cmp_right.unwrap().raw_pixels()
+        );
+    }
+
     #[test]
     fn test_rotate90() {
         let img: DynamicImage = setup_default_test_image();
@@ -1053,6 +1971,70 @@ mod tests {
         output_test_image_for_manual_inspection(&img_result, "target/test_rotate270.png")
     }
 
+    #[test]
+    fn test_overlay() {
+        use crate::operations::placement::{Anchor, Placement};
+
+        let img: DynamicImage = setup_default_test_image();
+        let operation = Operation::Overlay(
+            "resources/rainbow_8x6.bmp".to_string(),
+            Placement::Anchor(Anchor::BottomRight),
+            1.0,
+        );
+
+        let mut operator = ImageEngine::new(img.clone());
+        let done = operator.ignite(vec![Statement::Operation(operation)]);
+
+        let img_result = done.unwrap();
+        let top = setup_test_image("resources/rainbow_8x6.bmp");
+
+        assert_eq!(img.dimensions(), img_result.dimensions());
+        assert_eq!(img.get_pixel(0, 0), img_result.get_pixel(0, 0));
+        assert_eq!(top.get_pixel(7, 5), img_result.get_pixel(216, 446));
+
+        output_test_image_for_manual_inspection(img_result, "target/test_overlay.png")
+    }
+
+    #[test]
+    fn test_overlay_missing_image() {
+        use crate::operations::placement::Placement;
+
+        let img: DynamicImage = setup_default_test_image();
+        let operation = Operation::Overlay(
+            "resources/does_not_exist.png".to_string(),
+            Placement::Offset(0, 0),
+            0.5,
+        );
+
+        let mut operator = ImageEngine::new(img);
+        let done = operator.ignite(vec![Statement::Operation(operation)]);
+
+        match done {
+            Err(Error::OverlayImage { path, source }) => {
+                assert_eq!(std::path::Path::new("resources/does_not_exist.png"), path);
+                assert!(matches!(*source, Error::Io(_)));
+            }
+            other => panic!("Unexpected result: {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn test_overlay_invalid_opacity() {
+        use crate::operations::placement::Placement;
+
+        let img: DynamicImage = setup_default_test_image();
+        let operation = Operation::Overlay(
+            "resources/rainbow_8x6.bmp".to_string(),
+            Placement::Offset(0, 0),
+            1.5,
+        );
+
+        let mut operator = ImageEngine::new(img);
+        let done = operator.ignite(vec![Statement::Operation(operation)]);
+
+        assert!(matches!(done, Err(Error::OverlayInvalidOpacity(_))));
+    }
+
     #[test]
     fn test_unsharpen_pos() {
         let img: DynamicImage = setup_default_test_image();
@@ -1122,4 +2104,345 @@ mod tests {
 
         output_test_image_for_manual_inspection(&done_image, "target/test_multi.png")
     }
+
+    #[test]
+    fn test_undo_redo() {
+        let img: DynamicImage = setup_test_image("resources/rainbow_8x6.bmp");
+        let mut engine = ImageEngine::with_history(img.clone(), 1 << 20);
+
+        engine
+            .ignite(vec![
+                Statement::Operation(Operation::Resize(4, 3)),
+                Statement::Operation(Operation::Rotate90),
+            ])
+            .unwrap();
+        assert_eq!((3, 4), engine.image().dimensions());
+
+        assert!(engine.undo());
+        assert_eq!((4, 3), engine.image().dimensions());
+        assert!(engine.undo());
+        assert_eq!(img.raw_pixels(), engine.image().raw_pixels());
+        assert!(!engine.undo());
+
+        assert!(engine.redo());
+        assert!(engine.redo());
+        assert_eq!((3, 4), engine.image().dimensions());
+        assert!(!engine.redo());
+    }
+
+    #[test]
+    fn test_undo_environment() {
+        let img: DynamicImage = setup_test_image("resources/rainbow_8x6.bmp");
+        let mut engine = ImageEngine::with_history(img, 1 << 20);
+
+        engine
+            .process_statement(Statement::RegisterEnvironmentItem(
+                EnvironmentItem::PreserveAspectRatio,
+            ))
+            .unwrap();
+        assert!(engine.undo());
+
+        assert_eq!(
+            None,
+            engine
+                .environment
+                .get(EnvironmentKind::OptResizePreserveAspectRatio)
+        );
+    }
+
+    #[test]
+    fn test_failed_statement_is_not_a_step() {
+        let img: DynamicImage = setup_test_image("resources/rainbow_8x6.bmp");
+        let mut engine = ImageEngine::with_history(img, 1 << 20);
+
+        engine
+            .process_statement(Statement::Operation(Operation::Invert))
+            .unwrap();
+        let err = engine.process_statement(Statement::Operation(Operation::Crop(0, 0, 100, 100)));
+
+        assert!(err.is_err());
+        assert!(engine.undo());
+        assert!(!engine.can_undo());
+    }
+
+    #[test]
+    fn test_failed_statement_keeps_redo() {
+        let img: DynamicImage = setup_test_image("resources/rainbow_8x6.bmp");
+        let mut engine = ImageEngine::with_history(img, 1 << 20);
+
+        engine
+            .process_statement(Statement::Operation(Operation::Rotate90))
+            .unwrap();
+        assert!(engine.undo());
+
+        let err = engine.process_statement(Statement::Operation(Operation::Crop(0, 0, 100, 100)));
+
+        assert!(err.is_err());
+        assert!(engine.redo());
+        assert_eq!((6, 8), engine.image().dimensions());
+    }
+
+    #[test]
+    fn test_oversized_step_clears_history() {
+        // room for a single 8x6 RGB image
+        let img: DynamicImage = setup_test_image("resources/rainbow_8x6.bmp");
+        let mut engine = ImageEngine::with_history(img, 8 * 6 * 3);
+
+        engine
+            .ignite(vec![
+                Statement::Operation(Operation::Invert),
+                Statement::Operation(Operation::Resize(16, 12)),
+            ])
+            .unwrap();
+        assert!(engine.can_undo());
+
+        // the 16x12 image can't be kept, so the steps before it can't be reached anymore
+        engine
+            .process_statement(Statement::Operation(Operation::Resize(8, 6)))
+            .unwrap();
+        assert!(!engine.can_undo());
+    }
+
+    #[test]
+    fn test_oversized_step_after_environment_step_clears_history() {
+        // room for the environment only
+        let img: DynamicImage = setup_test_image("resources/rainbow_8x6.bmp");
+        let mut engine = ImageEngine::with_history(img, 0);
+
+        engine
+            .process_statement(Statement::RegisterEnvironmentItem(
+                EnvironmentItem::ResizeShrinkOnly,
+            ))
+            .unwrap();
+        assert!(engine.can_undo());
+
+        // undoing the environment step would otherwise keep the inverted image
+        engine
+            .process_statement(Statement::Operation(Operation::Invert))
+            .unwrap();
+        assert!(!engine.can_undo());
+        assert!(!engine.undo());
+    }
+
+    #[test]
+    fn test_undo_environment_keeps_image() {
+        let img: DynamicImage = setup_test_image("resources/rainbow_8x6.bmp");
+        let mut engine = ImageEngine::with_history(img, 0);
+
+        engine
+            .ignite(vec![
+                Statement::Operation(Operation::Invert),
+                Statement::RegisterEnvironmentItem(EnvironmentItem::ResizeShrinkOnly),
+            ])
+            .unwrap();
+        let inverted = engine.image().raw_pixels();
+
+        // with a memory limit of 0, only the environment step could be recorded
+        assert!(engine.undo());
+        assert!(!engine.can_undo());
+        assert_eq!(inverted, engine.image().raw_pixels());
+        assert_eq!(
+            None,
+            engine.environment.get(EnvironmentKind::OptResizeShrinkOnly)
+        );
+    }
+
+    #[test]
+    fn test_snapshots() {
+        let img: DynamicImage = setup_test_image("resources/rainbow_8x6.bmp");
+        let mut engine = ImageEngine::with_history(img.clone(), 1 << 20);
+
+        engine.snapshot("original").unwrap();
+        engine
+            .ignite(vec![
+                Statement::Operation(Operation::GrayScale),
+                Statement::Operation(Operation::Resize(2, 2)),
+            ])
+            .unwrap();
+
+        engine.restore_snapshot("original").unwrap();
+        assert_eq!(img.raw_pixels(), engine.image().raw_pixels());
+
+        // restoring is a step of its own
+        assert!(engine.undo());
+        assert_eq!((2, 2), engine.image().dimensions());
+
+        assert!(engine.remove_snapshot("original"));
+        assert!(matches!(
+            engine.restore_snapshot("original"),
+            Err(Error::UnknownSnapshot(_))
+        ));
+    }
+
+    #[test]
+    fn test_without_history() {
+        let img: DynamicImage = setup_test_image("resources/rainbow_8x6.bmp");
+        let mut engine = ImageEngine::new(img);
+
+        engine
+            .process_statement(Statement::Operation(Operation::Invert))
+            .unwrap();
+
+        assert!(!engine.can_undo());
+        assert!(!engine.undo());
+        assert!(matches!(engine.snapshot("a"), Err(Error::HistoryDisabled)));
+    }
+
+    // Records the notifications and cancels before the statement at `cancel_at`.
+    struct RecordingObserver {
+        events: Vec<(&'static str, usize, usize)>,
+        cancel_at: Option<usize>,
+    }
+
+    impl Observer for RecordingObserver {
+        fn before_statement(&mut self, _statement: &Statement, progress: Progress) -> Flow {
+            self.events.push(("before", progress.index, progress.total));
+
+            if self.cancel_at == Some(progress.index) {
+                Flow::Cancel
+            } else {
+                Flow::Continue
+            }
+        }
+
+        fn after_statement(
+            &mut self,
+            _statement: &Statement,
+            progress: Progress,
+            duration: std::time::Duration,
+        ) -> Flow {
+            assert!(duration <= progress.elapsed);
+            self.events.push(("after", progress.index, progress.total));
+
+            Flow::Continue
+        }
+    }
+
+    #[test]
+    fn test_observer_is_notified() {
+        let img: DynamicImage = setup_test_image("resources/rainbow_8x6.bmp");
+        let mut engine = ImageEngine::new(img);
+        let mut observer = RecordingObserver {
+            events: Vec::new(),
+            cancel_at: None,
+        };
+
+        engine
+            .ignite_with_observer(
+                vec![
+                    Statement::Operation(Operation::Invert),
+                    Statement::RegisterEnvironmentItem(EnvironmentItem::PreserveAspectRatio),
+                ],
+                &mut observer,
+            )
+            .unwrap();
+
+        assert_eq!(
+            vec![
+                ("before", 0, 2),
+                ("after", 0, 2),
+                ("before", 1, 2),
+                ("after", 1, 2)
+            ],
+            observer.events
+        );
+    }
+
+    #[test]
+    fn test_observer_cancels() {
+        let img: DynamicImage = setup_test_image("resources/rainbow_8x6.bmp");
+        let mut engine = ImageEngine::new(img);
+        let mut observer = RecordingObserver {
+            events: Vec::new(),
+            cancel_at: Some(1),
+        };
+
+        let result = engine.ignite_with_observer(
+            vec![
+                Statement::Operation(Operation::Resize(4, 4)),
+                Statement::Operation(Operation::Resize(2, 2)),
+            ],
+            &mut observer,
+        );
+
+        assert!(matches!(result, Err(Error::Cancelled { completed: 1 })));
+        assert_eq!(3, observer.events.len());
+
+        // the image of the last completed statement is kept
+        assert_eq!((4, 4), engine.image().dimensions());
+    }
+
+    // Halves the dimensions of an image; fails on images smaller than 2x2.
+    struct Halve;
+
+    impl ImageOperation for Halve {
+        fn name(&self) -> &str {
+            "halve"
+        }
+
+        fn arg_kind(&self) -> OpArgKind {
+            OpArgKind::Empty
+        }
+
+        fn apply(
+            &self,
+            image: &DynamicImage,
+            _arg: &OpArg,
+        ) -> Result<DynamicImage, Box<dyn std::error::Error + Send + Sync>> {
+            let (width, height) = image.dimensions();
+
+            if width < 2 || height < 2 {
+                return Err("image too small".into());
+            }
+
+            Ok(image.resize_exact(width / 2, height / 2, image::FilterType::Nearest))
+        }
+    }
+
+    #[test]
+    fn test_custom_operation() {
+        let mut registry = Registry::new();
+        registry.register(Halve).unwrap();
+
+        let img: DynamicImage = setup_test_image("resources/rainbow_8x6.bmp");
+        let mut engine = ImageEngine::new(img);
+        engine.set_registry(&registry);
+
+        let program = parse_script_with_registry("halve; fliph; halve", &registry).unwrap();
+        let done = engine.ignite(program).unwrap();
+        assert_eq!((2, 1), done.dimensions());
+
+        let err = engine
+            .process_statement(Statement::Operation(Operation::Custom(
+                "halve".to_string(),
+                OpArg::Empty,
+            )))
+            .unwrap_err();
+
+        assert!(matches!(err, Error::CustomOperation { .. }));
+        assert_eq!("Operation: halve -- The operation failed.", err.to_string());
+        assert_eq!(
+            "image too small",
+            std::error::Error::source(&err).unwrap().to_string()
+        );
+        assert_eq!((2, 1), engine.image().dimensions());
+    }
+
+    #[test]
+    fn test_unregistered_custom_operation() {
+        let mut registry = Registry::new();
+        registry.register(Halve).unwrap();
+
+        // the operation is registered, but not in the registry of the engine
+        let img: DynamicImage = setup_test_image("resources/rainbow_8x6.bmp");
+        let mut engine = ImageEngine::new(img);
+
+        let result = engine.process_statement(Statement::Operation(Operation::Custom(
+            "halve".to_string(),
+            OpArg::Empty,
+        )));
+
+        assert!(matches!(result, Err(Error::UnknownOperation { .. })));
+        assert!(parse_script("halve").is_err());
+    }
 }
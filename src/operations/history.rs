@@ -0,0 +1,408 @@
+use std::collections::{HashMap, VecDeque};
+
+use image::{DynamicImage, GenericImageView};
+
+use crate::error::Error;
+use crate::operations::engine::Environment;
+
+// Size of the pixel buffer of an image, in bytes.
+pub(crate) fn image_size(image: &DynamicImage) -> usize {
+    let (width, height) = image.dimensions();
+    let bits = u64::from(width) * u64::from(height) * u64::from(image.color().bits_per_pixel());
+
+    // A partially used last byte counts as a whole byte.
+    (bits / 8 + u64::from(bits % 8 != 0)) as usize
+}
+
+// The state of an `ImageEngine` in between two statements.
+#[derive(Clone)]
+pub(crate) struct State {
+    // None if the image is the same as in the adjacent state, e.g. around a statement which only
+    // changes the environment.
+    pub(crate) image: Option<DynamicImage>,
+    pub(crate) environment: Environment,
+}
+
+impl State {
+    fn size(&self) -> usize {
+        self.image.as_ref().map_or(0, image_size)
+    }
+}
+
+// Undo and redo stacks and named snapshots of the states of an `ImageEngine`.
+//
+// The images held by the history (undo and redo steps and snapshots together) never take more
+// than `memory_limit` bytes. When a new step doesn't fit, the oldest undo steps are dropped
+// first. Snapshots are only dropped on request.
+#[derive(Clone)]
+pub(crate) struct History {
+    memory_limit: usize,
+    undo: VecDeque<State>,
+    redo: Vec<State>,
+    snapshots: HashMap<String, State>,
+}
+
+impl History {
+    pub(crate) fn new(memory_limit: usize) -> Self {
+        History {
+            memory_limit,
+            undo: VecDeque::new(),
+            redo: Vec::new(),
+            snapshots: HashMap::new(),
+        }
+    }
+
+    // The state of an engine, or None if its image is kept but can't fit within the memory limit.
+    // The image is checked before it's copied, so an image which can't fit is never copied.
+    pub(crate) fn capture(
+        &self,
+        image: &DynamicImage,
+        environment: &Environment,
+        keep_image: bool,
+    ) -> Option<State> {
+        let image = if keep_image {
+            if image_size(image) > self.available() {
+                return None;
+            }
+
+            Some(image.clone())
+        } else {
+            None
+        };
+
+        Some(State {
+            image,
+            environment: environment.clone(),
+        })
+    }
+
+    // The memory which undo and redo steps may take; snapshots are only dropped on request.
+    fn available(&self) -> usize {
+        let snapshots: usize = self.snapshots.values().map(State::size).sum();
+
+        self.memory_limit.saturating_sub(snapshots)
+    }
+
+    fn memory_usage(&self) -> usize {
+        self.undo
+            .iter()
+            .chain(self.redo.iter())
+            .chain(self.snapshots.values())
+            .map(State::size)
+            .sum()
+    }
+
+    // Drops undo steps, oldest first, and then redo steps, furthest first, until `additional`
+    // bytes fit within the memory limit. Returns false, without dropping anything, if they can't
+    // fit even then.
+    fn make_room(&mut self, additional: usize) -> bool {
+        if additional > self.available() {
+            return false;
+        }
+
+        let mut usage = self.memory_usage();
+
+        while usage + additional > self.memory_limit {
+            let dropped = self.undo.pop_front().or_else(|| {
+                if self.redo.is_empty() {
+                    None
+                } else {
+                    Some(self.redo.remove(0))
+                }
+            });
+
+            match dropped {
+                Some(state) => usage -= state.size(),
+                None => return false,
+            }
+        }
+
+        true
+    }
+
+    // Records the state prior to a new step, as captured by `capture`, once the step succeeded.
+    // Any redo steps are discarded, since they don't follow from the new step. A state which
+    // couldn't be captured or doesn't fit discards the undo steps too: an undo step which only
+    // holds the environment keeps the current image, so undoing past the missing step would
+    // combine an image and environment which never existed together.
+    pub(crate) fn record(&mut self, state: Option<State>) {
+        self.redo.clear();
+
+        match state {
+            Some(state) if self.make_room(state.size()) => self.undo.push_back(state),
+            _ => self.undo.clear(),
+        }
+    }
+
+    // Returns the state prior to the last step; the current state becomes the next redo step.
+    pub(crate) fn undo(
+        &mut self,
+        image: &DynamicImage,
+        environment: &Environment,
+    ) -> Option<State> {
+        let previous = self.undo.pop_back()?;
+        let current = self.capture(image, environment, previous.image.is_some());
+
+        // Like `record`, a missing redo step makes the remaining ones unreachable.
+        match current {
+            Some(current) if self.make_room(current.size()) => self.redo.push(current),
+            _ => self.redo.clear(),
+        }
+
+        Some(previous)
+    }
+
+    // Returns the state after the last undone step; the current state becomes the next undo step.
+    pub(crate) fn redo(
+        &mut self,
+        image: &DynamicImage,
+        environment: &Environment,
+    ) -> Option<State> {
+        let next = self.redo.pop()?;
+        let current = self.capture(image, environment, next.image.is_some());
+
+        match current {
+            Some(current) if self.make_room(current.size()) => self.undo.push_back(current),
+            _ => self.undo.clear(),
+        }
+
+        Some(next)
+    }
+
+    pub(crate) fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub(crate) fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    pub(crate) fn snapshot(
+        &mut self,
+        name: &str,
+        image: &DynamicImage,
+        environment: &Environment,
+    ) -> Result<(), Error> {
+        // A snapshot which is replaced doesn't count towards the memory usage.
+        let replaced = self.snapshots.remove(name);
+        let size = image_size(image);
+
+        if self.make_room(size) {
+            let state = State {
+                image: Some(image.clone()),
+                environment: environment.clone(),
+            };
+
+            self.snapshots.insert(name.to_string(), state);
+            Ok(())
+        } else {
+            if let Some(replaced) = replaced {
+                self.snapshots.insert(name.to_string(), replaced);
+            }
+
+            Err(Error::HistoryMemoryLimitExceeded {
+                required: size,
+                limit: self.memory_limit,
+            })
+        }
+    }
+
+    pub(crate) fn snapshot_state(&self, name: &str) -> Result<State, Error> {
+        self.snapshots
+            .get(name)
+            .cloned()
+            .ok_or_else(|| Error::UnknownSnapshot(name.to_string()))
+    }
+
+    pub(crate) fn remove_snapshot(&mut self, name: &str) -> bool {
+        self.snapshots.remove(name).is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{ImageBuffer, Luma};
+
+    use super::*;
+
+    fn image(value: u8) -> DynamicImage {
+        DynamicImage::ImageLuma8(ImageBuffer::from_pixel(10, 10, Luma([value])))
+    }
+
+    fn state(value: u8) -> Option<State> {
+        Some(State {
+            image: Some(image(value)),
+            environment: Environment::default(),
+        })
+    }
+
+    fn value(state: &State) -> u8 {
+        state.image.as_ref().unwrap().get_pixel(0, 0)[0]
+    }
+
+    #[test]
+    fn image_size_by_color_type() {
+        let image = DynamicImage::ImageLuma8(ImageBuffer::new(3, 5));
+
+        assert_eq!(15, image_size(&image));
+        assert_eq!(60, image_size(&DynamicImage::ImageRgba8(image.to_rgba())));
+    }
+
+    #[test]
+    fn memory_limit_drops_oldest_undo_steps() {
+        // room for two states of 100 bytes
+        let mut history = History::new(250);
+        let env = Environment::default();
+
+        history.record(state(0));
+        history.record(state(1));
+        history.record(state(2));
+
+        let previous = history.undo(&image(3), &env).unwrap();
+        assert_eq!(2, value(&previous));
+        let previous = history.undo(&image(2), &env).unwrap();
+        assert_eq!(1, value(&previous));
+        assert!(history.undo(&image(1), &env).is_none());
+    }
+
+    #[test]
+    fn undo_larger_state_stays_within_limit() {
+        let mut history = History::new(250);
+        let large = DynamicImage::ImageLuma8(ImageBuffer::new(20, 10));
+
+        history.record(state(0));
+        history.record(state(1));
+        history.undo(&large, &Environment::default()).unwrap();
+
+        assert!(history.memory_usage() <= 250);
+        assert!(history.can_redo());
+        assert!(!history.can_undo());
+    }
+
+    #[test]
+    fn state_larger_than_limit_is_not_captured() {
+        let history = History::new(50);
+
+        assert!(history
+            .capture(&image(0), &Environment::default(), true)
+            .is_none());
+    }
+
+    #[test]
+    fn state_larger_than_limit_clears_history() {
+        let mut history = History::new(250);
+        let env = Environment::default();
+        let large = DynamicImage::ImageLuma8(ImageBuffer::new(30, 10));
+
+        history.record(state(0));
+        history.record(state(1));
+        history.undo(&image(2), &env).unwrap();
+        assert!(history.can_undo());
+        assert!(history.can_redo());
+
+        history.record(history.capture(&large, &env, true));
+
+        assert!(!history.can_undo());
+        assert!(!history.can_redo());
+    }
+
+    #[test]
+    fn undo_state_larger_than_limit_clears_redo() {
+        let mut history = History::new(250);
+        let env = Environment::default();
+        let large = DynamicImage::ImageLuma8(ImageBuffer::new(30, 10));
+
+        history.record(state(0));
+        history.record(state(1));
+        history.undo(&image(2), &env).unwrap();
+        history.undo(&large, &env).unwrap();
+
+        assert!(!history.can_redo());
+    }
+
+    #[test]
+    fn environment_state_keeps_no_image() {
+        let mut history = History::new(0);
+        let env = Environment::default();
+
+        history.record(history.capture(&image(0), &env, false));
+
+        assert!(history.can_undo());
+        assert!(history.undo(&image(0), &env).unwrap().image.is_none());
+        assert!(history.redo(&image(0), &env).unwrap().image.is_none());
+        assert_eq!(0, history.memory_usage());
+    }
+
+    #[test]
+    fn record_discards_redo() {
+        let mut history = History::new(1000);
+
+        history.record(state(0));
+        let previous = history.undo(&image(1), &Environment::default()).unwrap();
+        assert!(history.can_redo());
+
+        history.record(Some(previous));
+
+        assert!(!history.can_redo());
+    }
+
+    #[test]
+    fn snapshot_over_limit() {
+        let mut history = History::new(150);
+        let env = Environment::default();
+
+        history.snapshot("a", &image(0), &env).unwrap();
+        let err = history.snapshot("b", &image(1), &env).unwrap_err();
+
+        assert!(matches!(
+            err,
+            Error::HistoryMemoryLimitExceeded {
+                required: 100,
+                limit: 150
+            }
+        ));
+
+        // replacing a snapshot frees its memory
+        history.snapshot("a", &image(2), &env).unwrap();
+        assert_eq!(2, value(&history.snapshot_state("a").unwrap()));
+    }
+
+    #[test]
+    fn snapshot_over_limit_keeps_history() {
+        let mut history = History::new(250);
+        let env = Environment::default();
+        let large = DynamicImage::ImageLuma8(ImageBuffer::new(30, 10));
+
+        history.record(state(0));
+        history.record(state(1));
+        history.undo(&image(2), &env).unwrap();
+
+        assert!(history.snapshot("a", &large, &env).is_err());
+        assert!(history.can_undo());
+        assert!(history.can_redo());
+    }
+
+    #[test]
+    fn snapshot_makes_room_by_dropping_undo_steps() {
+        let mut history = History::new(250);
+        let env = Environment::default();
+
+        history.record(state(0));
+        history.record(state(1));
+        history.snapshot("a", &image(2), &env).unwrap();
+
+        assert!(history.can_undo());
+        assert_eq!(1, value(&history.undo(&image(3), &env).unwrap()));
+    }
+
+    #[test]
+    fn unknown_snapshot() {
+        let mut history = History::new(1000);
+
+        assert!(matches!(
+            history.snapshot_state("a"),
+            Err(Error::UnknownSnapshot(_))
+        ));
+        assert!(!history.remove_snapshot("a"));
+    }
+}
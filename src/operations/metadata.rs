@@ -0,0 +1,575 @@
+// Machine readable descriptions of the built-in operations, from which front-ends can generate
+// help pages, shell completions and documentation.
+use std::fmt;
+use std::ops::Bound::{self, Excluded, Included, Unbounded};
+
+use crate::operations::parse::ValueKind;
+use crate::operations::{OpArgKind, Operation};
+
+// Values outside of the range are rejected by the engine or aren't meaningful.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ValueRange {
+    pub min: Bound<f64>,
+    pub max: Bound<f64>,
+}
+
+impl fmt::Display for ValueRange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (self.min, self.max) {
+            (Included(min), Included(max)) => write!(f, "between {} and {}", min, max),
+            (Unbounded, Unbounded) => write!(f, "any value"),
+            (min, max) => {
+                let min = match min {
+                    Included(min) => Some(format!("at least {}", min)),
+                    Excluded(min) => Some(format!("larger than {}", min)),
+                    Unbounded => None,
+                };
+                let max = match max {
+                    Included(max) => Some(format!("at most {}", max)),
+                    Excluded(max) => Some(format!("below {}", max)),
+                    Unbounded => None,
+                };
+                let bounds: Vec<String> = min.into_iter().chain(max).collect();
+
+                write!(f, "{}", bounds.join(" and "))
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ArgMetadata {
+    pub name: &'static str,
+    pub range: Option<ValueRange>,
+}
+
+// The built-in operations are defined by this metadata: the script parser and
+// `operation_by_name` accept an operation with the given name and arguments of `arg_kind`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct OperationMetadata {
+    pub name: &'static str,
+    pub arg_kind: OpArgKind,
+    // The arguments, in the order in which they appear in the `OpArg` of `arg_kind`.
+    pub args: &'static [ArgMetadata],
+    pub description: &'static str,
+    // A statement using the operation, in the script language.
+    pub example: &'static str,
+}
+
+impl OperationMetadata {
+    // The kinds of the values of the arguments, in the order of `args`, as expected by the
+    // script parser.
+    pub fn value_kinds(&self) -> Vec<ValueKind> {
+        match self.arg_kind {
+            OpArgKind::Empty => vec![],
+            OpArgKind::FloatingPoint => vec![ValueKind::FloatingPoint],
+            OpArgKind::Integer => vec![ValueKind::Integer],
+            OpArgKind::UnsignedInteger => vec![ValueKind::UnsignedInteger],
+            OpArgKind::UnsignedIntegerTuple2 => vec![ValueKind::UnsignedInteger; 2],
+            OpArgKind::UnsignedIntegerTuple2Anchor => vec![
+                ValueKind::UnsignedInteger,
+                ValueKind::UnsignedInteger,
+                ValueKind::Anchor,
+            ],
+            OpArgKind::UnsignedIntegerTuple4 => vec![ValueKind::UnsignedInteger; 4],
+            OpArgKind::FloatingPointArray9 => vec![ValueKind::FloatingPoint; 9],
+            OpArgKind::FloatingPointTuple5 => vec![ValueKind::FloatingPoint; 5],
+            OpArgKind::FloatingPointTuple2Vec => vec![ValueKind::ControlPoints],
+            OpArgKind::FloatingPointIntegerTuple2 => {
+                vec![ValueKind::FloatingPoint, ValueKind::Integer]
+            }
+            OpArgKind::PathPlacementFloatingPoint => vec![
+                ValueKind::Path,
+                ValueKind::Placement,
+                ValueKind::FloatingPoint,
+            ],
+            OpArgKind::DitherUnsignedInteger => vec![ValueKind::Dither, ValueKind::UnsignedInteger],
+        }
+    }
+
+    // E.g. `resize <width> <height>`.
+    pub fn usage(&self) -> String {
+        self.args.iter().fold(self.name.to_string(), |usage, arg| {
+            format!("{} <{}>", usage, arg.name)
+        })
+    }
+}
+
+// Help text of the operation: its usage, description, arguments and example.
+impl fmt::Display for OperationMetadata {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{}", self.usage())?;
+        writeln!(f, "    {}", self.description)?;
+
+        for (arg, kind) in self.args.iter().zip(self.value_kinds()) {
+            write!(f, "    <{}>: {}", arg.name, kind)?;
+
+            if let Some(range) = arg.range {
+                write!(f, ", {}", range)?;
+            }
+
+            writeln!(f)?;
+        }
+
+        writeln!(f, "    example: {}", self.example)
+    }
+}
+
+const fn arg(name: &'static str) -> ArgMetadata {
+    ArgMetadata { name, range: None }
+}
+
+const fn ranged_arg(name: &'static str, min: Bound<f64>, max: Bound<f64>) -> ArgMetadata {
+    ArgMetadata {
+        name,
+        range: Some(ValueRange { min, max }),
+    }
+}
+
+const KERNEL: [ArgMetadata; 9] = [
+    arg("k0"),
+    arg("k1"),
+    arg("k2"),
+    arg("k3"),
+    arg("k4"),
+    arg("k5"),
+    arg("k6"),
+    arg("k7"),
+    arg("k8"),
+];
+
+const OPERATIONS: &[OperationMetadata] = &[
+    OperationMetadata {
+        name: "auto-levels",
+        arg_kind: OpArgKind::FloatingPoint,
+        args: &[ranged_arg("clip", Included(0.0), Excluded(50.0))],
+        description: "Stretches each color channel to the full range, after clipping the given \
+                      percentage of the pixels at both ends of the channel to black and white. \
+                      The percentage must be below 50.",
+        example: "auto-levels 0.5",
+    },
+    OperationMetadata {
+        name: "blur",
+        arg_kind: OpArgKind::FloatingPoint,
+        args: &[ranged_arg("sigma", Included(0.0), Unbounded)],
+        description: "Blurs the image with a gaussian blur; a larger sigma blurs more.",
+        example: "blur 1.5",
+    },
+    OperationMetadata {
+        name: "brighten",
+        arg_kind: OpArgKind::Integer,
+        args: &[arg("amount")],
+        description: "Adds the amount to each color channel; a negative amount darkens the image.",
+        example: "brighten 20",
+    },
+    OperationMetadata {
+        name: "contrast",
+        arg_kind: OpArgKind::FloatingPoint,
+        args: &[arg("amount")],
+        description: "Adjusts the contrast; a negative amount decreases the contrast.",
+        example: "contrast 12.5",
+    },
+    OperationMetadata {
+        name: "crop",
+        arg_kind: OpArgKind::UnsignedIntegerTuple4,
+        args: &[arg("lx"), arg("ly"), arg("rx"), arg("ry")],
+        description: "Crops the image to the selection from the top left corner (lx, ly) up to \
+                      the bottom right corner (rx, ry), which should lie within the image.",
+        example: "crop 0 0 100 50",
+    },
+    OperationMetadata {
+        name: "curve",
+        arg_kind: OpArgKind::FloatingPointTuple2Vec,
+        args: &[arg("points")],
+        description: "Maps the tones of the color channels through a smooth, monotone curve \
+                      through the given control points; left of the first and right of the \
+                      last point, the curve is flat.",
+        example: "curve 0,0 0.25,0.2 0.75,0.8 1,1",
+    },
+    OperationMetadata {
+        name: "dither",
+        arg_kind: OpArgKind::DitherUnsignedInteger,
+        args: &[
+            arg("method"),
+            ranged_arg("levels", Included(2.0), Included(6.0)),
+        ],
+        description: "Dithers the image to a fixed palette, with the given amount of evenly \
+                      spaced levels for each color channel: 2 levels give black, white and the \
+                      primary and secondary colors, 6 levels the web-safe palette.",
+        example: "dither floyd-steinberg 2",
+    },
+    OperationMetadata {
+        name: "equalize",
+        arg_kind: OpArgKind::Empty,
+        args: &[],
+        description: "Equalizes the histogram of each color channel, which spreads the values \
+                      evenly over the full range, but may shift the hues.",
+        example: "equalize",
+    },
+    OperationMetadata {
+        name: "equalize-luminance",
+        arg_kind: OpArgKind::Empty,
+        args: &[],
+        description: "Equalizes the histogram of the luminance, and scales the color of each \
+                      pixel along, which keeps the hues intact.",
+        example: "equalize-luminance",
+    },
+    OperationMetadata {
+        name: "filter3x3",
+        arg_kind: OpArgKind::FloatingPointArray9,
+        args: &KERNEL,
+        description: "Convolves the image with the 3x3 kernel, given row by row.",
+        example: "filter3x3 0 -1 0 -1 5 -1 0 -1 0",
+    },
+    OperationMetadata {
+        name: "fliph",
+        arg_kind: OpArgKind::Empty,
+        args: &[],
+        description: "Flips the image horizontally.",
+        example: "fliph",
+    },
+    OperationMetadata {
+        name: "flipv",
+        arg_kind: OpArgKind::Empty,
+        args: &[],
+        description: "Flips the image vertically.",
+        example: "flipv",
+    },
+    OperationMetadata {
+        name: "gamma",
+        arg_kind: OpArgKind::FloatingPoint,
+        args: &[ranged_arg("gamma", Excluded(0.0), Unbounded)],
+        description: "Applies gamma correction to the color channels; a gamma larger than 1 \
+                      brightens the midtones, a gamma smaller than 1 darkens them.",
+        example: "gamma 2.2",
+    },
+    OperationMetadata {
+        name: "grayscale",
+        arg_kind: OpArgKind::Empty,
+        args: &[],
+        description: "Converts the image to grayscale.",
+        example: "grayscale",
+    },
+    OperationMetadata {
+        name: "huerotate",
+        arg_kind: OpArgKind::Integer,
+        args: &[arg("degrees")],
+        description: "Rotates the hue of each pixel by the given amount of degrees.",
+        example: "huerotate 90",
+    },
+    OperationMetadata {
+        name: "invert",
+        arg_kind: OpArgKind::Empty,
+        args: &[],
+        description: "Inverts the colors of the image.",
+        example: "invert",
+    },
+    OperationMetadata {
+        name: "levels",
+        arg_kind: OpArgKind::FloatingPointTuple5,
+        args: &[
+            ranged_arg("input-black", Included(0.0), Included(1.0)),
+            ranged_arg("input-white", Included(0.0), Included(1.0)),
+            ranged_arg("midtone", Excluded(0.0), Unbounded),
+            ranged_arg("output-black", Included(0.0), Included(1.0)),
+            ranged_arg("output-white", Included(0.0), Included(1.0)),
+        ],
+        description: "Maps the input black and white points of the color channels to the output \
+                      black and white points, with the midtone as gamma in between. The input \
+                      black point must be below the input white point.",
+        example: "levels 0.1 0.9 1.2 0 1",
+    },
+    OperationMetadata {
+        name: "overlay",
+        arg_kind: OpArgKind::PathPlacementFloatingPoint,
+        args: &[
+            arg("path"),
+            arg("placement"),
+            ranged_arg("opacity", Included(0.0), Included(1.0)),
+        ],
+        description: "Draws the image at the path on top of the image, at an anchor or offset.",
+        example: "overlay watermark.png bottom-right 0.5",
+    },
+    OperationMetadata {
+        name: "pad",
+        arg_kind: OpArgKind::UnsignedIntegerTuple4,
+        args: &[arg("top"), arg("right"), arg("bottom"), arg("left")],
+        description: "Extends the canvas by the given margins, which are filled with the pad \
+                      `fill-color` option (transparent black by default).",
+        example: "pad 10 20 10 20",
+    },
+    OperationMetadata {
+        name: "pad-to",
+        arg_kind: OpArgKind::UnsignedIntegerTuple2Anchor,
+        args: &[arg("width"), arg("height"), arg("anchor")],
+        description: "Extends the canvas to the given dimensions, with the image placed at the \
+                      anchor. The added area is filled with the pad `fill-color` option \
+                      (transparent black by default).",
+        example: "pad-to 128 128 center",
+    },
+    OperationMetadata {
+        name: "quantize",
+        arg_kind: OpArgKind::UnsignedInteger,
+        args: &[ranged_arg("colors", Included(1.0), Included(256.0))],
+        description: "Reduces the colors of the image to a palette of at most the given amount \
+                      of colors. The palette is chosen by median cut, or by k-means with \
+                      `set quantize method k-means`; `set quantize dither floyd-steinberg` or \
+                      `bayer` enables dithering.",
+        example: "quantize 16",
+    },
+    OperationMetadata {
+        name: "resize",
+        arg_kind: OpArgKind::UnsignedIntegerTuple2,
+        args: &[arg("width"), arg("height")],
+        description: "Resizes the image to the given dimensions, or to fit within them if the \
+                      `preserve-aspect-ratio` option is set. The dimensions may also be given \
+                      as a single `WxH` word.",
+        example: "resize 100 100",
+    },
+    OperationMetadata {
+        name: "resize-fill",
+        arg_kind: OpArgKind::UnsignedIntegerTuple2,
+        args: &[
+            ranged_arg("width", Included(1.0), Unbounded),
+            ranged_arg("height", Included(1.0), Unbounded),
+        ],
+        description: "Resizes the image, preserving its aspect ratio, such that it covers the \
+                      given dimensions, and crops what sticks out around the center.",
+        example: "resize-fill 100 100",
+    },
+    OperationMetadata {
+        name: "resize-fit",
+        arg_kind: OpArgKind::UnsignedIntegerTuple2,
+        args: &[
+            ranged_arg("width", Included(1.0), Unbounded),
+            ranged_arg("height", Included(1.0), Unbounded),
+        ],
+        description: "Resizes the image, preserving its aspect ratio, to the largest dimensions \
+                      which fit within the given dimensions.",
+        example: "resize-fit 100 100",
+    },
+    OperationMetadata {
+        name: "resize-height",
+        arg_kind: OpArgKind::UnsignedInteger,
+        args: &[ranged_arg("height", Included(1.0), Unbounded)],
+        description: "Resizes the image to the given height; the width follows from the aspect \
+                      ratio.",
+        example: "resize-height 100",
+    },
+    OperationMetadata {
+        name: "resize-percent",
+        arg_kind: OpArgKind::FloatingPoint,
+        args: &[ranged_arg("percentage", Excluded(0.0), Unbounded)],
+        description: "Resizes the width and height of the image by the given percentage, which \
+                      must be larger than 0.",
+        example: "resize-percent 50",
+    },
+    OperationMetadata {
+        name: "resize-width",
+        arg_kind: OpArgKind::UnsignedInteger,
+        args: &[ranged_arg("width", Included(1.0), Unbounded)],
+        description: "Resizes the image to the given width; the height follows from the aspect \
+                      ratio.",
+        example: "resize-width 100",
+    },
+    OperationMetadata {
+        name: "rotate",
+        arg_kind: OpArgKind::FloatingPoint,
+        args: &[arg("degrees")],
+        description: "Rotates the image clockwise by the given amount of degrees.",
+        example: "rotate 45",
+    },
+    OperationMetadata {
+        name: "rotate90",
+        arg_kind: OpArgKind::Empty,
+        args: &[],
+        description: "Rotates the image clockwise by 90 degrees.",
+        example: "rotate90",
+    },
+    OperationMetadata {
+        name: "rotate180",
+        arg_kind: OpArgKind::Empty,
+        args: &[],
+        description: "Rotates the image by 180 degrees.",
+        example: "rotate180",
+    },
+    OperationMetadata {
+        name: "rotate270",
+        arg_kind: OpArgKind::Empty,
+        args: &[],
+        description: "Rotates the image clockwise by 270 degrees.",
+        example: "rotate270",
+    },
+    OperationMetadata {
+        name: "thumbnail",
+        arg_kind: OpArgKind::UnsignedIntegerTuple2,
+        args: &[
+            ranged_arg("width", Included(1.0), Unbounded),
+            ranged_arg("height", Included(1.0), Unbounded),
+        ],
+        description: "Quickly downscales the image, preserving its aspect ratio, to the largest \
+                      dimensions which fit within the given dimensions. The last step uses the \
+                      resize sampling filter if set, and triangle otherwise.",
+        example: "thumbnail 128 128",
+    },
+    OperationMetadata {
+        name: "unsharpen",
+        arg_kind: OpArgKind::FloatingPointIntegerTuple2,
+        args: &[
+            ranged_arg("sigma", Included(0.0), Unbounded),
+            arg("threshold"),
+        ],
+        description: "Sharpens the image with an unsharp mask; sigma is the amount to blur the \
+                      mask by, and only differences larger than the threshold are sharpened.",
+        example: "unsharpen 1.5 3",
+    },
+];
+
+// The metadata of all built-in operations, sorted by name.
+pub fn all_operations() -> &'static [OperationMetadata] {
+    OPERATIONS
+}
+
+pub fn metadata_by_name(name: &str) -> Option<&'static OperationMetadata> {
+    OPERATIONS.iter().find(|metadata| metadata.name == name)
+}
+
+impl Operation {
+    // Name of the operation, as accepted by `operation_by_name`.
+    pub fn name(&self) -> &str {
+        match self {
+            Operation::AutoLevels(_) => "auto-levels",
+            Operation::Blur(_) => "blur",
+            Operation::Brighten(_) => "brighten",
+            Operation::Contrast(_) => "contrast",
+            Operation::Crop(..) => "crop",
+            Operation::Curve(_) => "curve",
+            Operation::Custom(name, _) => name,
+            Operation::Dither(..) => "dither",
+            Operation::Equalize => "equalize",
+            Operation::EqualizeLuminance => "equalize-luminance",
+            Operation::Filter3x3(_) => "filter3x3",
+            Operation::FlipHorizontal => "fliph",
+            Operation::FlipVertical => "flipv",
+            Operation::Gamma(_) => "gamma",
+            Operation::GrayScale => "grayscale",
+            Operation::HueRotate(_) => "huerotate",
+            Operation::Invert => "invert",
+            Operation::Levels(..) => "levels",
+            Operation::Overlay(..) => "overlay",
+            Operation::Pad(..) => "pad",
+            Operation::PadTo(..) => "pad-to",
+            Operation::Quantize(_) => "quantize",
+            Operation::Resize(..) => "resize",
+            Operation::ResizeFill(..) => "resize-fill",
+            Operation::ResizeFit(..) => "resize-fit",
+            Operation::ResizeHeight(_) => "resize-height",
+            Operation::ResizePercent(_) => "resize-percent",
+            Operation::ResizeWidth(_) => "resize-width",
+            Operation::Rotate(_) => "rotate",
+            Operation::Rotate90 => "rotate90",
+            Operation::Rotate180 => "rotate180",
+            Operation::Rotate270 => "rotate270",
+            Operation::Thumbnail(..) => "thumbnail",
+            Operation::Unsharpen(..) => "unsharpen",
+        }
+    }
+
+    // Metadata of the operation; None for custom operations.
+    pub fn metadata(&self) -> Option<&'static OperationMetadata> {
+        match self {
+            Operation::Custom(..) => None,
+            operation => metadata_by_name(operation.name()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::operations::engine::Statement;
+    use crate::operations::parse::parse_script;
+    use crate::operations::OpArg;
+
+    use super::*;
+
+    #[test]
+    fn metadata_matches_operations() {
+        let mut names: Vec<_> = OPERATIONS.iter().map(|m| m.name).collect();
+        names.sort_unstable();
+        names.dedup();
+        assert_eq!(OPERATIONS.len(), names.len());
+
+        for metadata in all_operations() {
+            assert_eq!(
+                metadata.value_kinds().len(),
+                metadata.args.len(),
+                "{}",
+                metadata.name
+            );
+
+            let example = parse_script(metadata.example).unwrap();
+            match example.as_slice() {
+                [Statement::Operation(operation)] => {
+                    assert_eq!(metadata.name, operation.name());
+                    assert_eq!(Some(metadata), operation.metadata());
+                }
+                _ => panic!("Example of {} isn't a single operation.", metadata.name),
+            }
+        }
+    }
+
+    #[test]
+    fn range_bounds() {
+        let clip = metadata_by_name("auto-levels").unwrap().args[0]
+            .range
+            .unwrap();
+        let gamma = metadata_by_name("gamma").unwrap().args[0].range.unwrap();
+        let colors = metadata_by_name("quantize").unwrap().args[0].range.unwrap();
+
+        assert_eq!("at least 0 and below 50", clip.to_string());
+        assert_eq!("larger than 0", gamma.to_string());
+        assert_eq!("between 1 and 256", colors.to_string());
+        assert_eq!(
+            "at most 1",
+            ValueRange {
+                min: Unbounded,
+                max: Included(1.0)
+            }
+            .to_string()
+        );
+    }
+
+    #[test]
+    fn readme_lists_every_operation() {
+        let readme = std::fs::read_to_string("README.md").unwrap();
+
+        for metadata in all_operations() {
+            let item = format!("* [x] `{}`", metadata.name);
+            assert!(readme.contains(&item), "{} is missing", metadata.name);
+        }
+    }
+
+    #[test]
+    fn custom_operation_has_no_metadata() {
+        let operation = Operation::Custom("brand-grade".to_string(), OpArg::Empty);
+
+        assert_eq!("brand-grade", operation.name());
+        assert_eq!(None, operation.metadata());
+    }
+
+    #[test]
+    fn help_text() {
+        let help = metadata_by_name("overlay").unwrap().to_string();
+        let lines: Vec<&str> = help.lines().collect();
+
+        assert_eq!("overlay <path> <placement> <opacity>", lines[0]);
+        assert_eq!(
+            "    <opacity>: a floating point number, between 0 and 1",
+            lines[4]
+        );
+        assert_eq!(
+            "    example: overlay watermark.png bottom-right 0.5",
+            lines[5]
+        );
+    }
+}
@@ -1,58 +1,281 @@
+use std::fmt;
+
+use crate::error::Error;
+use crate::operations::placement::{Anchor, Placement};
+use crate::operations::registry::Registry;
+use crate::operations::transformations::dither::Dither;
+
 #[cfg(test)]
 mod mod_test_includes;
 
 pub mod engine;
+pub(crate) mod history;
+pub mod metadata;
+pub mod observer;
+pub mod optimize;
+pub mod parse;
+pub mod placement;
+pub mod profile;
+pub mod registry;
+#[cfg(feature = "serde")]
+pub mod serialization;
+pub(crate) mod suggestion;
+pub mod transformations;
+pub mod validate;
 pub mod wrapper;
 
 #[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(tag = "op", content = "args")
+)]
 pub enum Operation {
+    // Percentage of the pixels clipped at each end of each color channel; see
+    // `normalize::auto_levels`.
+    #[cfg_attr(feature = "serde", serde(rename = "auto-levels"))]
+    AutoLevels(f32),
+    #[cfg_attr(feature = "serde", serde(rename = "blur"))]
     Blur(f32),
+    #[cfg_attr(feature = "serde", serde(rename = "brighten"))]
     Brighten(i32),
+    #[cfg_attr(feature = "serde", serde(rename = "contrast"))]
     Contrast(f32),
+    #[cfg_attr(feature = "serde", serde(rename = "crop"))]
     Crop(u32, u32, u32, u32),
+    // Control points (x, y) of a tone curve, between 0 and 1.
+    #[cfg_attr(feature = "serde", serde(rename = "curve"))]
+    Curve(Vec<(f32, f32)>),
+    // Name and argument of an operation from the `registry`.
+    #[cfg_attr(feature = "serde", serde(rename = "custom"))]
+    Custom(String, OpArg),
+    // Dithering method, and the amount of evenly spaced levels of each color channel of the
+    // palette, between 2 and 6.
+    #[cfg_attr(feature = "serde", serde(rename = "dither"))]
+    Dither(Dither, u32),
+    #[cfg_attr(feature = "serde", serde(rename = "equalize"))]
+    Equalize,
+    // Equalize the luminance, which keeps the hues intact.
+    #[cfg_attr(feature = "serde", serde(rename = "equalize-luminance"))]
+    EqualizeLuminance,
+    #[cfg_attr(feature = "serde", serde(rename = "filter3x3"))]
     Filter3x3([f32; 9]),
+    #[cfg_attr(feature = "serde", serde(rename = "fliph"))]
     FlipHorizontal,
+    #[cfg_attr(feature = "serde", serde(rename = "flipv"))]
     FlipVertical,
+    #[cfg_attr(feature = "serde", serde(rename = "gamma"))]
+    Gamma(f32),
+    #[cfg_attr(feature = "serde", serde(rename = "grayscale"))]
     GrayScale,
+    #[cfg_attr(feature = "serde", serde(rename = "huerotate"))]
     HueRotate(i32),
+    #[cfg_attr(feature = "serde", serde(rename = "invert"))]
     Invert,
+    // Input black and white point, midtone, output black and white point; see `tone::Levels`.
+    #[cfg_attr(feature = "serde", serde(rename = "levels"))]
+    Levels(f32, f32, f32, f32, f32),
+    // Path of the image drawn on top, its placement and its opacity (between 0 and 1).
+    #[cfg_attr(feature = "serde", serde(rename = "overlay"))]
+    Overlay(String, Placement, f32),
+    // Margins added around the image: top, right, bottom and left.
+    #[cfg_attr(feature = "serde", serde(rename = "pad"))]
+    Pad(u32, u32, u32, u32),
+    // Dimensions of the canvas the image is placed on, and the anchor it's placed at.
+    #[cfg_attr(feature = "serde", serde(rename = "pad-to"))]
+    PadTo(u32, u32, Anchor),
+    // Maximum amount of colors, between 1 and 256.
+    #[cfg_attr(feature = "serde", serde(rename = "quantize"))]
+    Quantize(u32),
+    #[cfg_attr(feature = "serde", serde(rename = "resize"))]
     Resize(u32, u32),
+    // Resize such that the image covers the given dimensions, then crop the overflow around
+    // the center.
+    #[cfg_attr(feature = "serde", serde(rename = "resize-fill"))]
+    ResizeFill(u32, u32),
+    // Resize such that the image fits within the given dimensions, preserving the aspect ratio.
+    #[cfg_attr(feature = "serde", serde(rename = "resize-fit"))]
+    ResizeFit(u32, u32),
+    #[cfg_attr(feature = "serde", serde(rename = "resize-height"))]
+    ResizeHeight(u32),
+    #[cfg_attr(feature = "serde", serde(rename = "resize-percent"))]
+    ResizePercent(f32),
+    #[cfg_attr(feature = "serde", serde(rename = "resize-width"))]
+    ResizeWidth(u32),
+    #[cfg_attr(feature = "serde", serde(rename = "rotate"))]
+    Rotate(f32),
+    #[cfg_attr(feature = "serde", serde(rename = "rotate90"))]
     Rotate90,
+    #[cfg_attr(feature = "serde", serde(rename = "rotate180"))]
     Rotate180,
+    #[cfg_attr(feature = "serde", serde(rename = "rotate270"))]
     Rotate270,
+    // Fast downscale to the largest dimensions which fit within the given dimensions.
+    #[cfg_attr(feature = "serde", serde(rename = "thumbnail"))]
+    Thumbnail(u32, u32),
+    #[cfg_attr(feature = "serde", serde(rename = "unsharpen"))]
     Unsharpen(f32, i32),
 }
 
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
 pub enum OpArg {
     Empty,
     FloatingPoint(f32),
     Integer(i32),
+    UnsignedInteger(u32),
     UnsignedIntegerTuple2(u32, u32),
+    UnsignedIntegerTuple2Anchor(u32, u32, Anchor),
     UnsignedIntegerTuple4(u32, u32, u32, u32),
     FloatingPointArray9([f32; 9]),
+    FloatingPointTuple5(f32, f32, f32, f32, f32),
+    FloatingPointTuple2Vec(Vec<(f32, f32)>),
     FloatingPointIntegerTuple2(f32, i32),
+    PathPlacementFloatingPoint(String, Placement, f32),
+    DitherUnsignedInteger(Dither, u32),
+}
+
+impl OpArg {
+    pub fn kind(&self) -> OpArgKind {
+        match self {
+            OpArg::Empty => OpArgKind::Empty,
+            OpArg::FloatingPoint(_) => OpArgKind::FloatingPoint,
+            OpArg::Integer(_) => OpArgKind::Integer,
+            OpArg::UnsignedInteger(_) => OpArgKind::UnsignedInteger,
+            OpArg::UnsignedIntegerTuple2(..) => OpArgKind::UnsignedIntegerTuple2,
+            OpArg::UnsignedIntegerTuple2Anchor(..) => OpArgKind::UnsignedIntegerTuple2Anchor,
+            OpArg::UnsignedIntegerTuple4(..) => OpArgKind::UnsignedIntegerTuple4,
+            OpArg::FloatingPointArray9(_) => OpArgKind::FloatingPointArray9,
+            OpArg::FloatingPointTuple5(..) => OpArgKind::FloatingPointTuple5,
+            OpArg::FloatingPointTuple2Vec(_) => OpArgKind::FloatingPointTuple2Vec,
+            OpArg::FloatingPointIntegerTuple2(..) => OpArgKind::FloatingPointIntegerTuple2,
+            OpArg::PathPlacementFloatingPoint(..) => OpArgKind::PathPlacementFloatingPoint,
+            OpArg::DitherUnsignedInteger(..) => OpArgKind::DitherUnsignedInteger,
+        }
+    }
+}
+
+// The shape of an `OpArg`, without its values.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum OpArgKind {
+    Empty,
+    FloatingPoint,
+    Integer,
+    UnsignedInteger,
+    UnsignedIntegerTuple2,
+    UnsignedIntegerTuple2Anchor,
+    UnsignedIntegerTuple4,
+    FloatingPointArray9,
+    FloatingPointTuple5,
+    FloatingPointTuple2Vec,
+    FloatingPointIntegerTuple2,
+    PathPlacementFloatingPoint,
+    DitherUnsignedInteger,
+}
+
+// Describes the shape like the README does, e.g. `[u32] [u32]`.
+impl fmt::Display for OpArgKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let shape = match self {
+            OpArgKind::Empty => "(no arguments)",
+            OpArgKind::FloatingPoint => "[f32]",
+            OpArgKind::Integer => "[i32]",
+            OpArgKind::UnsignedInteger => "[u32]",
+            OpArgKind::UnsignedIntegerTuple2 => "[u32] [u32]",
+            OpArgKind::UnsignedIntegerTuple2Anchor => "[u32] [u32] [anchor]",
+            OpArgKind::UnsignedIntegerTuple4 => "[u32] [u32] [u32] [u32]",
+            OpArgKind::FloatingPointArray9 => "[f32] x 9",
+            OpArgKind::FloatingPointTuple5 => "[f32] [f32] [f32] [f32] [f32]",
+            OpArgKind::FloatingPointTuple2Vec => "[f32],[f32] ...",
+            OpArgKind::FloatingPointIntegerTuple2 => "[f32] [i32]",
+            OpArgKind::PathPlacementFloatingPoint => "[path] [anchor | i32 i32] [f32]",
+            OpArgKind::DitherUnsignedInteger => "[dither] [u32]",
+        };
+
+        f.write_str(shape)
+    }
+}
+
+// The argument shape accepted by the built-in operation with the given name, as used by
+// `operation_by_name`. Use `Registry::arg_kind_by_name` to find registered operations as well.
+pub fn arg_kind_by_name(name: &str) -> Option<OpArgKind> {
+    builtin_arg_kind(name)
+}
+
+pub(crate) fn builtin_arg_kind(name: &str) -> Option<OpArgKind> {
+    metadata::metadata_by_name(name).map(|metadata| metadata.arg_kind)
+}
+
+// The built-in operation with the given name. Use `Registry::operation_by_name` to find
+// registered operations as well.
+pub fn operation_by_name(name: &str, value: OpArg) -> Result<Operation, Error> {
+    resolve_operation(name, value, &Registry::default())
 }
 
-pub fn operation_by_name(name: &str, value: OpArg) -> Result<Operation, String> {
+pub(crate) fn resolve_operation(
+    name: &str,
+    value: OpArg,
+    registry: &Registry,
+) -> Result<Operation, Error> {
     match (name, value) {
+        ("auto-levels", OpArg::FloatingPoint(v)) => Ok(Operation::AutoLevels(v)),
         ("blur", OpArg::FloatingPoint(v)) => Ok(Operation::Blur(v)),
         ("brighten", OpArg::Integer(v)) => Ok(Operation::Brighten(v)),
         ("contrast", OpArg::FloatingPoint(v)) => Ok(Operation::Contrast(v)),
         ("crop", OpArg::UnsignedIntegerTuple4(u0, u1, u2, u3)) => {
             Ok(Operation::Crop(u0, u1, u2, u3))
         }
+        ("curve", OpArg::FloatingPointTuple2Vec(points)) => Ok(Operation::Curve(points)),
+        ("dither", OpArg::DitherUnsignedInteger(method, levels)) => {
+            Ok(Operation::Dither(method, levels))
+        }
+        ("equalize", OpArg::Empty) => Ok(Operation::Equalize),
+        ("equalize-luminance", OpArg::Empty) => Ok(Operation::EqualizeLuminance),
         ("filter3x3", OpArg::FloatingPointArray9(v)) => Ok(Operation::Filter3x3(v)),
         ("fliph", OpArg::Empty) => Ok(Operation::FlipHorizontal),
         ("flipv", OpArg::Empty) => Ok(Operation::FlipVertical),
+        ("gamma", OpArg::FloatingPoint(v)) => Ok(Operation::Gamma(v)),
         ("grayscale", OpArg::Empty) => Ok(Operation::GrayScale),
         ("huerotate", OpArg::Integer(v)) => Ok(Operation::HueRotate(v)),
         ("invert", OpArg::Empty) => Ok(Operation::Invert),
+        ("levels", OpArg::FloatingPointTuple5(f0, f1, f2, f3, f4)) => {
+            Ok(Operation::Levels(f0, f1, f2, f3, f4))
+        }
+        ("overlay", OpArg::PathPlacementFloatingPoint(path, placement, opacity)) => {
+            Ok(Operation::Overlay(path, placement, opacity))
+        }
+        ("pad", OpArg::UnsignedIntegerTuple4(u0, u1, u2, u3)) => Ok(Operation::Pad(u0, u1, u2, u3)),
+        ("pad-to", OpArg::UnsignedIntegerTuple2Anchor(u0, u1, anchor)) => {
+            Ok(Operation::PadTo(u0, u1, anchor))
+        }
+        ("quantize", OpArg::UnsignedInteger(v)) => Ok(Operation::Quantize(v)),
         ("resize", OpArg::UnsignedIntegerTuple2(u0, u1)) => Ok(Operation::Resize(u0, u1)),
+        ("resize-fill", OpArg::UnsignedIntegerTuple2(u0, u1)) => Ok(Operation::ResizeFill(u0, u1)),
+        ("resize-fit", OpArg::UnsignedIntegerTuple2(u0, u1)) => Ok(Operation::ResizeFit(u0, u1)),
+        ("resize-height", OpArg::UnsignedInteger(v)) => Ok(Operation::ResizeHeight(v)),
+        ("resize-percent", OpArg::FloatingPoint(v)) => Ok(Operation::ResizePercent(v)),
+        ("resize-width", OpArg::UnsignedInteger(v)) => Ok(Operation::ResizeWidth(v)),
+        ("rotate", OpArg::FloatingPoint(v)) => Ok(Operation::Rotate(v)),
         ("rotate90", OpArg::Empty) => Ok(Operation::Rotate90),
         ("rotate180", OpArg::Empty) => Ok(Operation::Rotate180),
         ("rotate270", OpArg::Empty) => Ok(Operation::Rotate270),
+        ("thumbnail", OpArg::UnsignedIntegerTuple2(u0, u1)) => Ok(Operation::Thumbnail(u0, u1)),
         ("unsharpen", OpArg::FloatingPointIntegerTuple2(f, i)) => Ok(Operation::Unsharpen(f, i)),
-        _ => Err("No suitable operation was found.".to_string()),
+        (name, arg) => match builtin_arg_kind(name) {
+            Some(expected) => Err(Error::OperationArgumentMismatch {
+                name: name.to_string(),
+                expected,
+                given: arg.kind(),
+            }),
+            None => {
+                registry.resolve(name, &arg)?;
+                Ok(Operation::Custom(name.to_string(), arg))
+            }
+        },
     }
 }
 
@@ -60,6 +283,88 @@ pub fn operation_by_name(name: &str, value: OpArg) -> Result<Operation, String>
 mod tests {
     use super::*;
 
+    // kinds
+    // ----------
+
+    #[test]
+    fn arg_kind_matches_operation_by_name() {
+        let args = [
+            OpArg::Empty,
+            OpArg::FloatingPoint(1.0),
+            OpArg::Integer(1),
+            OpArg::UnsignedInteger(1),
+            OpArg::UnsignedIntegerTuple2(1, 1),
+            OpArg::UnsignedIntegerTuple2Anchor(1, 1, Anchor::Center),
+            OpArg::UnsignedIntegerTuple4(0, 0, 1, 1),
+            OpArg::FloatingPointArray9([1.0; 9]),
+            OpArg::FloatingPointTuple5(0.0, 1.0, 1.0, 0.0, 1.0),
+            OpArg::FloatingPointTuple2Vec(vec![(0.0, 0.0), (1.0, 1.0)]),
+            OpArg::FloatingPointIntegerTuple2(1.0, 1),
+            OpArg::PathPlacementFloatingPoint("a.png".into(), Placement::Offset(0, 0), 1.0),
+            OpArg::DitherUnsignedInteger(Dither::Bayer, 2),
+        ];
+
+        let names = [
+            "auto-levels",
+            "blur",
+            "brighten",
+            "contrast",
+            "crop",
+            "curve",
+            "dither",
+            "equalize",
+            "equalize-luminance",
+            "filter3x3",
+            "fliph",
+            "flipv",
+            "gamma",
+            "grayscale",
+            "huerotate",
+            "invert",
+            "levels",
+            "overlay",
+            "pad",
+            "pad-to",
+            "quantize",
+            "resize",
+            "resize-fill",
+            "resize-fit",
+            "resize-height",
+            "resize-percent",
+            "resize-width",
+            "rotate",
+            "rotate90",
+            "rotate180",
+            "rotate270",
+            "thumbnail",
+            "unsharpen",
+        ];
+
+        for name in names.iter() {
+            let kind = arg_kind_by_name(name).expect("Operation without argument kind.");
+
+            for arg in args.iter() {
+                let accepted = operation_by_name(name, arg.clone()).is_ok();
+                assert_eq!(kind == arg.kind(), accepted, "{} with {:?}", name, arg);
+            }
+        }
+    }
+
+    #[test]
+    fn arg_kind_unknown_name() {
+        assert_eq!(None, arg_kind_by_name("blur'"));
+    }
+
+    // auto-levels
+    // ----------
+
+    #[test]
+    fn auto_levels_ok() {
+        let actual = operation_by_name("auto-levels", OpArg::FloatingPoint(0.5));
+
+        assert_eq!(actual.ok(), Some(Operation::AutoLevels(0.5)));
+    }
+
     // blur
     // ----------
 
@@ -67,21 +372,59 @@ mod tests {
     fn blur_ok() {
         let actual = operation_by_name("blur", OpArg::FloatingPoint(1.5));
 
-        assert_eq!(actual, Ok(Operation::Blur(1.5)));
+        assert_eq!(actual.ok(), Some(Operation::Blur(1.5)));
     }
 
     #[test]
     fn blur_name_err() {
         let actual = operation_by_name("blur'", OpArg::FloatingPoint(1.5));
 
-        assert_ne!(actual, Ok(Operation::Blur(1.5)));
+        assert_ne!(actual.ok(), Some(Operation::Blur(1.5)));
+    }
+
+    #[test]
+    fn blur_name_err_is_unknown_operation() {
+        let actual = operation_by_name("blur'", OpArg::FloatingPoint(1.5));
+
+        match actual {
+            Err(Error::UnknownOperation { name, suggestion }) => {
+                assert_eq!("blur'", name);
+                assert_eq!(Some("blur".to_string()), suggestion);
+            }
+            _ => panic!("Expected an UnknownOperation error."),
+        }
     }
 
     #[test]
     fn blur_arg_err() {
         let actual = operation_by_name("blur", OpArg::Empty);
 
-        assert_ne!(actual, Ok(Operation::Blur(1.5)));
+        assert_ne!(actual.ok(), Some(Operation::Blur(1.5)));
+    }
+
+    #[test]
+    fn blur_arg_err_is_argument_mismatch() {
+        let actual = operation_by_name("blur", OpArg::Empty);
+
+        match actual {
+            Err(err @ Error::OperationArgumentMismatch { .. }) => assert_eq!(
+                "Operation: blur -- Expected arguments of the shape [f32], but the given \
+                 arguments have the shape (no arguments).",
+                err.to_string()
+            ),
+            _ => panic!("Expected an OperationArgumentMismatch error."),
+        }
+    }
+
+    #[test]
+    fn unknown_name_without_suggestion() {
+        match operation_by_name("sepia", OpArg::Empty) {
+            Err(err @ Error::UnknownOperation { .. }) => assert_eq!(
+                "No suitable operation was found for: sepia.",
+                err.to_string()
+            ),
+            _ => panic!("Expected an UnknownOperation error."),
+        }
     }
 
     // brighten
@@ -91,7 +434,7 @@ mod tests {
     fn brighten_ok() {
         let actual = operation_by_name("brighten", OpArg::Integer(-25));
 
-        assert_eq!(actual, Ok(Operation::Brighten(-25)));
+        assert_eq!(actual.ok(), Some(Operation::Brighten(-25)));
     }
 
     // contrast
@@ -101,7 +444,7 @@ mod tests {
     fn contrast_ok() {
         let actual = operation_by_name("contrast", OpArg::FloatingPoint(1.5));
 
-        assert_eq!(actual, Ok(Operation::Contrast(1.5)));
+        assert_eq!(actual.ok(), Some(Operation::Contrast(1.5)));
     }
 
     // crop
@@ -111,7 +454,18 @@ mod tests {
     fn crop_ok() {
         let actual = operation_by_name("crop", OpArg::UnsignedIntegerTuple4(0, 1, 2, 3));
 
-        assert_eq!(actual, Ok(Operation::Crop(0, 1, 2, 3)));
+        assert_eq!(actual.ok(), Some(Operation::Crop(0, 1, 2, 3)));
+    }
+
+    // curve
+    // ----------
+
+    #[test]
+    fn curve_ok() {
+        let points = vec![(0.0, 0.1), (0.5, 0.6), (1.0, 0.9)];
+        let actual = operation_by_name("curve", OpArg::FloatingPointTuple2Vec(points.clone()));
+
+        assert_eq!(actual.ok(), Some(Operation::Curve(points)));
     }
 
     // filter3x3
@@ -123,7 +477,24 @@ mod tests {
 
         let actual = operation_by_name("filter3x3", OpArg::FloatingPointArray9(array));
 
-        assert_eq!(actual, Ok(Operation::Filter3x3(array)));
+        assert_eq!(actual.ok(), Some(Operation::Filter3x3(array)));
+    }
+
+    // equalize
+    // ----------
+
+    #[test]
+    fn equalize_ok() {
+        let actual = operation_by_name("equalize", OpArg::Empty);
+
+        assert_eq!(actual.ok(), Some(Operation::Equalize));
+    }
+
+    #[test]
+    fn equalize_luminance_ok() {
+        let actual = operation_by_name("equalize-luminance", OpArg::Empty);
+
+        assert_eq!(actual.ok(), Some(Operation::EqualizeLuminance));
     }
 
     // fliph
@@ -133,7 +504,7 @@ mod tests {
     fn fliph_ok() {
         let actual = operation_by_name("fliph", OpArg::Empty);
 
-        assert_eq!(actual, Ok(Operation::FlipHorizontal));
+        assert_eq!(actual.ok(), Some(Operation::FlipHorizontal));
     }
 
     // flipv
@@ -143,7 +514,17 @@ mod tests {
     fn flipv_ok() {
         let actual = operation_by_name("flipv", OpArg::Empty);
 
-        assert_eq!(actual, Ok(Operation::FlipVertical));
+        assert_eq!(actual.ok(), Some(Operation::FlipVertical));
+    }
+
+    // gamma
+    // ----------
+
+    #[test]
+    fn gamma_ok() {
+        let actual = operation_by_name("gamma", OpArg::FloatingPoint(2.2));
+
+        assert_eq!(actual.ok(), Some(Operation::Gamma(2.2)));
     }
 
     // grayscale
@@ -153,7 +534,7 @@ mod tests {
     fn grayscale_ok() {
         let actual = operation_by_name("grayscale", OpArg::Empty);
 
-        assert_eq!(actual, Ok(Operation::GrayScale));
+        assert_eq!(actual.ok(), Some(Operation::GrayScale));
     }
 
     // huerotate
@@ -163,7 +544,7 @@ mod tests {
     fn huerotate_ok() {
         let actual = operation_by_name("huerotate", OpArg::Integer(-399));
 
-        assert_eq!(actual, Ok(Operation::HueRotate(-399)));
+        assert_eq!(actual.ok(), Some(Operation::HueRotate(-399)));
     }
 
     // invert
@@ -173,7 +554,53 @@ mod tests {
     fn invert_ok() {
         let actual = operation_by_name("invert", OpArg::Empty);
 
-        assert_eq!(actual, Ok(Operation::Invert));
+        assert_eq!(actual.ok(), Some(Operation::Invert));
+    }
+
+    // levels
+    // ----------
+
+    #[test]
+    fn levels_ok() {
+        let actual = operation_by_name(
+            "levels",
+            OpArg::FloatingPointTuple5(0.1, 0.9, 1.2, 0.0, 1.0),
+        );
+
+        assert_eq!(
+            actual.ok(),
+            Some(Operation::Levels(0.1, 0.9, 1.2, 0.0, 1.0))
+        );
+    }
+
+    // pad
+    // ----------
+
+    #[test]
+    fn pad_ok() {
+        let actual = operation_by_name("pad", OpArg::UnsignedIntegerTuple4(1, 2, 3, 4));
+
+        assert_eq!(actual.ok(), Some(Operation::Pad(1, 2, 3, 4)));
+    }
+
+    #[test]
+    fn pad_to_ok() {
+        let actual = operation_by_name(
+            "pad-to",
+            OpArg::UnsignedIntegerTuple2Anchor(80, 40, Anchor::Bottom),
+        );
+
+        assert_eq!(actual.ok(), Some(Operation::PadTo(80, 40, Anchor::Bottom)));
+    }
+
+    // quantize
+    // ----------
+
+    #[test]
+    fn quantize_ok() {
+        let actual = operation_by_name("quantize", OpArg::UnsignedInteger(16));
+
+        assert_eq!(actual.ok(), Some(Operation::Quantize(16)));
     }
 
     // resize
@@ -183,7 +610,45 @@ mod tests {
     fn resize_ok() {
         let actual = operation_by_name("resize", OpArg::UnsignedIntegerTuple2(80, 40));
 
-        assert_eq!(actual, Ok(Operation::Resize(80, 40)));
+        assert_eq!(actual.ok(), Some(Operation::Resize(80, 40)));
+    }
+
+    #[test]
+    fn resize_percent_ok() {
+        let actual = operation_by_name("resize-percent", OpArg::FloatingPoint(50.0));
+
+        assert_eq!(actual.ok(), Some(Operation::ResizePercent(50.0)));
+    }
+
+    #[test]
+    fn resize_width_ok() {
+        let actual = operation_by_name("resize-width", OpArg::UnsignedInteger(80));
+
+        assert_eq!(actual.ok(), Some(Operation::ResizeWidth(80)));
+    }
+
+    #[test]
+    fn resize_width_arg_err() {
+        let actual = operation_by_name("resize-width", OpArg::Integer(80));
+
+        assert!(actual.is_err());
+    }
+
+    // rotate
+    // ----------
+
+    #[test]
+    fn rotate_ok() {
+        let actual = operation_by_name("rotate", OpArg::FloatingPoint(-1.7));
+
+        assert_eq!(actual.ok(), Some(Operation::Rotate(-1.7)));
+    }
+
+    #[test]
+    fn rotate_arg_err() {
+        let actual = operation_by_name("rotate", OpArg::Integer(2));
+
+        assert!(actual.is_err());
     }
 
     // rotate90
@@ -193,7 +658,7 @@ mod tests {
     fn rotate90_ok() {
         let actual = operation_by_name("rotate90", OpArg::Empty);
 
-        assert_eq!(actual, Ok(Operation::Rotate90));
+        assert_eq!(actual.ok(), Some(Operation::Rotate90));
     }
 
     // rotate180
@@ -203,7 +668,7 @@ mod tests {
     fn rotate180_ok() {
         let actual = operation_by_name("rotate180", OpArg::Empty);
 
-        assert_eq!(actual, Ok(Operation::Rotate180));
+        assert_eq!(actual.ok(), Some(Operation::Rotate180));
     }
 
     // rotate270
@@ -213,7 +678,17 @@ mod tests {
     fn rotate270_ok() {
         let actual = operation_by_name("rotate270", OpArg::Empty);
 
-        assert_eq!(actual, Ok(Operation::Rotate270));
+        assert_eq!(actual.ok(), Some(Operation::Rotate270));
+    }
+
+    // thumbnail
+    // ----------
+
+    #[test]
+    fn thumbnail_ok() {
+        let actual = operation_by_name("thumbnail", OpArg::UnsignedIntegerTuple2(80, 40));
+
+        assert_eq!(actual.ok(), Some(Operation::Thumbnail(80, 40)));
     }
 
     // unsharpen
@@ -223,6 +698,61 @@ mod tests {
     fn unsharpen_ok() {
         let actual = operation_by_name("unsharpen", OpArg::FloatingPointIntegerTuple2(1.5, 3));
 
-        assert_eq!(actual, Ok(Operation::Unsharpen(1.5, 3)));
+        assert_eq!(actual.ok(), Some(Operation::Unsharpen(1.5, 3)));
+    }
+
+    // custom
+    // ----------
+
+    struct Sepia;
+
+    impl registry::ImageOperation for Sepia {
+        fn name(&self) -> &str {
+            "sepia"
+        }
+
+        fn arg_kind(&self) -> OpArgKind {
+            OpArgKind::FloatingPoint
+        }
+
+        fn apply(
+            &self,
+            image: &image::DynamicImage,
+            _arg: &OpArg,
+        ) -> Result<image::DynamicImage, Box<dyn std::error::Error + Send + Sync>> {
+            Ok(image.clone())
+        }
+    }
+
+    #[test]
+    fn custom_ok() {
+        let mut registry = Registry::new();
+        registry.register(Sepia).unwrap();
+
+        let actual = registry.operation_by_name("sepia", OpArg::FloatingPoint(0.5));
+        assert_eq!(
+            actual.ok(),
+            Some(Operation::Custom(
+                "sepia".to_string(),
+                OpArg::FloatingPoint(0.5)
+            ))
+        );
+
+        assert!(registry.operation_by_name("sepia", OpArg::Empty).is_err());
+        assert_eq!(
+            Some(Operation::Blur(1.0)),
+            registry
+                .operation_by_name("blur", OpArg::FloatingPoint(1.0))
+                .ok()
+        );
+    }
+
+    #[test]
+    fn custom_unregistered() {
+        assert_eq!(None, arg_kind_by_name("sepia"));
+        assert!(matches!(
+            operation_by_name("sepia", OpArg::FloatingPoint(0.5)),
+            Err(Error::UnknownOperation { .. })
+        ));
     }
 }
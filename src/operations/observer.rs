@@ -0,0 +1,48 @@
+use std::time::Duration;
+
+use crate::operations::engine::Statement;
+
+// Where a run of a program is at, when an `Observer` is notified.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Progress {
+    // Index of the current statement within the program.
+    pub index: usize,
+    // Amount of statements in the program.
+    pub total: usize,
+    // Time since the start of the run.
+    pub elapsed: Duration,
+}
+
+// Returned by an `Observer` to decide whether the run should go on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Flow {
+    Continue,
+    // Stops the run; `ImageEngine::ignite_with_observer` then returns `Error::Cancelled`.
+    Cancel,
+}
+
+// Notified by `ImageEngine::ignite_with_observer` before and after each statement of a program.
+//
+// Cancelling leaves the engine with the image and environment of the last completed statement.
+// To cancel from another thread, an observer can for example check an `AtomicBool`.
+pub trait Observer {
+    fn before_statement(&mut self, _statement: &Statement, _progress: Progress) -> Flow {
+        Flow::Continue
+    }
+
+    // Only called for statements which completed successfully; `duration` is the time the
+    // statement took.
+    fn after_statement(
+        &mut self,
+        _statement: &Statement,
+        _progress: Progress,
+        _duration: Duration,
+    ) -> Flow {
+        Flow::Continue
+    }
+}
+
+// Observer which doesn't do anything.
+pub(crate) struct NoObserver;
+
+impl Observer for NoObserver {}
@@ -0,0 +1,340 @@
+// Peephole optimization of a `Program`.
+//
+// Only rewrites which produce exactly the same image are applied, so for example consecutive
+// resizes are left alone: resampling an image twice doesn't give the same pixels as resampling
+// it once. The optimized program fails if and only if the original program fails, although it
+// may fail at another statement.
+use crate::operations::engine::{Program, Statement};
+use crate::operations::Operation;
+
+// Optimizes `program`. Statements which modify the environment are never moved, and operations
+// are never moved across them.
+pub fn optimize(program: Program) -> Program {
+    let mut optimized = Vec::with_capacity(program.len());
+    let mut operations = Vec::new();
+
+    for statement in program {
+        match statement {
+            Statement::Operation(operation) => operations.push(operation),
+            other => {
+                flush(&mut operations, &mut optimized);
+                optimized.push(other);
+            }
+        }
+    }
+
+    flush(&mut operations, &mut optimized);
+
+    optimized
+}
+
+fn flush(operations: &mut Vec<Operation>, program: &mut Program) {
+    let mut current = std::mem::take(operations);
+
+    loop {
+        let next = fold_orientations(hoist_crops(current.clone()));
+
+        if next == current {
+            break;
+        }
+
+        current = next;
+    }
+
+    program.extend(current.into_iter().map(Statement::Operation));
+}
+
+// Operations which compute each pixel only from the pixel at the same position and which don't
+// change the dimensions of the image.
+fn is_pointwise(operation: &Operation) -> bool {
+    matches!(
+        operation,
+        Operation::Brighten(_)
+            | Operation::Contrast(_)
+            | Operation::Curve(_)
+            | Operation::Gamma(_)
+            | Operation::GrayScale
+            | Operation::HueRotate(_)
+            | Operation::Invert
+            | Operation::Levels(..)
+    )
+}
+
+// Moves crops in front of pointwise operations, so they process fewer pixels.
+fn hoist_crops(mut operations: Vec<Operation>) -> Vec<Operation> {
+    for i in 1..operations.len() {
+        let mut at = i;
+
+        while at > 0
+            && matches!(operations[at], Operation::Crop(..))
+            && is_pointwise(&operations[at - 1])
+        {
+            operations.swap(at - 1, at);
+            at -= 1;
+        }
+    }
+
+    operations
+}
+
+// Combines each run of flips, quarter turns and pointwise operations into the pointwise
+// operations followed by the fewest flips and quarter turns with the same result. Flips and
+// quarter turns only move pixels around, so they can be moved past pointwise operations.
+fn fold_orientations(operations: Vec<Operation>) -> Vec<Operation> {
+    let mut folded = Vec::with_capacity(operations.len());
+    let mut pointwise = Vec::new();
+    let mut orientation = Orientation::default();
+
+    for operation in operations {
+        if let Some(next) = orientation.then(&operation) {
+            orientation = next;
+        } else if is_pointwise(&operation) {
+            push_pointwise(&mut pointwise, operation);
+        } else {
+            folded.append(&mut pointwise);
+            folded.extend(orientation.operations());
+            orientation = Orientation::default();
+
+            folded.push(operation);
+        }
+    }
+
+    folded.append(&mut pointwise);
+    folded.extend(orientation.operations());
+
+    folded
+}
+
+fn push_pointwise(operations: &mut Vec<Operation>, operation: Operation) {
+    match (operations.last(), &operation) {
+        (_, Operation::Brighten(0)) => {}
+        (Some(Operation::Invert), Operation::Invert) => {
+            operations.pop();
+        }
+        (Some(Operation::GrayScale), Operation::GrayScale) => {}
+        _ => operations.push(operation),
+    }
+}
+
+// An element of the symmetry group of a rectangle under flips and quarter turns: an optional
+// horizontal flip, followed by a number of clockwise quarter turns.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+struct Orientation {
+    flip: bool,
+    quarter_turns: u8,
+}
+
+impl Orientation {
+    // The orientation after also applying `operation`, or None if it isn't a flip or quarter turn.
+    fn then(self, operation: &Operation) -> Option<Orientation> {
+        let turn = |turns: u8| Orientation {
+            quarter_turns: (self.quarter_turns + turns) % 4,
+            ..self
+        };
+
+        // A flip after `n` quarter turns is the same as the flip followed by `-n` quarter turns.
+        let flip = |turns: u8| Orientation {
+            flip: !self.flip,
+            quarter_turns: (4 - self.quarter_turns + turns) % 4,
+        };
+
+        match *operation {
+            Operation::Rotate90 => Some(turn(1)),
+            Operation::Rotate180 => Some(turn(2)),
+            Operation::Rotate270 => Some(turn(3)),
+            Operation::FlipHorizontal => Some(flip(0)),
+            // A vertical flip is a horizontal flip followed by a half turn.
+            Operation::FlipVertical => Some(flip(2)),
+            _ => None,
+        }
+    }
+
+    fn operations(self) -> Vec<Operation> {
+        match (self.flip, self.quarter_turns) {
+            (false, 0) => vec![],
+            (false, 1) => vec![Operation::Rotate90],
+            (false, 2) => vec![Operation::Rotate180],
+            (false, _) => vec![Operation::Rotate270],
+            (true, 0) => vec![Operation::FlipHorizontal],
+            (true, 1) => vec![Operation::FlipHorizontal, Operation::Rotate90],
+            (true, 2) => vec![Operation::FlipVertical],
+            (true, _) => vec![Operation::FlipHorizontal, Operation::Rotate270],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{DynamicImage, GenericImageView, ImageBuffer, Rgba};
+
+    use crate::operations::engine::{EnvironmentItem, ImageEngine};
+    use crate::operations::mod_test_includes::*;
+
+    use super::*;
+
+    fn operations(ops: &[Operation]) -> Program {
+        ops.iter().cloned().map(Statement::Operation).collect()
+    }
+
+    fn run(image: &DynamicImage, program: Program) -> DynamicImage {
+        let mut engine = ImageEngine::new(image.clone());
+        engine.ignite(program).unwrap().clone()
+    }
+
+    fn assert_same_output(image: &DynamicImage, program: Program) {
+        let expected = run(image, program.clone());
+        let actual = run(image, optimize(program.clone()));
+
+        assert_eq!(expected.dimensions(), actual.dimensions(), "{:?}", program);
+        assert_eq!(expected.color(), actual.color(), "{:?}", program);
+        assert_eq!(expected.raw_pixels(), actual.raw_pixels(), "{:?}", program);
+    }
+
+    // Small image of which every pixel is distinct, so any misplaced pixel is noticed.
+    fn setup_distinct_image() -> DynamicImage {
+        DynamicImage::ImageRgba8(ImageBuffer::from_fn(3, 2, |x, y| {
+            Rgba([x as u8 * 40, y as u8 * 90, 10, 200])
+        }))
+    }
+
+    #[test]
+    fn cancel_inverse_operations() {
+        let cases = [
+            vec![Operation::Rotate90, Operation::Rotate270],
+            vec![Operation::Rotate180, Operation::Rotate180],
+            vec![Operation::FlipHorizontal, Operation::FlipHorizontal],
+            vec![Operation::FlipVertical, Operation::FlipVertical],
+            vec![Operation::Invert, Operation::Invert],
+            vec![Operation::Brighten(0)],
+        ];
+
+        for case in cases.iter() {
+            assert!(optimize(operations(case)).is_empty(), "{:?}", case);
+        }
+    }
+
+    #[test]
+    fn fuse_orientations() {
+        let program = operations(&[
+            Operation::Rotate90,
+            Operation::Rotate90,
+            Operation::FlipHorizontal,
+            Operation::Rotate270,
+        ]);
+
+        // half turn, flip, three quarter turns = flip, one quarter turn
+        assert_eq!(
+            operations(&[Operation::FlipHorizontal, Operation::Rotate90]),
+            optimize(program)
+        );
+    }
+
+    #[test]
+    fn fuse_across_pointwise_operations() {
+        let program = operations(&[
+            Operation::Rotate90,
+            Operation::Invert,
+            Operation::GrayScale,
+            Operation::GrayScale,
+            Operation::Rotate270,
+        ]);
+
+        assert_eq!(
+            operations(&[Operation::Invert, Operation::GrayScale]),
+            optimize(program)
+        );
+    }
+
+    #[test]
+    fn hoist_crop_before_pointwise_operations() {
+        let program = operations(&[
+            Operation::Invert,
+            Operation::HueRotate(40),
+            Operation::Crop(0, 0, 2, 2),
+        ]);
+
+        assert_eq!(
+            operations(&[
+                Operation::Crop(0, 0, 2, 2),
+                Operation::Invert,
+                Operation::HueRotate(40),
+            ]),
+            optimize(program)
+        );
+    }
+
+    #[test]
+    fn keep_non_equivalent_operations() {
+        let program = operations(&[
+            Operation::Resize(100, 100),
+            Operation::Resize(50, 50),
+            Operation::Blur(1.0),
+            Operation::Crop(0, 0, 10, 10),
+            Operation::Rotate90,
+            Operation::Crop(0, 0, 5, 5),
+            Operation::Rotate270,
+        ]);
+
+        assert_eq!(program.clone(), optimize(program));
+    }
+
+    #[test]
+    fn environment_statements_are_barriers() {
+        let program = vec![
+            Statement::Operation(Operation::Rotate90),
+            Statement::RegisterEnvironmentItem(EnvironmentItem::PreserveAspectRatio),
+            Statement::Operation(Operation::Rotate270),
+        ];
+
+        assert_eq!(program.clone(), optimize(program));
+    }
+
+    #[test]
+    fn same_output_for_all_short_orientation_programs() {
+        let image = setup_distinct_image();
+        let candidates = [
+            Operation::Rotate90,
+            Operation::Rotate180,
+            Operation::Rotate270,
+            Operation::FlipHorizontal,
+            Operation::FlipVertical,
+            Operation::Invert,
+        ];
+
+        for a in candidates.iter() {
+            assert_same_output(&image, operations(std::slice::from_ref(a)));
+
+            for b in candidates.iter() {
+                assert_same_output(&image, operations(&[a.clone(), b.clone()]));
+
+                for c in candidates.iter() {
+                    assert_same_output(&image, operations(&[a.clone(), b.clone(), c.clone()]));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn same_output_for_mixed_program() {
+        let image = setup_default_test_image();
+        let program = vec![
+            Statement::Operation(Operation::Rotate90),
+            Statement::Operation(Operation::Brighten(10)),
+            Statement::Operation(Operation::FlipVertical),
+            Statement::Operation(Operation::Contrast(5.0)),
+            Statement::Operation(Operation::Crop(10, 20, 110, 200)),
+            Statement::Operation(Operation::Rotate270),
+            Statement::Operation(Operation::Invert),
+            Statement::Operation(Operation::Invert),
+            Statement::Operation(Operation::FlipHorizontal),
+            Statement::RegisterEnvironmentItem(EnvironmentItem::PreserveAspectRatio),
+            Statement::Operation(Operation::GrayScale),
+            Statement::Operation(Operation::Resize(50, 50)),
+            Statement::Operation(Operation::HueRotate(20)),
+            Statement::Operation(Operation::Crop(0, 0, 20, 20)),
+        ];
+
+        assert!(optimize(program.clone()).len() < program.len());
+        assert_same_output(&image, program);
+    }
+}
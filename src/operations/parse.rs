@@ -0,0 +1,1389 @@
+// Parser for the image operations script language, which produces an engine `Program`.
+//
+// A script consists of statements, separated by semicolons (the last semicolon is optional):
+//
+//   set resize sampling-filter lanczos3;
+//   resize 100 100;
+//   blur 1.5;
+//   overlay watermark.png bottom-right 0.5;
+//
+// Statements:
+// - `<operation> <arguments...>` applies an image operation, e.g. `crop 0 0 10 10`.
+// - `set <operation> <option> <values...>` registers an option in the engine environment.
+// - `del <operation> <option>` de-registers a previously registered option.
+//
+// Everything after a `#` up to the end of the line is a comment. Words, and thus paths, can't
+// contain whitespace, `;` or `#`.
+use std::error::Error;
+use std::fmt;
+use std::mem;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use crate::operations::engine::{EnvironmentItem, EnvironmentKind, Program, Statement};
+use crate::operations::placement::{Anchor, Placement};
+use crate::operations::registry::Registry;
+use crate::operations::suggestion::suggest_operation;
+use crate::operations::transformations::dither::Dither;
+use crate::operations::transformations::quantize::QuantizeMethod;
+use crate::operations::wrapper::filter_type::FilterTypeWrap;
+use crate::operations::{OpArg, OpArgKind, Operation};
+
+const KEYWORD_SET: &str = "set";
+const KEYWORD_DEL: &str = "del";
+
+// A position within a script. Both line and column start at 1.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Span {
+    pub line: usize,
+    pub column: usize,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ValueKind {
+    UnsignedInteger,
+    Integer,
+    FloatingPoint,
+    ColorComponent,
+    SamplingFilter,
+    Path,
+    Placement,
+    Dimensions,
+    Anchor,
+    ControlPoints,
+    QuantizeMethod,
+    Dither,
+}
+
+impl fmt::Display for ValueKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let msg = match self {
+            ValueKind::UnsignedInteger => "an unsigned integer",
+            ValueKind::Integer => "an integer",
+            ValueKind::FloatingPoint => "a floating point number",
+            ValueKind::ColorComponent => "an integer between 0 and 255",
+            ValueKind::SamplingFilter => {
+                "a sampling filter (catmullrom, gaussian, lanczos3, nearest or triangle)"
+            }
+            ValueKind::Path => "a path",
+            ValueKind::Placement => {
+                "an anchor (top-left, top, top-right, left, center, right, bottom-left, bottom \
+                 or bottom-right) or an x and y offset"
+            }
+            ValueKind::Dimensions => "a width and height as `WxH`, e.g. `100x50`",
+            ValueKind::ControlPoints => {
+                "control points as `x,y` words, between 0 and 1 and with increasing x, e.g. \
+                 `0,0 0.5,0.6 1,1`"
+            }
+            ValueKind::Anchor => {
+                "an anchor (top-left, top, top-right, left, center, right, bottom-left, bottom \
+                 or bottom-right)"
+            }
+            ValueKind::QuantizeMethod => "a quantization method (median-cut or k-means)",
+            ValueKind::Dither => "a dithering method (floyd-steinberg or bayer)",
+        };
+
+        f.write_str(msg)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ParseErrorKind {
+    UnknownOperation {
+        name: String,
+        // The name of an existing operation which is close to `name`.
+        suggestion: Option<String>,
+    },
+    UnknownOption(String),
+    MissingOperation,
+    MissingOption,
+    MissingArgument {
+        index: usize,
+        expected: ValueKind,
+    },
+    InvalidArgument {
+        index: usize,
+        expected: ValueKind,
+        found: String,
+    },
+    UnexpectedArgument(String),
+    // The operation and its arguments were parsed, but the operation couldn't be constructed from
+    // them, e.g. because a registered operation no longer accepts the shape of its arguments.
+    InvalidOperation(OperationError),
+}
+
+// Shares the error, so parse errors remain cheap to clone.
+#[derive(Clone, Debug)]
+pub struct OperationError(pub Arc<crate::error::Error>);
+
+// Errors can't be compared in general, so they're compared by their variant. Argument shape
+// mismatches, the errors which are expected here, are compared by their shapes as well.
+impl PartialEq for OperationError {
+    fn eq(&self, other: &Self) -> bool {
+        use crate::error::Error::OperationArgumentMismatch;
+
+        match (&*self.0, &*other.0) {
+            (
+                OperationArgumentMismatch {
+                    name,
+                    expected,
+                    given,
+                },
+                OperationArgumentMismatch {
+                    name: other_name,
+                    expected: other_expected,
+                    given: other_given,
+                },
+            ) => name == other_name && expected == other_expected && given == other_given,
+            (err, other) => mem::discriminant(err) == mem::discriminant(other),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParseError {
+    pub kind: ParseErrorKind,
+    pub span: Span,
+    // Name of the operation the erroneous statement belongs to, if known.
+    pub operation: Option<String>,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}, column {}: ", self.span.line, self.span.column)?;
+
+        if let Some(operation) = &self.operation {
+            write!(f, "{}: ", operation)?;
+        }
+
+        write!(f, "{}", self.kind)
+    }
+}
+
+// The error without its position, e.g. for arguments which weren't part of a script.
+impl fmt::Display for ParseErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseErrorKind::UnknownOperation { name, suggestion } => {
+                write!(f, "unknown operation `{}`", name)?;
+
+                match suggestion {
+                    Some(suggestion) => write!(f, ", did you mean `{}`?", suggestion),
+                    None => Ok(()),
+                }
+            }
+            ParseErrorKind::UnknownOption(name) => write!(f, "unknown option `{}`", name),
+            ParseErrorKind::MissingOperation => {
+                write!(f, "expected the name of an operation after `set` or `del`")
+            }
+            ParseErrorKind::MissingOption => write!(f, "expected the name of an option"),
+            ParseErrorKind::MissingArgument { index, expected } => {
+                write!(f, "argument {} is missing, expected {}", index, expected)
+            }
+            ParseErrorKind::InvalidArgument {
+                index,
+                expected,
+                found,
+            } => write!(
+                f,
+                "argument {} must be {}, but found `{}`",
+                index, expected, found
+            ),
+            ParseErrorKind::UnexpectedArgument(found) => {
+                write!(f, "unexpected argument `{}`", found)
+            }
+            ParseErrorKind::InvalidOperation(err) => write!(f, "{}", err.0),
+        }
+    }
+}
+
+impl Error for ParseError {}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum TokenKind<'a> {
+    Word(&'a str),
+    Separator,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Token<'a> {
+    kind: TokenKind<'a>,
+    span: Span,
+}
+
+impl<'a> Token<'a> {
+    fn text(&self) -> &'a str {
+        match self.kind {
+            TokenKind::Word(text) => text,
+            TokenKind::Separator => ";",
+        }
+    }
+}
+
+// Splits a script into words and statement separators; comments are dropped.
+// Also returns the position directly after the last character of the script.
+fn tokenize(script: &str) -> (Vec<Token<'_>>, Span) {
+    let mut tokens = Vec::new();
+    let mut word_start: Option<(usize, Span)> = None;
+    let mut in_comment = false;
+    let mut span = Span { line: 1, column: 1 };
+
+    for (index, c) in script.char_indices() {
+        let ends_word = in_comment || c.is_whitespace() || c == ';' || c == '#';
+
+        if ends_word {
+            if let Some((start, start_span)) = word_start.take() {
+                tokens.push(Token {
+                    kind: TokenKind::Word(&script[start..index]),
+                    span: start_span,
+                });
+            }
+        }
+
+        if c == '\n' {
+            in_comment = false;
+        } else if !in_comment {
+            if c == '#' {
+                in_comment = true;
+            } else if c == ';' {
+                tokens.push(Token {
+                    kind: TokenKind::Separator,
+                    span,
+                });
+            } else if !c.is_whitespace() && word_start.is_none() {
+                word_start = Some((index, span));
+            }
+        }
+
+        if c == '\n' {
+            span.line += 1;
+            span.column = 1;
+        } else {
+            span.column += 1;
+        }
+    }
+
+    if let Some((start, start_span)) = word_start {
+        tokens.push(Token {
+            kind: TokenKind::Word(&script[start..]),
+            span: start_span,
+        });
+    }
+
+    (tokens, span)
+}
+
+// Parses a script which uses built-in operations only.
+pub fn parse_script(script: &str) -> Result<Program, ParseError> {
+    parse_script_with_registry(script, &Registry::default())
+}
+
+// Parses a script which may use the operations of `registry` as well.
+pub fn parse_script_with_registry(
+    script: &str,
+    registry: &Registry,
+) -> Result<Program, ParseError> {
+    let (tokens, end_of_script) = tokenize(script);
+
+    let mut program = Program::new();
+    let mut statement: Vec<Token> = Vec::new();
+
+    for token in tokens {
+        match token.kind {
+            TokenKind::Separator => {
+                if !statement.is_empty() {
+                    program.push(parse_statement(&statement, token.span, registry)?);
+                    statement.clear();
+                }
+            }
+            TokenKind::Word(_) => statement.push(token),
+        }
+    }
+
+    if !statement.is_empty() {
+        program.push(parse_statement(&statement, end_of_script, registry)?);
+    }
+
+    Ok(program)
+}
+
+// `end` is the position where the statement was terminated; errors about missing parts of
+// the statement will point there.
+fn parse_statement(
+    words: &[Token],
+    end: Span,
+    registry: &Registry,
+) -> Result<Statement, ParseError> {
+    let head = words[0];
+
+    match head.text() {
+        KEYWORD_SET => parse_set(&words[1..], end),
+        KEYWORD_DEL => parse_del(&words[1..], end),
+        _ => parse_operation(head, &words[1..], end, registry),
+    }
+}
+
+fn parse_operation(
+    name: Token,
+    words: &[Token],
+    end: Span,
+    registry: &Registry,
+) -> Result<Statement, ParseError> {
+    let operation = name.text();
+    let arg = parse_arguments(name, words, end, registry)?;
+
+    registry
+        .operation_by_name(operation, arg)
+        .map(Statement::Operation)
+        .map_err(|err| {
+            let kind = match err {
+                crate::error::Error::UnknownOperation { name, suggestion } => {
+                    ParseErrorKind::UnknownOperation { name, suggestion }
+                }
+                err => ParseErrorKind::InvalidOperation(OperationError(Arc::new(err))),
+            };
+
+            ParseError {
+                kind,
+                span: name.span,
+                operation: None,
+            }
+        })
+}
+
+fn parse_arguments(
+    name: Token,
+    words: &[Token],
+    end: Span,
+    registry: &Registry,
+) -> Result<OpArg, ParseError> {
+    let operation = name.text();
+
+    let kind = registry
+        .arg_kind_by_name(operation)
+        .ok_or_else(|| ParseError {
+            kind: ParseErrorKind::UnknownOperation {
+                name: operation.to_string(),
+                suggestion: suggest_operation(operation, registry),
+            },
+            span: name.span,
+            operation: None,
+        })?;
+
+    let mut args = Arguments::new(operation, words, end);
+
+    let arg = match kind {
+        OpArgKind::Empty => OpArg::Empty,
+        OpArgKind::FloatingPoint => OpArg::FloatingPoint(args.next(ValueKind::FloatingPoint)?),
+        OpArgKind::Integer => OpArg::Integer(args.next(ValueKind::Integer)?),
+        OpArgKind::UnsignedInteger => {
+            OpArg::UnsignedInteger(args.next(ValueKind::UnsignedInteger)?)
+        }
+        OpArgKind::UnsignedIntegerTuple2 => {
+            let (width, height) = args.next_dimensions()?;
+            OpArg::UnsignedIntegerTuple2(width, height)
+        }
+        OpArgKind::UnsignedIntegerTuple2Anchor => {
+            let (width, height) = args.next_dimensions()?;
+            OpArg::UnsignedIntegerTuple2Anchor(width, height, args.next_anchor()?)
+        }
+        OpArgKind::UnsignedIntegerTuple4 => OpArg::UnsignedIntegerTuple4(
+            args.next(ValueKind::UnsignedInteger)?,
+            args.next(ValueKind::UnsignedInteger)?,
+            args.next(ValueKind::UnsignedInteger)?,
+            args.next(ValueKind::UnsignedInteger)?,
+        ),
+        OpArgKind::FloatingPointArray9 => {
+            let mut array = [0f32; 9];
+            for value in array.iter_mut() {
+                *value = args.next(ValueKind::FloatingPoint)?;
+            }
+            OpArg::FloatingPointArray9(array)
+        }
+        OpArgKind::FloatingPointTuple5 => OpArg::FloatingPointTuple5(
+            args.next(ValueKind::FloatingPoint)?,
+            args.next(ValueKind::FloatingPoint)?,
+            args.next(ValueKind::FloatingPoint)?,
+            args.next(ValueKind::FloatingPoint)?,
+            args.next(ValueKind::FloatingPoint)?,
+        ),
+        OpArgKind::FloatingPointTuple2Vec => {
+            OpArg::FloatingPointTuple2Vec(args.next_control_points()?)
+        }
+        OpArgKind::FloatingPointIntegerTuple2 => OpArg::FloatingPointIntegerTuple2(
+            args.next(ValueKind::FloatingPoint)?,
+            args.next(ValueKind::Integer)?,
+        ),
+        OpArgKind::PathPlacementFloatingPoint => OpArg::PathPlacementFloatingPoint(
+            args.next(ValueKind::Path)?,
+            args.next_placement()?,
+            args.next(ValueKind::FloatingPoint)?,
+        ),
+        OpArgKind::DitherUnsignedInteger => OpArg::DitherUnsignedInteger(
+            args.next_dither()?,
+            args.next(ValueKind::UnsignedInteger)?,
+        ),
+    };
+
+    args.finish()?;
+
+    Ok(arg)
+}
+
+// Parses the argument words of the operation `name`, e.g. `["0", "0", "10", "10"]` for `crop`,
+// into the `OpArg` it takes. This is meant for front-ends which receive an operation and its
+// arguments as separate words (e.g. command line arguments), instead of as a script.
+//
+// The words are parsed like the words of a script statement would be, except that they may
+// contain whitespace, `;` or `#`. Errors have the position the word would have in the script
+// `name words...`, where the words are separated by a single space.
+pub fn parse_op_arg<S: AsRef<str>>(name: &str, words: &[S]) -> Result<OpArg, ParseError> {
+    parse_op_arg_with_registry(name, words, &Registry::default())
+}
+
+// Like `parse_op_arg`, but `name` may be an operation of `registry` as well.
+pub fn parse_op_arg_with_registry<S: AsRef<str>>(
+    name: &str,
+    words: &[S],
+    registry: &Registry,
+) -> Result<OpArg, ParseError> {
+    let (name, words, end) = tokens_from_words(name, words);
+
+    parse_arguments(name, &words, end, registry)
+}
+
+// Like `parse_op_arg`, but produces the operation itself.
+pub fn parse_operation_words<S: AsRef<str>>(
+    name: &str,
+    words: &[S],
+) -> Result<Operation, ParseError> {
+    parse_operation_words_with_registry(name, words, &Registry::default())
+}
+
+// Like `parse_operation_words`, but `name` may be an operation of `registry` as well.
+pub fn parse_operation_words_with_registry<S: AsRef<str>>(
+    name: &str,
+    words: &[S],
+    registry: &Registry,
+) -> Result<Operation, ParseError> {
+    let (name, words, end) = tokens_from_words(name, words);
+
+    match parse_operation(name, &words, end, registry)? {
+        Statement::Operation(operation) => Ok(operation),
+        _ => unreachable!("parse_operation only produces operations"),
+    }
+}
+
+fn tokens_from_words<'a, S: AsRef<str>>(
+    name: &'a str,
+    words: &'a [S],
+) -> (Token<'a>, Vec<Token<'a>>, Span) {
+    let name = Token {
+        kind: TokenKind::Word(name),
+        span: Span { line: 1, column: 1 },
+    };
+
+    let mut column = name.text().chars().count() + 2;
+    let mut tokens = Vec::with_capacity(words.len());
+
+    for word in words {
+        let word = word.as_ref();
+
+        tokens.push(Token {
+            kind: TokenKind::Word(word),
+            span: Span { line: 1, column },
+        });
+
+        column += word.chars().count() + 1;
+    }
+
+    (
+        name,
+        tokens,
+        Span {
+            line: 1,
+            column: column - 1,
+        },
+    )
+}
+
+// Returns the operation and option words of a `set` or `del` statement, and its remaining
+// words.
+fn option_target<'a, 't>(
+    words: &'t [Token<'a>],
+    end: Span,
+) -> Result<(Token<'a>, Token<'a>, &'t [Token<'a>]), ParseError> {
+    let operation = words.first().ok_or(ParseError {
+        kind: ParseErrorKind::MissingOperation,
+        span: end,
+        operation: None,
+    })?;
+
+    let option = words.get(1).ok_or_else(|| ParseError {
+        kind: ParseErrorKind::MissingOption,
+        span: end,
+        operation: Some(operation.text().to_string()),
+    })?;
+
+    Ok((*operation, *option, &words[2..]))
+}
+
+fn unknown_option(operation: Token, option: Token) -> ParseError {
+    ParseError {
+        kind: ParseErrorKind::UnknownOption(option.text().to_string()),
+        span: option.span,
+        operation: Some(operation.text().to_string()),
+    }
+}
+
+fn parse_set(words: &[Token], end: Span) -> Result<Statement, ParseError> {
+    let (operation, option, values) = option_target(words, end)?;
+    let mut args = Arguments::new(operation.text(), values, end);
+
+    let item = match (operation.text(), option.text()) {
+        ("pad", "fill-color") => EnvironmentItem::PadFillColor([
+            args.next(ValueKind::ColorComponent)?,
+            args.next(ValueKind::ColorComponent)?,
+            args.next(ValueKind::ColorComponent)?,
+            args.next(ValueKind::ColorComponent)?,
+        ]),
+        ("quantize", "dither") => EnvironmentItem::QuantizeDither(args.next_dither()?),
+        ("quantize", "method") => EnvironmentItem::QuantizeMethod(args.next_quantize_method()?),
+        ("resize", "sampling-filter") => {
+            EnvironmentItem::OptResizeSamplingFilter(args.next_sampling_filter()?)
+        }
+        ("resize", "preserve-aspect-ratio") => EnvironmentItem::PreserveAspectRatio,
+        ("resize", "shrink-only") => EnvironmentItem::ResizeShrinkOnly,
+        ("rotate", "sampling-filter") => {
+            EnvironmentItem::OptRotateSamplingFilter(args.next_sampling_filter()?)
+        }
+        ("rotate", "expand-canvas") => EnvironmentItem::RotateExpandCanvas,
+        ("rotate", "fill-color") => EnvironmentItem::RotateFillColor([
+            args.next(ValueKind::ColorComponent)?,
+            args.next(ValueKind::ColorComponent)?,
+            args.next(ValueKind::ColorComponent)?,
+            args.next(ValueKind::ColorComponent)?,
+        ]),
+        _ => return Err(unknown_option(operation, option)),
+    };
+
+    args.finish()?;
+
+    Ok(Statement::RegisterEnvironmentItem(item))
+}
+
+fn parse_del(words: &[Token], end: Span) -> Result<Statement, ParseError> {
+    let (operation, option, values) = option_target(words, end)?;
+
+    let key = match (operation.text(), option.text()) {
+        ("pad", "fill-color") => EnvironmentKind::OptPadFillColor,
+        ("quantize", "dither") => EnvironmentKind::OptQuantizeDither,
+        ("quantize", "method") => EnvironmentKind::OptQuantizeMethod,
+        ("resize", "sampling-filter") => EnvironmentKind::OptResizeSamplingFilter,
+        ("resize", "preserve-aspect-ratio") => EnvironmentKind::OptResizePreserveAspectRatio,
+        ("resize", "shrink-only") => EnvironmentKind::OptResizeShrinkOnly,
+        ("rotate", "sampling-filter") => EnvironmentKind::OptRotateSamplingFilter,
+        ("rotate", "expand-canvas") => EnvironmentKind::OptRotateExpandCanvas,
+        ("rotate", "fill-color") => EnvironmentKind::OptRotateFillColor,
+        _ => return Err(unknown_option(operation, option)),
+    };
+
+    Arguments::new(operation.text(), values, end).finish()?;
+
+    Ok(Statement::DeregisterEnvironmentItem(key))
+}
+
+// Consumes the argument words of a single statement.
+struct Arguments<'a, 't> {
+    operation: &'a str,
+    words: &'t [Token<'a>],
+    end: Span,
+    position: usize,
+}
+
+impl<'a, 't> Arguments<'a, 't> {
+    fn new(operation: &'a str, words: &'t [Token<'a>], end: Span) -> Self {
+        Arguments {
+            operation,
+            words,
+            end,
+            position: 0,
+        }
+    }
+
+    fn error(&self, kind: ParseErrorKind, span: Span) -> ParseError {
+        ParseError {
+            kind,
+            span,
+            operation: Some(self.operation.to_string()),
+        }
+    }
+
+    fn next_word(&mut self, expected: ValueKind) -> Result<Token<'a>, ParseError> {
+        let index = self.position + 1;
+
+        match self.words.get(self.position) {
+            Some(word) => {
+                self.position += 1;
+                Ok(*word)
+            }
+            None => Err(self.error(
+                ParseErrorKind::MissingArgument { index, expected },
+                self.end,
+            )),
+        }
+    }
+
+    fn next<T: FromStr>(&mut self, expected: ValueKind) -> Result<T, ParseError> {
+        let word = self.next_word(expected)?;
+
+        word.text().parse::<T>().map_err(|_| {
+            self.error(
+                ParseErrorKind::InvalidArgument {
+                    index: self.position,
+                    expected,
+                    found: word.text().to_string(),
+                },
+                word.span,
+            )
+        })
+    }
+
+    fn next_sampling_filter(&mut self) -> Result<FilterTypeWrap, ParseError> {
+        let expected = ValueKind::SamplingFilter;
+        let word = self.next_word(expected)?;
+
+        FilterTypeWrap::try_from_str(word.text()).map_err(|_| {
+            self.error(
+                ParseErrorKind::InvalidArgument {
+                    index: self.position,
+                    expected,
+                    found: word.text().to_string(),
+                },
+                word.span,
+            )
+        })
+    }
+
+    // Either a width and a height, or both as a single `WxH` word.
+    fn next_dimensions(&mut self) -> Result<(u32, u32), ParseError> {
+        let word = self.next_word(ValueKind::UnsignedInteger)?;
+
+        if let Ok(width) = word.text().parse::<u32>() {
+            return Ok((width, self.next(ValueKind::UnsignedInteger)?));
+        }
+
+        let (expected, dimensions) = match word.text().split_once(['x', 'X']) {
+            Some((width, height)) => (
+                ValueKind::Dimensions,
+                width.parse::<u32>().ok().zip(height.parse::<u32>().ok()),
+            ),
+            None => (ValueKind::UnsignedInteger, None),
+        };
+
+        dimensions.ok_or_else(|| {
+            self.error(
+                ParseErrorKind::InvalidArgument {
+                    index: self.position,
+                    expected,
+                    found: word.text().to_string(),
+                },
+                word.span,
+            )
+        })
+    }
+
+    // All remaining words, each an `x,y` point; at least one.
+    fn next_control_points(&mut self) -> Result<Vec<(f32, f32)>, ParseError> {
+        let mut points = vec![self.next_control_point()?];
+
+        while self.position < self.words.len() {
+            points.push(self.next_control_point()?);
+        }
+
+        Ok(points)
+    }
+
+    fn next_control_point(&mut self) -> Result<(f32, f32), ParseError> {
+        let expected = ValueKind::ControlPoints;
+        let word = self.next_word(expected)?;
+
+        let point = word
+            .text()
+            .split_once(',')
+            .and_then(|(x, y)| x.parse::<f32>().ok().zip(y.parse::<f32>().ok()));
+
+        point.ok_or_else(|| {
+            self.error(
+                ParseErrorKind::InvalidArgument {
+                    index: self.position,
+                    expected,
+                    found: word.text().to_string(),
+                },
+                word.span,
+            )
+        })
+    }
+
+    fn next_anchor(&mut self) -> Result<Anchor, ParseError> {
+        let expected = ValueKind::Anchor;
+        let word = self.next_word(expected)?;
+
+        Anchor::try_from_str(word.text()).map_err(|_| {
+            self.error(
+                ParseErrorKind::InvalidArgument {
+                    index: self.position,
+                    expected,
+                    found: word.text().to_string(),
+                },
+                word.span,
+            )
+        })
+    }
+
+    fn next_quantize_method(&mut self) -> Result<QuantizeMethod, ParseError> {
+        let expected = ValueKind::QuantizeMethod;
+        let word = self.next_word(expected)?;
+
+        QuantizeMethod::try_from_str(word.text()).map_err(|_| {
+            self.error(
+                ParseErrorKind::InvalidArgument {
+                    index: self.position,
+                    expected,
+                    found: word.text().to_string(),
+                },
+                word.span,
+            )
+        })
+    }
+
+    fn next_dither(&mut self) -> Result<Dither, ParseError> {
+        let expected = ValueKind::Dither;
+        let word = self.next_word(expected)?;
+
+        Dither::try_from_str(word.text()).map_err(|_| {
+            self.error(
+                ParseErrorKind::InvalidArgument {
+                    index: self.position,
+                    expected,
+                    found: word.text().to_string(),
+                },
+                word.span,
+            )
+        })
+    }
+
+    // Either an anchor name, or an x and y offset.
+    fn next_placement(&mut self) -> Result<Placement, ParseError> {
+        let expected = ValueKind::Placement;
+        let word = self.next_word(expected)?;
+
+        if let Ok(anchor) = Anchor::try_from_str(word.text()) {
+            return Ok(Placement::Anchor(anchor));
+        }
+
+        let x = word.text().parse::<i32>().map_err(|_| {
+            self.error(
+                ParseErrorKind::InvalidArgument {
+                    index: self.position,
+                    expected,
+                    found: word.text().to_string(),
+                },
+                word.span,
+            )
+        })?;
+
+        Ok(Placement::Offset(x, self.next(ValueKind::Integer)?))
+    }
+
+    fn finish(self) -> Result<(), ParseError> {
+        match self.words.get(self.position) {
+            Some(word) => Err(self.error(
+                ParseErrorKind::UnexpectedArgument(word.text().to_string()),
+                word.span,
+            )),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::DynamicImage;
+
+    use crate::operations::registry::ImageOperation;
+    use crate::operations::Operation;
+
+    use super::*;
+
+    fn span(line: usize, column: usize) -> Span {
+        Span { line, column }
+    }
+
+    #[test]
+    fn parse_example() {
+        let program =
+            parse_script("set resize sampling-filter lanczos3; resize 100 100; blur 1.5;");
+
+        assert_eq!(
+            program,
+            Ok(vec![
+                Statement::RegisterEnvironmentItem(EnvironmentItem::OptResizeSamplingFilter(
+                    FilterTypeWrap::Inner(image::FilterType::Lanczos3)
+                )),
+                Statement::Operation(Operation::Resize(100, 100)),
+                Statement::Operation(Operation::Blur(1.5)),
+            ])
+        );
+    }
+
+    #[test]
+    fn parse_empty() {
+        assert_eq!(parse_script(""), Ok(vec![]));
+        assert_eq!(parse_script(" ;\n; # nothing here"), Ok(vec![]));
+    }
+
+    #[test]
+    fn parse_without_trailing_separator() {
+        assert_eq!(
+            parse_script("fliph; rotate -1.7"),
+            Ok(vec![
+                Statement::Operation(Operation::FlipHorizontal),
+                Statement::Operation(Operation::Rotate(-1.7)),
+            ])
+        );
+    }
+
+    #[test]
+    fn parse_statement_over_multiple_lines_with_comments() {
+        let script = "# crop the borders\ncrop 1 2 # top anchor\n  30 40;\nunsharpen 1.5 -2;";
+
+        assert_eq!(
+            parse_script(script),
+            Ok(vec![
+                Statement::Operation(Operation::Crop(1, 2, 30, 40)),
+                Statement::Operation(Operation::Unsharpen(1.5, -2)),
+            ])
+        );
+    }
+
+    #[test]
+    fn parse_all_operations() {
+        let script = "auto-levels 0.5; blur 1; brighten -2; contrast 3.5; crop 0 0 1 1; \
+                      curve 0,0.1 0.5,0.5 1,1; dither bayer 2; equalize; equalize-luminance; \
+                      filter3x3 1 2 3 4 5 6 7 8 9; fliph; flipv; \
+                      gamma 2.2; grayscale; huerotate 90; invert; levels 0.1 0.9 1.2 0 1; \
+                      overlay a.png center 0.5; overlay b.png -1 2 1; pad 1 2 3 4; \
+                      pad-to 30x40 top-left; pad-to 50 60 bottom; quantize 16; resize 10 20; \
+                      resize-fill 10 20; resize-fit 30x40; resize-height 50; resize-percent 12.5; \
+                      resize-width 60; rotate 45; rotate90; rotate180; rotate270; thumbnail 64x48; \
+                      unsharpen 1 2";
+
+        let program = parse_script(script).expect("Unable to parse script.");
+
+        assert_eq!(
+            program,
+            vec![
+                Operation::AutoLevels(0.5),
+                Operation::Blur(1.0),
+                Operation::Brighten(-2),
+                Operation::Contrast(3.5),
+                Operation::Crop(0, 0, 1, 1),
+                Operation::Curve(vec![(0.0, 0.1), (0.5, 0.5), (1.0, 1.0)]),
+                Operation::Dither(Dither::Bayer, 2),
+                Operation::Equalize,
+                Operation::EqualizeLuminance,
+                Operation::Filter3x3([1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0, 9.0]),
+                Operation::FlipHorizontal,
+                Operation::FlipVertical,
+                Operation::Gamma(2.2),
+                Operation::GrayScale,
+                Operation::HueRotate(90),
+                Operation::Invert,
+                Operation::Levels(0.1, 0.9, 1.2, 0.0, 1.0),
+                Operation::Overlay("a.png".to_string(), Placement::Anchor(Anchor::Center), 0.5),
+                Operation::Overlay("b.png".to_string(), Placement::Offset(-1, 2), 1.0),
+                Operation::Pad(1, 2, 3, 4),
+                Operation::PadTo(30, 40, Anchor::TopLeft),
+                Operation::PadTo(50, 60, Anchor::Bottom),
+                Operation::Quantize(16),
+                Operation::Resize(10, 20),
+                Operation::ResizeFill(10, 20),
+                Operation::ResizeFit(30, 40),
+                Operation::ResizeHeight(50),
+                Operation::ResizePercent(12.5),
+                Operation::ResizeWidth(60),
+                Operation::Rotate(45.0),
+                Operation::Rotate90,
+                Operation::Rotate180,
+                Operation::Rotate270,
+                Operation::Thumbnail(64, 48),
+                Operation::Unsharpen(1.0, 2),
+            ]
+            .into_iter()
+            .map(Statement::Operation)
+            .collect::<Program>()
+        );
+    }
+
+    #[test]
+    fn parse_set_and_del_options() {
+        let script = "set resize preserve-aspect-ratio; set rotate sampling-filter nearest; \
+                      set rotate expand-canvas; set rotate fill-color 255 0 0 128; \
+                      set resize shrink-only; del resize preserve-aspect-ratio; \
+                      del rotate fill-color; del resize shrink-only; \
+                      set pad fill-color 255 255 255 255; del pad fill-color; \
+                      set quantize method k-means; set quantize dither floyd-steinberg; \
+                      del quantize method; del quantize dither";
+
+        assert_eq!(
+            parse_script(script),
+            Ok(vec![
+                Statement::RegisterEnvironmentItem(EnvironmentItem::PreserveAspectRatio),
+                Statement::RegisterEnvironmentItem(EnvironmentItem::OptRotateSamplingFilter(
+                    FilterTypeWrap::Inner(image::FilterType::Nearest)
+                )),
+                Statement::RegisterEnvironmentItem(EnvironmentItem::RotateExpandCanvas),
+                Statement::RegisterEnvironmentItem(EnvironmentItem::RotateFillColor([
+                    255, 0, 0, 128
+                ])),
+                Statement::RegisterEnvironmentItem(EnvironmentItem::ResizeShrinkOnly),
+                Statement::DeregisterEnvironmentItem(EnvironmentKind::OptResizePreserveAspectRatio),
+                Statement::DeregisterEnvironmentItem(EnvironmentKind::OptRotateFillColor),
+                Statement::DeregisterEnvironmentItem(EnvironmentKind::OptResizeShrinkOnly),
+                Statement::RegisterEnvironmentItem(EnvironmentItem::PadFillColor([255; 4])),
+                Statement::DeregisterEnvironmentItem(EnvironmentKind::OptPadFillColor),
+                Statement::RegisterEnvironmentItem(EnvironmentItem::QuantizeMethod(
+                    QuantizeMethod::KMeans
+                )),
+                Statement::RegisterEnvironmentItem(EnvironmentItem::QuantizeDither(
+                    Dither::FloydSteinberg
+                )),
+                Statement::DeregisterEnvironmentItem(EnvironmentKind::OptQuantizeMethod),
+                Statement::DeregisterEnvironmentItem(EnvironmentKind::OptQuantizeDither),
+            ])
+        );
+    }
+
+    #[test]
+    fn error_unknown_operation() {
+        let err = parse_script("blur 1;\n  bluur 1;").unwrap_err();
+
+        assert_eq!(
+            err.kind,
+            ParseErrorKind::UnknownOperation {
+                name: "bluur".to_string(),
+                suggestion: Some("blur".to_string())
+            }
+        );
+        assert_eq!(err.span, span(2, 3));
+        assert_eq!(err.operation, None);
+        assert_eq!(
+            "line 2, column 3: unknown operation `bluur`, did you mean `blur`?",
+            err.to_string()
+        );
+    }
+
+    #[test]
+    fn parse_registered_operation() {
+        struct Sepia;
+
+        impl ImageOperation for Sepia {
+            fn name(&self) -> &str {
+                "sepia"
+            }
+
+            fn arg_kind(&self) -> OpArgKind {
+                OpArgKind::FloatingPoint
+            }
+
+            fn apply(
+                &self,
+                image: &DynamicImage,
+                _arg: &OpArg,
+            ) -> Result<DynamicImage, Box<dyn Error + Send + Sync>> {
+                Ok(image.clone())
+            }
+        }
+
+        let mut registry = Registry::new();
+        registry.register(Sepia).unwrap();
+
+        assert_eq!(
+            parse_script_with_registry("sepia 0.5", &registry).unwrap(),
+            vec![Statement::Operation(Operation::Custom(
+                "sepia".to_string(),
+                OpArg::FloatingPoint(0.5)
+            ))]
+        );
+        assert_eq!(
+            parse_op_arg_with_registry("sepia", &["0.5"], &registry).unwrap(),
+            OpArg::FloatingPoint(0.5)
+        );
+
+        // registered operations are suggested, but only when the registry is given
+        let err = parse_script_with_registry("sepa 0.5", &registry).unwrap_err();
+        assert_eq!(
+            err.kind,
+            ParseErrorKind::UnknownOperation {
+                name: "sepa".to_string(),
+                suggestion: Some("sepia".to_string())
+            }
+        );
+        assert!(matches!(
+            parse_script("sepia 0.5").unwrap_err().kind,
+            ParseErrorKind::UnknownOperation {
+                suggestion: None,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn error_invalid_operation() {
+        use std::sync::atomic::{AtomicBool, Ordering};
+
+        // Accepts a float when parsing, but an integer when constructing the operation.
+        struct Fickle(AtomicBool);
+
+        impl ImageOperation for Fickle {
+            fn name(&self) -> &str {
+                "fickle"
+            }
+
+            fn arg_kind(&self) -> OpArgKind {
+                if self.0.swap(true, Ordering::SeqCst) {
+                    OpArgKind::Integer
+                } else {
+                    OpArgKind::FloatingPoint
+                }
+            }
+
+            fn apply(
+                &self,
+                image: &DynamicImage,
+                _arg: &OpArg,
+            ) -> Result<DynamicImage, Box<dyn Error + Send + Sync>> {
+                Ok(image.clone())
+            }
+        }
+
+        let mut registry = Registry::new();
+        registry.register(Fickle(AtomicBool::new(false))).unwrap();
+
+        let err = parse_script_with_registry("fickle 0.5", &registry).unwrap_err();
+
+        match &err.kind {
+            ParseErrorKind::InvalidOperation(OperationError(source)) => assert!(matches!(
+                **source,
+                crate::error::Error::OperationArgumentMismatch {
+                    expected: OpArgKind::Integer,
+                    given: OpArgKind::FloatingPoint,
+                    ..
+                }
+            )),
+            other => panic!("Unexpected error: {:?}", other),
+        }
+        assert_eq!(err, err.clone());
+
+        // a mismatch of other shapes is a different error
+        let other = ParseError {
+            kind: ParseErrorKind::InvalidOperation(OperationError(Arc::new(
+                crate::error::Error::OperationArgumentMismatch {
+                    name: "fickle".to_string(),
+                    expected: OpArgKind::Empty,
+                    given: OpArgKind::FloatingPoint,
+                },
+            ))),
+            ..err.clone()
+        };
+        assert_ne!(err, other);
+        assert!(err.to_string().ends_with(&err.kind.to_string()));
+    }
+
+    #[test]
+    fn error_invalid_argument() {
+        let err = parse_script("crop 0 0 -1 1").unwrap_err();
+
+        assert_eq!(
+            err.kind,
+            ParseErrorKind::InvalidArgument {
+                index: 3,
+                expected: ValueKind::UnsignedInteger,
+                found: "-1".to_string()
+            }
+        );
+        assert_eq!(err.span, span(1, 10));
+        assert_eq!(err.operation, Some("crop".to_string()));
+        assert_eq!(
+            err.to_string(),
+            "line 1, column 10: crop: argument 3 must be an unsigned integer, but found `-1`"
+        );
+    }
+
+    #[test]
+    fn error_missing_argument() {
+        let err = parse_script("resize 10;").unwrap_err();
+
+        assert_eq!(
+            err.kind,
+            ParseErrorKind::MissingArgument {
+                index: 2,
+                expected: ValueKind::UnsignedInteger
+            }
+        );
+        assert_eq!(err.span, span(1, 10));
+        assert_eq!(err.operation, Some("resize".to_string()));
+    }
+
+    #[test]
+    fn error_unexpected_argument() {
+        let err = parse_script("fliph 1").unwrap_err();
+
+        assert_eq!(
+            err.kind,
+            ParseErrorKind::UnexpectedArgument("1".to_string())
+        );
+        assert_eq!(err.span, span(1, 7));
+        assert_eq!(err.operation, Some("fliph".to_string()));
+    }
+
+    #[test]
+    fn error_missing_statement_separator() {
+        // Without a separator, `blur` is considered to be an argument of `fliph`.
+        let err = parse_script("fliph\nblur 1").unwrap_err();
+
+        assert_eq!(
+            err.kind,
+            ParseErrorKind::UnexpectedArgument("blur".to_string())
+        );
+        assert_eq!(err.span, span(2, 1));
+    }
+
+    #[test]
+    fn error_invalid_sampling_filter() {
+        let err = parse_script("set resize sampling-filter bicubic").unwrap_err();
+
+        assert_eq!(
+            err.kind,
+            ParseErrorKind::InvalidArgument {
+                index: 1,
+                expected: ValueKind::SamplingFilter,
+                found: "bicubic".to_string()
+            }
+        );
+        assert_eq!(err.span, span(1, 28));
+        assert_eq!(err.operation, Some("resize".to_string()));
+    }
+
+    #[test]
+    fn error_invalid_dither() {
+        let err = parse_script("set quantize dither atkinson").unwrap_err();
+
+        assert_eq!(
+            err.kind,
+            ParseErrorKind::InvalidArgument {
+                index: 1,
+                expected: ValueKind::Dither,
+                found: "atkinson".to_string()
+            }
+        );
+        assert_eq!(err.span, span(1, 21));
+    }
+
+    #[test]
+    fn error_invalid_color_component() {
+        let err = parse_script("set rotate fill-color 0 0 256 0").unwrap_err();
+
+        assert_eq!(
+            err.kind,
+            ParseErrorKind::InvalidArgument {
+                index: 3,
+                expected: ValueKind::ColorComponent,
+                found: "256".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn error_invalid_placement() {
+        let err = parse_script("overlay a.png middle 0.5").unwrap_err();
+
+        assert_eq!(
+            err.kind,
+            ParseErrorKind::InvalidArgument {
+                index: 2,
+                expected: ValueKind::Placement,
+                found: "middle".to_string()
+            }
+        );
+        assert_eq!(err.span, span(1, 15));
+    }
+
+    #[test]
+    fn error_invalid_anchor() {
+        let err = parse_script("pad-to 10 10 middle").unwrap_err();
+
+        assert_eq!(
+            err.kind,
+            ParseErrorKind::InvalidArgument {
+                index: 3,
+                expected: ValueKind::Anchor,
+                found: "middle".to_string()
+            }
+        );
+        assert_eq!(err.span, span(1, 14));
+    }
+
+    #[test]
+    fn error_invalid_control_point() {
+        let err = parse_script("curve 0,0 0.5;0.5").unwrap_err();
+
+        assert_eq!(
+            err.kind,
+            ParseErrorKind::InvalidArgument {
+                index: 2,
+                expected: ValueKind::ControlPoints,
+                found: "0.5".to_string()
+            }
+        );
+        assert_eq!(err.span, span(1, 11));
+    }
+
+    #[test]
+    fn error_missing_offset() {
+        let err = parse_script("overlay a.png 10 0.5").unwrap_err();
+
+        assert_eq!(
+            err.kind,
+            ParseErrorKind::InvalidArgument {
+                index: 3,
+                expected: ValueKind::Integer,
+                found: "0.5".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn error_unknown_option() {
+        let err = parse_script("set resize sampling_filter nearest").unwrap_err();
+
+        assert_eq!(
+            err.kind,
+            ParseErrorKind::UnknownOption("sampling_filter".to_string())
+        );
+        assert_eq!(err.span, span(1, 12));
+        assert_eq!(err.operation, Some("resize".to_string()));
+    }
+
+    #[test]
+    fn error_incomplete_set() {
+        assert_eq!(
+            parse_script("set;").unwrap_err().kind,
+            ParseErrorKind::MissingOperation
+        );
+        assert_eq!(
+            parse_script("del resize").unwrap_err().kind,
+            ParseErrorKind::MissingOption
+        );
+    }
+
+    #[test]
+    fn parse_resize_dimensions_word() {
+        let program = parse_script("resize 100x50; resize 3X4").unwrap();
+
+        assert_eq!(
+            program,
+            vec![
+                Statement::Operation(Operation::Resize(100, 50)),
+                Statement::Operation(Operation::Resize(3, 4)),
+            ]
+        );
+    }
+
+    #[test]
+    fn error_invalid_dimensions_word() {
+        let err = parse_script("resize 100x").unwrap_err();
+
+        assert_eq!(
+            err.kind,
+            ParseErrorKind::InvalidArgument {
+                index: 1,
+                expected: ValueKind::Dimensions,
+                found: "100x".to_string()
+            }
+        );
+
+        let err = parse_script("resize wide 10").unwrap_err();
+
+        assert_eq!(
+            err.kind,
+            ParseErrorKind::InvalidArgument {
+                index: 1,
+                expected: ValueKind::UnsignedInteger,
+                found: "wide".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn parse_op_arg_from_words() {
+        assert_eq!(
+            parse_op_arg("crop", &["0", "1", "2", "3"]).unwrap(),
+            OpArg::UnsignedIntegerTuple4(0, 1, 2, 3)
+        );
+        assert_eq!(
+            parse_op_arg("resize", &["100x50"]).unwrap(),
+            OpArg::UnsignedIntegerTuple2(100, 50)
+        );
+        assert_eq!(parse_op_arg("fliph", &[] as &[&str]).unwrap(), OpArg::Empty);
+
+        // words aren't split any further
+        let words = vec![
+            "my watermark.png".to_string(),
+            "10".to_string(),
+            "-5".to_string(),
+            "0.5".to_string(),
+        ];
+        assert_eq!(
+            parse_operation_words("overlay", &words).unwrap(),
+            Operation::Overlay(
+                "my watermark.png".to_string(),
+                Placement::Offset(10, -5),
+                0.5
+            )
+        );
+    }
+
+    #[test]
+    fn parse_op_arg_errors_match_script_errors() {
+        let cases: [(&str, &[&str]); 4] = [
+            ("crop", &["0", "0", "-1", "1"]),
+            ("resize", &["10"]),
+            ("fliph", &["1"]),
+            ("bluur", &["1"]),
+        ];
+
+        for (name, words) in cases.iter() {
+            let script = std::iter::once(*name)
+                .chain(words.iter().cloned())
+                .collect::<Vec<_>>()
+                .join(" ");
+
+            assert_eq!(
+                parse_script(&script).unwrap_err(),
+                parse_op_arg(name, words).unwrap_err()
+            );
+        }
+    }
+
+    #[test]
+    fn error_kind_without_position() {
+        let err = parse_op_arg("crop", &["0", "0", "-1", "1"]).unwrap_err();
+
+        assert_eq!(
+            format!("{}: {}", err.operation.unwrap(), err.kind),
+            "crop: argument 3 must be an unsigned integer, but found `-1`"
+        );
+    }
+}
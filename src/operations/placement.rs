@@ -0,0 +1,154 @@
+use crate::error::Error;
+
+// Position of an item within a container, e.g. of an overlay on top of an image.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+pub enum Anchor {
+    TopLeft,
+    Top,
+    TopRight,
+    Left,
+    Center,
+    Right,
+    BottomLeft,
+    Bottom,
+    BottomRight,
+}
+
+impl Anchor {
+    pub fn try_from_str(val: &str) -> Result<Anchor, Error> {
+        match val.to_lowercase().as_str() {
+            "top-left" => Ok(Anchor::TopLeft),
+            "top" => Ok(Anchor::Top),
+            "top-right" => Ok(Anchor::TopRight),
+            "left" => Ok(Anchor::Left),
+            "center" => Ok(Anchor::Center),
+            "right" => Ok(Anchor::Right),
+            "bottom-left" => Ok(Anchor::BottomLeft),
+            "bottom" => Ok(Anchor::Bottom),
+            "bottom-right" => Ok(Anchor::BottomRight),
+            fail => Err(Error::UnknownAnchor(fail.to_string())),
+        }
+    }
+
+    // Canonical name of the anchor; accepted by `try_from_str`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Anchor::TopLeft => "top-left",
+            Anchor::Top => "top",
+            Anchor::TopRight => "top-right",
+            Anchor::Left => "left",
+            Anchor::Center => "center",
+            Anchor::Right => "right",
+            Anchor::BottomLeft => "bottom-left",
+            Anchor::Bottom => "bottom",
+            Anchor::BottomRight => "bottom-right",
+        }
+    }
+
+    // Offset of the top left corner of an item with dimensions `item` which is anchored within
+    // a container with dimensions `container`. The offset is negative if the item is larger
+    // than the container.
+    pub fn offset(self, container: (u32, u32), item: (u32, u32)) -> (i64, i64) {
+        let free_x = i64::from(container.0) - i64::from(item.0);
+        let free_y = i64::from(container.1) - i64::from(item.1);
+
+        let x = match self {
+            Anchor::TopLeft | Anchor::Left | Anchor::BottomLeft => 0,
+            Anchor::Top | Anchor::Center | Anchor::Bottom => free_x / 2,
+            Anchor::TopRight | Anchor::Right | Anchor::BottomRight => free_x,
+        };
+
+        let y = match self {
+            Anchor::TopLeft | Anchor::Top | Anchor::TopRight => 0,
+            Anchor::Left | Anchor::Center | Anchor::Right => free_y / 2,
+            Anchor::BottomLeft | Anchor::Bottom | Anchor::BottomRight => free_y,
+        };
+
+        (x, y)
+    }
+}
+
+// Placement of an item within a container, either relative to one of its anchors or at an
+// explicit offset of its top left corner from the top left corner of the container.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+pub enum Placement {
+    Anchor(Anchor),
+    Offset(i32, i32),
+}
+
+impl Placement {
+    pub fn offset(self, container: (u32, u32), item: (u32, u32)) -> (i64, i64) {
+        match self {
+            Placement::Anchor(anchor) => anchor.offset(container, item),
+            Placement::Offset(x, y) => (i64::from(x), i64::from(y)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ANCHORS: [Anchor; 9] = [
+        Anchor::TopLeft,
+        Anchor::Top,
+        Anchor::TopRight,
+        Anchor::Left,
+        Anchor::Center,
+        Anchor::Right,
+        Anchor::BottomLeft,
+        Anchor::Bottom,
+        Anchor::BottomRight,
+    ];
+
+    #[test]
+    fn name_round_trip() {
+        for anchor in ANCHORS.iter() {
+            assert_eq!(*anchor, Anchor::try_from_str(anchor.name()).unwrap());
+        }
+    }
+
+    #[test]
+    fn unknown_anchor() {
+        assert!(Anchor::try_from_str("middle").is_err());
+    }
+
+    #[test]
+    fn anchor_offsets() {
+        let expected = [
+            (0, 0),
+            (3, 0),
+            (7, 0),
+            (0, 2),
+            (3, 2),
+            (7, 2),
+            (0, 5),
+            (3, 5),
+            (7, 5),
+        ];
+
+        for (anchor, expected) in ANCHORS.iter().zip(expected.iter()) {
+            assert_eq!(*expected, anchor.offset((10, 8), (3, 3)), "{:?}", anchor);
+        }
+    }
+
+    #[test]
+    fn anchor_offset_of_larger_item() {
+        assert_eq!((-2, -4), Anchor::BottomRight.offset((2, 2), (4, 6)));
+    }
+
+    #[test]
+    fn explicit_offset() {
+        assert_eq!((-1, 5), Placement::Offset(-1, 5).offset((2, 2), (4, 6)));
+    }
+}
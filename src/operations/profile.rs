@@ -0,0 +1,307 @@
+// Per statement profiling of an `ImageEngine`; see `ImageEngine::enable_profiling`.
+//
+// With the `serde` feature, a report can be serialized, e.g. as JSON:
+//
+//   {
+//     "statements": [
+//       {
+//         "index": 0,
+//         "statement": { "type": "operation", "value": { "op": "resize", "args": [100, 100] } },
+//         "duration_ms": 12.5,
+//         "input": { "dimensions": [217, 447], "color_type": "rgb8", "buffer_size": 291003 },
+//         "output": { "dimensions": [100, 100], "color_type": "rgb8", "buffer_size": 30000 }
+//       }
+//     ]
+//   }
+use std::fmt;
+use std::time::Duration;
+
+use image::{ColorType, DynamicImage, GenericImageView};
+
+use crate::operations::engine::Statement;
+use crate::operations::history::image_size;
+
+// The image before or after a statement.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ImageProfile {
+    pub dimensions: (u32, u32),
+    #[cfg_attr(feature = "serde", serde(serialize_with = "serialize_color_type"))]
+    pub color_type: ColorType,
+    // Approximate size of the pixel buffer, in bytes.
+    pub buffer_size: usize,
+}
+
+impl ImageProfile {
+    pub(crate) fn of(image: &DynamicImage) -> Self {
+        ImageProfile {
+            dimensions: image.dimensions(),
+            color_type: image.color(),
+            buffer_size: image_size(image),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct StatementProfile {
+    // Position of the statement among the profiled statements.
+    pub index: usize,
+    pub statement: Statement,
+    // Wall time the statement took.
+    #[cfg_attr(
+        feature = "serde",
+        serde(rename = "duration_ms", serialize_with = "serialize_millis")
+    )]
+    pub duration: Duration,
+    pub input: ImageProfile,
+    pub output: ImageProfile,
+}
+
+// Report of the statements which were processed successfully while profiling was enabled.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Profile {
+    pub statements: Vec<StatementProfile>,
+}
+
+impl Profile {
+    pub(crate) fn record(
+        &mut self,
+        statement: Statement,
+        duration: Duration,
+        input: ImageProfile,
+        output: ImageProfile,
+    ) {
+        self.statements.push(StatementProfile {
+            index: self.statements.len(),
+            statement,
+            duration,
+            input,
+            output,
+        });
+    }
+
+    pub fn total_duration(&self) -> Duration {
+        self.statements.iter().map(|s| s.duration).sum()
+    }
+
+    // The largest image buffer seen by any of the statements, in bytes.
+    pub fn peak_buffer_size(&self) -> usize {
+        self.statements
+            .iter()
+            .map(|s| s.input.buffer_size.max(s.output.buffer_size))
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+fn color_type_name(color_type: ColorType) -> String {
+    match color_type {
+        ColorType::Gray(bits) => format!("gray{}", bits),
+        ColorType::GrayA(bits) => format!("graya{}", bits),
+        ColorType::RGB(bits) => format!("rgb{}", bits),
+        ColorType::RGBA(bits) => format!("rgba{}", bits),
+        ColorType::BGR(bits) => format!("bgr{}", bits),
+        ColorType::BGRA(bits) => format!("bgra{}", bits),
+        ColorType::Palette(bits) => format!("palette{}", bits),
+    }
+}
+
+fn statement_description(statement: &Statement) -> String {
+    match statement {
+        Statement::Operation(operation) => format!("{:?}", operation),
+        Statement::RegisterEnvironmentItem(item) => format!("set {:?}", item),
+        Statement::DeregisterEnvironmentItem(key) => format!("del {:?}", key),
+    }
+}
+
+fn image_description(image: &ImageProfile) -> String {
+    format!(
+        "{}x{} {}",
+        image.dimensions.0,
+        image.dimensions.1,
+        color_type_name(image.color_type)
+    )
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+#[cfg(feature = "serde")]
+fn serialize_color_type<S: serde::Serializer>(
+    color_type: &ColorType,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&color_type_name(*color_type))
+}
+
+#[cfg(feature = "serde")]
+fn serialize_millis<S: serde::Serializer>(
+    duration: &Duration,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_f64(millis(*duration))
+}
+
+// Prints the report as a table, with a row per statement.
+impl fmt::Display for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let rows: Vec<[String; 6]> = self
+            .statements
+            .iter()
+            .map(|s| {
+                [
+                    s.index.to_string(),
+                    statement_description(&s.statement),
+                    format!("{:.3}", millis(s.duration)),
+                    image_description(&s.input),
+                    image_description(&s.output),
+                    s.output.buffer_size.to_string(),
+                ]
+            })
+            .collect();
+
+        let header = [
+            "#",
+            "statement",
+            "time (ms)",
+            "input",
+            "output",
+            "output size (bytes)",
+        ];
+
+        let mut widths = [0; 6];
+        for (i, width) in widths.iter_mut().enumerate() {
+            *width = rows
+                .iter()
+                .map(|row| row[i].len())
+                .chain(std::iter::once(header[i].len()))
+                .max()
+                .unwrap_or(0);
+        }
+
+        let write_row = |f: &mut fmt::Formatter, row: [&str; 6]| {
+            writeln!(
+                f,
+                "{:>w0$}  {:<w1$}  {:>w2$}  {:<w3$}  {:<w4$}  {:>w5$}",
+                row[0],
+                row[1],
+                row[2],
+                row[3],
+                row[4],
+                row[5],
+                w0 = widths[0],
+                w1 = widths[1],
+                w2 = widths[2],
+                w3 = widths[3],
+                w4 = widths[4],
+                w5 = widths[5],
+            )
+        };
+
+        write_row(f, header)?;
+
+        for row in rows.iter() {
+            write_row(f, [&row[0], &row[1], &row[2], &row[3], &row[4], &row[5]])?;
+        }
+
+        writeln!(
+            f,
+            "total: {:.3} ms, peak buffer size: {} bytes",
+            millis(self.total_duration()),
+            self.peak_buffer_size()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::operations::engine::ImageEngine;
+    use crate::operations::mod_test_includes::*;
+    use crate::operations::Operation;
+
+    use super::*;
+
+    fn setup_profile() -> Profile {
+        let img: DynamicImage = setup_test_image("resources/rainbow_8x6.bmp");
+        let mut engine = ImageEngine::new(img);
+        engine.enable_profiling();
+
+        engine
+            .ignite(vec![
+                Statement::Operation(Operation::Resize(4, 2)),
+                Statement::Operation(Operation::GrayScale),
+            ])
+            .unwrap();
+
+        engine.take_profile().unwrap()
+    }
+
+    #[test]
+    fn profile_statements() {
+        let profile = setup_profile();
+
+        assert_eq!(2, profile.statements.len());
+
+        let resize = &profile.statements[0];
+        assert_eq!(
+            Statement::Operation(Operation::Resize(4, 2)),
+            resize.statement
+        );
+        assert_eq!((8, 6), resize.input.dimensions);
+        assert_eq!(144, resize.input.buffer_size);
+        assert_eq!((4, 2), resize.output.dimensions);
+        assert_eq!(24, resize.output.buffer_size);
+
+        let gray = &profile.statements[1];
+        assert_eq!(1, gray.index);
+        assert_eq!(ColorType::RGB(8), gray.input.color_type);
+        assert_eq!(ColorType::Gray(8), gray.output.color_type);
+        assert_eq!(8, gray.output.buffer_size);
+
+        assert_eq!(144, profile.peak_buffer_size());
+    }
+
+    #[test]
+    fn profile_table() {
+        let table = setup_profile().to_string();
+        let lines: Vec<&str> = table.lines().collect();
+
+        assert_eq!(4, lines.len());
+        assert!(lines[0].starts_with("#  statement"));
+        assert!(lines[1].contains("Resize(4, 2)"));
+        assert!(lines[1].contains("8x6 rgb8"));
+        assert!(lines[2].contains("4x2 gray8"));
+        assert!(lines[3].starts_with("total: "));
+    }
+
+    #[test]
+    fn profiling_is_opt_in() {
+        let img: DynamicImage = setup_test_image("resources/rainbow_8x6.bmp");
+        let mut engine = ImageEngine::new(img);
+
+        engine
+            .ignite(vec![Statement::Operation(Operation::Invert)])
+            .unwrap();
+
+        assert!(engine.profile().is_none());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn profile_json() {
+        let json = serde_json::to_value(setup_profile()).unwrap();
+        let resize = &json["statements"][0];
+
+        assert_eq!(
+            serde_json::json!({ "op": "resize", "args": [4, 2] }),
+            resize["statement"]["value"]
+        );
+        assert!(resize["duration_ms"].is_f64());
+        assert_eq!(serde_json::json!([8, 6]), resize["input"]["dimensions"]);
+        assert_eq!("rgb8", resize["input"]["color_type"]);
+        assert_eq!(24, resize["output"]["buffer_size"]);
+    }
+}
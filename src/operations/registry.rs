@@ -0,0 +1,247 @@
+// Registry of user-defined operations, which run alongside the built-in operations.
+//
+// A registry is an explicit value: an operation registered in one registry is only known to the
+// parser, `validate` and engines which are given that registry. Within a program, a registered
+// operation is represented as `Operation::Custom`, and is looked up when no built-in operation
+// has the same name.
+use std::collections::BTreeMap;
+use std::error;
+use std::fmt;
+use std::sync::Arc;
+
+use image::DynamicImage;
+
+use crate::error::Error;
+use crate::operations::suggestion::suggest_operation;
+use crate::operations::{builtin_arg_kind, resolve_operation, OpArg, OpArgKind, Operation};
+
+// An image operation which isn't built in.
+pub trait ImageOperation: Send + Sync {
+    // Name by which the operation is used in programs and scripts. It should be a single word,
+    // i.e. it can't contain whitespace, `;` or `#`.
+    fn name(&self) -> &str;
+
+    // The shape of the argument the operation takes.
+    fn arg_kind(&self) -> OpArgKind;
+
+    // Dimensions of the image produced by `apply` for an image with the given dimensions; used
+    // by `validate`. By default, the dimensions are unchanged.
+    fn output_dimensions(&self, dimensions: (u32, u32), _arg: &OpArg) -> (u32, u32) {
+        dimensions
+    }
+
+    // Applies the operation; `arg` always has the shape given by `arg_kind`.
+    fn apply(
+        &self,
+        image: &DynamicImage,
+        arg: &OpArg,
+    ) -> Result<DynamicImage, Box<dyn error::Error + Send + Sync>>;
+}
+
+// The user-defined operations known to a parser, `validate` or engine. Cloning a registry is
+// cheap, since the operations themselves are shared.
+#[derive(Clone, Default)]
+pub struct Registry {
+    operations: BTreeMap<String, Arc<dyn ImageOperation>>,
+}
+
+impl Registry {
+    pub fn new() -> Self {
+        Registry::default()
+    }
+
+    // Registers `operation` under its name. Fails if the name isn't a single word, or if it's
+    // already used by a built-in or a registered operation.
+    pub fn register<O: ImageOperation + 'static>(&mut self, operation: O) -> Result<(), Error> {
+        let name = operation.name().to_string();
+
+        let invalid = name.is_empty()
+            || name == "set"
+            || name == "del"
+            || name
+                .chars()
+                .any(|c| c.is_whitespace() || c == ';' || c == '#');
+
+        if invalid {
+            return Err(Error::InvalidOperationName(name));
+        }
+
+        if builtin_arg_kind(&name).is_some() || self.operations.contains_key(&name) {
+            return Err(Error::OperationAlreadyRegistered(name));
+        }
+
+        self.operations.insert(name, Arc::new(operation));
+
+        Ok(())
+    }
+
+    // Returns whether an operation was registered under `name`.
+    pub fn unregister(&mut self, name: &str) -> bool {
+        self.operations.remove(name).is_some()
+    }
+
+    pub fn lookup(&self, name: &str) -> Option<Arc<dyn ImageOperation>> {
+        self.operations.get(name).cloned()
+    }
+
+    // Names of the registered operations, in alphabetical order.
+    pub fn registered_names(&self) -> Vec<String> {
+        self.operations.keys().cloned().collect()
+    }
+
+    // The argument shape accepted by the built-in or registered operation with the given name.
+    pub fn arg_kind_by_name(&self, name: &str) -> Option<OpArgKind> {
+        builtin_arg_kind(name).or_else(|| self.lookup(name).map(|operation| operation.arg_kind()))
+    }
+
+    // Like `operations::operation_by_name`, but registered operations are found as well.
+    pub fn operation_by_name(&self, name: &str, value: OpArg) -> Result<Operation, Error> {
+        resolve_operation(name, value, self)
+    }
+
+    // The registered operation with the given name, if it accepts `arg`.
+    pub(crate) fn resolve(
+        &self,
+        name: &str,
+        arg: &OpArg,
+    ) -> Result<Arc<dyn ImageOperation>, Error> {
+        let operation = self.lookup(name).ok_or_else(|| Error::UnknownOperation {
+            name: name.to_string(),
+            suggestion: suggest_operation(name, self),
+        })?;
+
+        if operation.arg_kind() == arg.kind() {
+            Ok(operation)
+        } else {
+            Err(Error::OperationArgumentMismatch {
+                name: name.to_string(),
+                expected: operation.arg_kind(),
+                given: arg.kind(),
+            })
+        }
+    }
+}
+
+impl fmt::Debug for Registry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_set().entries(self.operations.keys()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::GenericImageView;
+
+    use super::*;
+
+    struct Named(&'static str);
+
+    impl ImageOperation for Named {
+        fn name(&self) -> &str {
+            self.0
+        }
+
+        fn arg_kind(&self) -> OpArgKind {
+            OpArgKind::Empty
+        }
+
+        fn apply(
+            &self,
+            image: &DynamicImage,
+            _arg: &OpArg,
+        ) -> Result<DynamicImage, Box<dyn error::Error + Send + Sync>> {
+            Ok(image.clone())
+        }
+    }
+
+    #[test]
+    fn register_and_unregister() {
+        let mut registry = Registry::new();
+        registry.register(Named("sepia")).unwrap();
+
+        let operation = registry.lookup("sepia").unwrap();
+        assert_eq!("sepia", operation.name());
+        assert_eq!((3, 4), operation.output_dimensions((3, 4), &OpArg::Empty));
+        assert_eq!(vec!["sepia".to_string()], registry.registered_names());
+        assert_eq!(Some(OpArgKind::Empty), registry.arg_kind_by_name("sepia"));
+        assert_eq!(
+            Some(OpArgKind::FloatingPoint),
+            registry.arg_kind_by_name("blur")
+        );
+
+        assert!(registry.unregister("sepia"));
+        assert!(registry.lookup("sepia").is_none());
+        assert!(!registry.unregister("sepia"));
+    }
+
+    #[test]
+    fn registries_are_independent() {
+        let mut registry = Registry::new();
+        registry.register(Named("sepia")).unwrap();
+
+        let other = Registry::new();
+
+        assert!(other.lookup("sepia").is_none());
+        assert!(other.registered_names().is_empty());
+        assert!(registry.clone().lookup("sepia").is_some());
+    }
+
+    #[test]
+    fn register_name_in_use() {
+        let mut registry = Registry::new();
+
+        assert!(matches!(
+            registry.register(Named("blur")),
+            Err(Error::OperationAlreadyRegistered(_))
+        ));
+
+        registry.register(Named("sepia")).unwrap();
+        assert!(matches!(
+            registry.register(Named("sepia")),
+            Err(Error::OperationAlreadyRegistered(_))
+        ));
+    }
+
+    #[test]
+    fn register_invalid_name() {
+        let mut registry = Registry::new();
+
+        for name in ["", "set", "del", "two words", "semi;colon", "#comment"].iter() {
+            assert!(matches!(
+                registry.register(Named(name)),
+                Err(Error::InvalidOperationName(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn resolve_checks_arg_kind() {
+        let mut registry = Registry::new();
+        registry.register(Named("sepia")).unwrap();
+
+        let image = DynamicImage::new_rgb8(2, 1);
+        let operation = registry.resolve("sepia", &OpArg::Empty).unwrap();
+        assert_eq!(
+            (2, 1),
+            operation.apply(&image, &OpArg::Empty).unwrap().dimensions()
+        );
+
+        assert!(matches!(
+            registry.resolve("sepia", &OpArg::Integer(1)),
+            Err(Error::OperationArgumentMismatch {
+                expected: OpArgKind::Empty,
+                given: OpArgKind::Integer,
+                ..
+            })
+        ));
+
+        // registered operations are suggested too
+        assert!(matches!(
+            registry.resolve("sepa", &OpArg::Empty),
+            Err(Error::UnknownOperation {
+                suggestion: Some(_),
+                ..
+            })
+        ));
+    }
+}
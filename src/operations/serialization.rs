@@ -0,0 +1,274 @@
+// Versioned (de)serialization of engine programs; requires the `serde` feature.
+//
+// A serialized program has the following shape (here shown as JSON):
+//
+//   {
+//     "version": 1,
+//     "program": [
+//       { "type": "set", "value": { "option": "resize-sampling-filter", "value": "lanczos3" } },
+//       { "type": "operation", "value": { "op": "resize", "args": [100, 100] } },
+//       { "type": "operation", "value": { "op": "fliph" } },
+//       { "type": "del", "value": "resize-sampling-filter" }
+//     ]
+//   }
+//
+// Operations and options are identified by the same names as used by `operation_by_name` and
+// the script parser. The version is increased whenever the shape changes in an incompatible way.
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::operations::engine::Program;
+
+pub const PROGRAM_FORMAT_VERSION: u32 = 1;
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "UncheckedVersionedProgram")]
+pub struct VersionedProgram {
+    version: u32,
+    program: Program,
+}
+
+impl VersionedProgram {
+    pub fn new(program: Program) -> Self {
+        VersionedProgram {
+            version: PROGRAM_FORMAT_VERSION,
+            program,
+        }
+    }
+
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    pub fn program(&self) -> &Program {
+        &self.program
+    }
+
+    pub fn into_program(self) -> Program {
+        self.program
+    }
+}
+
+impl From<Program> for VersionedProgram {
+    fn from(program: Program) -> Self {
+        VersionedProgram::new(program)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct UnsupportedVersion(pub u32);
+
+impl fmt::Display for UnsupportedVersion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "unsupported program format version {} (supported: {})",
+            self.0, PROGRAM_FORMAT_VERSION
+        )
+    }
+}
+
+impl Error for UnsupportedVersion {}
+
+// Deserialization target of `VersionedProgram`, before its version has been checked.
+#[derive(Deserialize)]
+struct UncheckedVersionedProgram {
+    version: u32,
+    program: Program,
+}
+
+impl TryFrom<UncheckedVersionedProgram> for VersionedProgram {
+    type Error = UnsupportedVersion;
+
+    fn try_from(unchecked: UncheckedVersionedProgram) -> Result<Self, Self::Error> {
+        if unchecked.version == PROGRAM_FORMAT_VERSION {
+            Ok(VersionedProgram {
+                version: unchecked.version,
+                program: unchecked.program,
+            })
+        } else {
+            Err(UnsupportedVersion(unchecked.version))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::operations::engine::{EnvironmentItem, EnvironmentKind, Statement};
+    use crate::operations::placement::{Anchor, Placement};
+    use crate::operations::transformations::dither::Dither;
+    use crate::operations::transformations::quantize::QuantizeMethod;
+    use crate::operations::wrapper::filter_type::FilterTypeWrap;
+    use crate::operations::{OpArg, Operation};
+
+    use super::*;
+
+    fn all_operations() -> Vec<Operation> {
+        vec![
+            Operation::AutoLevels(0.5),
+            Operation::Blur(1.5),
+            Operation::Brighten(-25),
+            Operation::Contrast(0.1),
+            Operation::Crop(0, 1, 2, 3),
+            Operation::Custom("brand-grade".to_string(), OpArg::FloatingPoint(0.5)),
+            Operation::Custom(
+                "brand-frame".to_string(),
+                OpArg::PathPlacementFloatingPoint(
+                    "frame.png".to_string(),
+                    Placement::Offset(1, 2),
+                    0.5,
+                ),
+            ),
+            Operation::Curve(vec![(0.0, 0.1), (0.5, 0.45), (1.0, 1.0)]),
+            Operation::Dither(Dither::FloydSteinberg, 6),
+            Operation::Equalize,
+            Operation::EqualizeLuminance,
+            Operation::Filter3x3([0.5, 1.0, -1.5, 2.0, 0.25, 3.0, -4.0, 1e-3, 8.75]),
+            Operation::FlipHorizontal,
+            Operation::FlipVertical,
+            Operation::Gamma(2.2),
+            Operation::GrayScale,
+            Operation::HueRotate(-399),
+            Operation::Invert,
+            Operation::Levels(0.1, 0.9, 1.2, 0.0, 1.0),
+            Operation::Overlay(
+                "watermark.png".to_string(),
+                Placement::Anchor(Anchor::BottomRight),
+                0.5,
+            ),
+            Operation::Overlay("watermark.png".to_string(), Placement::Offset(-4, 8), 1.0),
+            Operation::Pad(1, 2, 3, 4),
+            Operation::PadTo(80, 40, Anchor::TopRight),
+            Operation::Quantize(16),
+            Operation::Resize(80, 40),
+            Operation::ResizeFill(80, 40),
+            Operation::ResizeFit(80, 40),
+            Operation::ResizeHeight(40),
+            Operation::ResizePercent(12.5),
+            Operation::ResizeWidth(80),
+            Operation::Rotate(-1.7),
+            Operation::Rotate90,
+            Operation::Rotate180,
+            Operation::Rotate270,
+            Operation::Thumbnail(64, 48),
+            Operation::Unsharpen(1.5, 3),
+        ]
+    }
+
+    fn all_environment_items() -> Vec<EnvironmentItem> {
+        vec![
+            EnvironmentItem::PadFillColor([0, 0, 0, 255]),
+            EnvironmentItem::QuantizeDither(Dither::Bayer),
+            EnvironmentItem::QuantizeMethod(QuantizeMethod::KMeans),
+            EnvironmentItem::OptResizeSamplingFilter(FilterTypeWrap::Inner(
+                image::FilterType::Lanczos3,
+            )),
+            EnvironmentItem::PreserveAspectRatio,
+            EnvironmentItem::ResizeShrinkOnly,
+            EnvironmentItem::OptRotateSamplingFilter(FilterTypeWrap::Inner(
+                image::FilterType::CatmullRom,
+            )),
+            EnvironmentItem::RotateExpandCanvas,
+            EnvironmentItem::RotateFillColor([255, 128, 0, 64]),
+        ]
+    }
+
+    fn all_environment_kinds() -> Vec<EnvironmentKind> {
+        vec![
+            EnvironmentKind::OptPadFillColor,
+            EnvironmentKind::OptQuantizeDither,
+            EnvironmentKind::OptQuantizeMethod,
+            EnvironmentKind::OptResizeSamplingFilter,
+            EnvironmentKind::OptResizePreserveAspectRatio,
+            EnvironmentKind::OptResizeShrinkOnly,
+            EnvironmentKind::OptRotateSamplingFilter,
+            EnvironmentKind::OptRotateExpandCanvas,
+            EnvironmentKind::OptRotateFillColor,
+        ]
+    }
+
+    fn round_trip(program: Program) -> Program {
+        let json = serde_json::to_string(&VersionedProgram::new(program))
+            .expect("Unable to serialize program.");
+
+        serde_json::from_str::<VersionedProgram>(&json)
+            .expect("Unable to deserialize program.")
+            .into_program()
+    }
+
+    #[test]
+    fn round_trip_every_operation() {
+        for operation in all_operations() {
+            let program = vec![Statement::Operation(operation.clone())];
+
+            assert_eq!(round_trip(program), vec![Statement::Operation(operation)]);
+        }
+    }
+
+    #[test]
+    fn round_trip_every_environment_statement() {
+        let statements = || {
+            all_environment_items()
+                .into_iter()
+                .map(Statement::RegisterEnvironmentItem)
+                .chain(
+                    all_environment_kinds()
+                        .into_iter()
+                        .map(Statement::DeregisterEnvironmentItem),
+                )
+                .collect::<Program>()
+        };
+
+        assert_eq!(round_trip(statements()), statements());
+    }
+
+    #[test]
+    fn stable_shape() {
+        let program = vec![
+            Statement::RegisterEnvironmentItem(EnvironmentItem::OptResizeSamplingFilter(
+                FilterTypeWrap::Inner(image::FilterType::Lanczos3),
+            )),
+            Statement::Operation(Operation::Resize(100, 100)),
+            Statement::Operation(Operation::FlipHorizontal),
+            Statement::DeregisterEnvironmentItem(EnvironmentKind::OptResizeSamplingFilter),
+        ];
+
+        let json = serde_json::to_value(VersionedProgram::new(program)).unwrap();
+
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "version": 1,
+                "program": [
+                    { "type": "set", "value": { "option": "resize-sampling-filter", "value": "lanczos3" } },
+                    { "type": "operation", "value": { "op": "resize", "args": [100, 100] } },
+                    { "type": "operation", "value": { "op": "fliph" } },
+                    { "type": "del", "value": "resize-sampling-filter" }
+                ]
+            })
+        );
+    }
+
+    #[test]
+    fn deserialize_unsupported_version() {
+        let json = r#"{ "version": 2, "program": [] }"#;
+
+        let err = serde_json::from_str::<VersionedProgram>(json).unwrap_err();
+
+        assert!(err
+            .to_string()
+            .contains("unsupported program format version 2"));
+    }
+
+    #[test]
+    fn deserialize_unknown_sampling_filter() {
+        let json = r#"{ "version": 1, "program": [
+            { "type": "set", "value": { "option": "resize-sampling-filter", "value": "bicubic" } }
+        ] }"#;
+
+        assert!(serde_json::from_str::<VersionedProgram>(json).is_err());
+    }
+}
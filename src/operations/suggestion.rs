@@ -0,0 +1,95 @@
+// Suggestions for misspelled names, e.g. "did you mean `huerotate`?" for `huerotat`.
+use crate::operations::metadata;
+use crate::operations::registry::Registry;
+
+// The name of a built-in operation, or of an operation of `registry`, which is closest to
+// `name`, if any is close enough to be a likely misspelling.
+pub(crate) fn suggest_operation(name: &str, registry: &Registry) -> Option<String> {
+    let builtin = metadata::all_operations()
+        .iter()
+        .map(|metadata| metadata.name.to_string());
+
+    closest(name, builtin.chain(registry.registered_names()))
+}
+
+// The candidate with the smallest edit distance to `name`. At most a third of the characters
+// (and at least one) may differ; on a tie, the first candidate wins.
+pub(crate) fn closest<I>(name: &str, candidates: I) -> Option<String>
+where
+    I: IntoIterator<Item = String>,
+{
+    let name = name.to_lowercase();
+    let max_distance = (name.chars().count() / 3).max(1);
+
+    candidates
+        .into_iter()
+        .map(|candidate| (edit_distance(&name, &candidate.to_lowercase()), candidate))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+// Levenshtein distance: the least amount of inserted, removed or substituted characters needed
+// to turn `a` into `b`.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + if ca == *cb { 0 } else { 1 };
+
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+
+        previous = current;
+    }
+
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn edit_distances() {
+        assert_eq!(0, edit_distance("blur", "blur"));
+        assert_eq!(1, edit_distance("blurr", "blur"));
+        assert_eq!(1, edit_distance("huerotat", "huerotate"));
+        assert_eq!(1, edit_distance("fliph", "flipv"));
+        assert_eq!(1, edit_distance("fliph", "flip"));
+        assert_eq!(2, edit_distance("flph", "flipv"));
+        assert_eq!(3, edit_distance("", "abc"));
+        assert_eq!(3, edit_distance("kitten", "sitting"));
+    }
+
+    #[test]
+    fn suggest_builtin_operations() {
+        let cases = [
+            ("huerotat", "huerotate"),
+            ("Blur", "blur"),
+            ("rotat90", "rotate90"),
+            ("grayscal", "grayscale"),
+            ("unsharp", "unsharpen"),
+            ("flph", "fliph"),
+        ];
+
+        for (misspelled, expected) in cases.iter() {
+            assert_eq!(
+                Some(expected.to_string()),
+                suggest_operation(misspelled, &Registry::default())
+            );
+        }
+    }
+
+    #[test]
+    fn no_suggestion_for_unrelated_names() {
+        let registry = Registry::default();
+
+        assert_eq!(None, suggest_operation("sepia", &registry));
+        assert_eq!(None, suggest_operation("x", &registry));
+    }
+}
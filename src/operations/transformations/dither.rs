@@ -0,0 +1,277 @@
+// Mapping of the colors of an image to a (small) palette, with optional dithering: areas with a
+// color which isn't in the palette are approximated by a pattern of colors which are.
+use std::cmp::Ordering;
+
+use image::{DynamicImage, GrayImage, ImageBuffer, Luma, Pixel, Rgb, Rgba};
+
+use crate::error::Error;
+use crate::operations::transformations::{dynamic_map, FromRgba8};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+pub enum Dither {
+    // Error diffusion: the difference between the color of a pixel and the palette color it's
+    // mapped to is spread over the neighbouring pixels which haven't been mapped yet.
+    FloydSteinberg,
+    // Ordered dithering: the color of a pixel is offset by the threshold at its position in a
+    // repeating 8x8 Bayer matrix before it's mapped.
+    Bayer,
+}
+
+impl Dither {
+    pub fn try_from_str(val: &str) -> Result<Dither, Error> {
+        match val.to_lowercase().as_str() {
+            "floyd-steinberg" => Ok(Dither::FloydSteinberg),
+            "bayer" => Ok(Dither::Bayer),
+            fail => Err(Error::UnknownDither(fail.to_string())),
+        }
+    }
+}
+
+const BAYER: [[u8; 8]; 8] = [
+    [0, 32, 8, 40, 2, 34, 10, 42],
+    [48, 16, 56, 24, 50, 18, 58, 26],
+    [12, 44, 4, 36, 14, 46, 6, 38],
+    [60, 28, 52, 20, 62, 30, 54, 22],
+    [3, 35, 11, 43, 1, 33, 9, 41],
+    [51, 19, 59, 27, 49, 17, 57, 25],
+    [15, 47, 7, 39, 13, 45, 5, 37],
+    [63, 31, 55, 23, 61, 29, 53, 21],
+];
+
+// Maps the color of each pixel to the closest color of the palette, which shouldn't be empty
+// unless the image is. The alpha channel, if any, is left as it is.
+pub fn map_to_palette(
+    image: &DynamicImage,
+    palette: &[Rgb<u8>],
+    dither: Option<Dither>,
+) -> DynamicImage {
+    dynamic_map!(*image, ref buffer => map_buffer_to_palette(buffer, palette, dither))
+}
+
+// The palette of all colors of which each channel is one of `levels` evenly spaced values,
+// including 0 and 255; e.g. 6 levels give the web-safe palette.
+pub fn uniform_palette(levels: u32) -> Vec<Rgb<u8>> {
+    let values: Vec<u8> = (0..levels)
+        .map(|level| (f64::from(level) * 255.0 / f64::from(levels.max(2) - 1)).round() as u8)
+        .collect();
+
+    let mut palette = Vec::with_capacity(values.len().pow(3));
+
+    for &r in &values {
+        for &g in &values {
+            for &b in &values {
+                palette.push(Rgb([r, g, b]));
+            }
+        }
+    }
+
+    palette
+}
+
+// A black and white image, in which each pixel with a luminance at or above `level` is white.
+pub fn threshold(image: &DynamicImage, level: u8) -> GrayImage {
+    let mut gray = image.to_luma();
+
+    for pixel in gray.pixels_mut() {
+        *pixel = if pixel[0] >= level {
+            Luma([255])
+        } else {
+            Luma([0])
+        };
+    }
+
+    gray
+}
+
+// A black and white image, in which the luminance of the pixels is approximated by dithering.
+pub fn black_and_white(image: &DynamicImage, dither: Dither) -> GrayImage {
+    let palette = [Rgb([0, 0, 0]), Rgb([255, 255, 255])];
+
+    map_buffer_to_palette(&image.to_luma(), &palette, Some(dither))
+}
+
+fn map_buffer_to_palette<P>(
+    buffer: &ImageBuffer<P, Vec<u8>>,
+    palette: &[Rgb<u8>],
+    dither: Option<Dither>,
+) -> ImageBuffer<P, Vec<u8>>
+where
+    P: Pixel<Subpixel = u8> + FromRgba8 + 'static,
+{
+    let (width, height) = buffer.dimensions();
+    let spread = bayer_spread(palette.len());
+
+    // Errors diffused to the current and to the next row, with a column of padding on both sides.
+    let mut errors = vec![[0.0f32; 3]; width as usize + 2];
+    let mut next_errors = errors.clone();
+
+    let mut result = buffer.clone();
+
+    for y in 0..height {
+        for x in 0..width {
+            let pixel = result.get_pixel_mut(x, y);
+            let Rgba([r, g, b, alpha]) = pixel.to_rgba();
+            let mut color = [f32::from(r), f32::from(g), f32::from(b)];
+            let at = x as usize + 1;
+
+            match dither {
+                Some(Dither::FloydSteinberg) => {
+                    for (channel, error) in color.iter_mut().zip(&errors[at]) {
+                        *channel = (*channel + error).clamp(0.0, 255.0);
+                    }
+                }
+                Some(Dither::Bayer) => {
+                    let threshold = BAYER[y as usize % 8][x as usize % 8];
+                    let offset = ((f32::from(threshold) + 0.5) / 64.0 - 0.5) * spread;
+
+                    for channel in color.iter_mut() {
+                        *channel += offset;
+                    }
+                }
+                None => {}
+            }
+
+            let closest = closest_color(palette, color);
+
+            if dither == Some(Dither::FloydSteinberg) {
+                for channel in 0..3 {
+                    let error = color[channel] - f32::from(closest[channel]);
+
+                    errors[at + 1][channel] += error * 7.0 / 16.0;
+                    next_errors[at - 1][channel] += error * 3.0 / 16.0;
+                    next_errors[at][channel] += error * 5.0 / 16.0;
+                    next_errors[at + 1][channel] += error / 16.0;
+                }
+            }
+
+            *pixel = P::from_rgba8(Rgba([closest[0], closest[1], closest[2], alpha]));
+        }
+
+        std::mem::swap(&mut errors, &mut next_errors);
+        next_errors.iter_mut().for_each(|error| *error = [0.0; 3]);
+    }
+
+    result
+}
+
+// The range of the Bayer offsets: the distance between the levels of a channel of a palette
+// with the given amount of evenly spaced colors, e.g. 255 for black and white.
+fn bayer_spread(colors: usize) -> f32 {
+    let levels = (colors as f32).cbrt().round().max(2.0);
+
+    255.0 / (levels - 1.0)
+}
+
+fn closest_color(palette: &[Rgb<u8>], color: [f32; 3]) -> Rgb<u8> {
+    let distance = |candidate: &Rgb<u8>| -> f32 {
+        candidate
+            .0
+            .iter()
+            .zip(&color)
+            .map(|(&c, v)| (f32::from(c) - v).powi(2))
+            .sum()
+    };
+
+    // The distances are sums of squares of finite values, so they can always be compared.
+    *palette
+        .iter()
+        .min_by(|a, b| {
+            distance(a)
+                .partial_cmp(&distance(b))
+                .unwrap_or(Ordering::Equal)
+        })
+        .expect("The palette should contain a color.")
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{GenericImageView, LumaA, RgbImage};
+
+    use super::*;
+
+    fn gray(value: u8) -> DynamicImage {
+        DynamicImage::ImageLuma8(GrayImage::from_pixel(16, 16, Luma([value])))
+    }
+
+    fn white_fraction(image: &GrayImage) -> f32 {
+        let white = image.pixels().filter(|pixel| pixel[0] == 255).count();
+
+        white as f32 / image.pixels().len() as f32
+    }
+
+    #[test]
+    fn map_without_dither_picks_closest_color() {
+        let buffer = RgbImage::from_fn(2, 1, |x, _| Rgb([200 * x as u8, 10, 10]));
+        let palette = [Rgb([0, 0, 0]), Rgb([255, 0, 0])];
+
+        let result = map_to_palette(&DynamicImage::ImageRgb8(buffer), &palette, None);
+
+        assert_eq!(Rgba([0, 0, 0, 255]), result.get_pixel(0, 0));
+        assert_eq!(Rgba([255, 0, 0, 255]), result.get_pixel(1, 0));
+    }
+
+    #[test]
+    fn map_keeps_alpha() {
+        let buffer = ImageBuffer::from_pixel(2, 2, LumaA([100, 30]));
+        let palette = [Rgb([0, 0, 0]), Rgb([255, 255, 255])];
+
+        for dither in [None, Some(Dither::FloydSteinberg), Some(Dither::Bayer)].iter() {
+            let result = map_buffer_to_palette(&buffer, &palette, *dither);
+
+            assert!(result.pixels().all(|pixel| pixel[1] == 30));
+        }
+    }
+
+    #[test]
+    fn uniform_palette_levels() {
+        let palette = uniform_palette(6);
+        let values: Vec<u8> = palette.iter().take(6).map(|color| color[2]).collect();
+
+        assert_eq!(216, palette.len());
+        assert_eq!(vec![0, 51, 102, 153, 204, 255], values);
+        assert_eq!(8, uniform_palette(2).len());
+    }
+
+    #[test]
+    fn threshold_level() {
+        let image = DynamicImage::ImageLuma8(GrayImage::from_fn(3, 1, |x, _| Luma([99 + x as u8])));
+
+        assert_eq!(vec![0, 255, 255], threshold(&image, 100).into_raw());
+        assert_eq!(vec![0, 0, 0], threshold(&image, 102).into_raw());
+    }
+
+    #[test]
+    fn dithered_black_and_white_keeps_average() {
+        for &value in [0u8, 64, 128, 191, 255].iter() {
+            for &dither in [Dither::FloydSteinberg, Dither::Bayer].iter() {
+                let result = black_and_white(&gray(value), dither);
+                let expected = f32::from(value) / 255.0;
+
+                assert!(result
+                    .pixels()
+                    .all(|pixel| pixel[0] == 0 || pixel[0] == 255));
+                assert!(
+                    (white_fraction(&result) - expected).abs() < 0.02,
+                    "{:?} of {}",
+                    dither,
+                    value
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn try_from_str() {
+        assert_eq!(
+            Dither::FloydSteinberg,
+            Dither::try_from_str("Floyd-Steinberg").unwrap()
+        );
+        assert_eq!(Dither::Bayer, Dither::try_from_str("bayer").unwrap());
+        assert!(Dither::try_from_str("atkinson").is_err());
+    }
+}
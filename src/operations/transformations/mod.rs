@@ -0,0 +1,74 @@
+// Pixel level algorithms which are not provided by the image crate itself.
+// The engine dispatches to these from `ImageEngine::process_operation`.
+use image::{Bgr, Bgra, Luma, LumaA, Pixel, Rgb, Rgba};
+
+pub mod dither;
+pub mod normalize;
+pub mod overlay;
+pub mod pad;
+pub mod quantize;
+pub mod resize;
+pub mod rotate;
+pub mod thumbnail;
+pub mod tone;
+
+// Applies `$action` to the image buffer held by each `DynamicImage` variant and wraps the
+// resulting buffer in the same variant again.
+macro_rules! dynamic_map {
+    ($dynimage:expr, ref $image:ident => $action:expr) => {
+        match $dynimage {
+            image::DynamicImage::ImageLuma8(ref $image) => image::DynamicImage::ImageLuma8($action),
+            image::DynamicImage::ImageLumaA8(ref $image) => {
+                image::DynamicImage::ImageLumaA8($action)
+            }
+            image::DynamicImage::ImageRgb8(ref $image) => image::DynamicImage::ImageRgb8($action),
+            image::DynamicImage::ImageRgba8(ref $image) => image::DynamicImage::ImageRgba8($action),
+            image::DynamicImage::ImageBgr8(ref $image) => image::DynamicImage::ImageBgr8($action),
+            image::DynamicImage::ImageBgra8(ref $image) => image::DynamicImage::ImageBgra8($action),
+        }
+    };
+}
+
+pub(crate) use dynamic_map;
+
+// Colors given to operations (for example a background fill) are always specified as RGBA.
+// This trait converts such a color to the pixel type of the image buffer the operation works on.
+pub(crate) trait FromRgba8 {
+    fn from_rgba8(color: Rgba<u8>) -> Self;
+}
+
+impl FromRgba8 for Luma<u8> {
+    fn from_rgba8(color: Rgba<u8>) -> Self {
+        color.to_luma()
+    }
+}
+
+impl FromRgba8 for LumaA<u8> {
+    fn from_rgba8(color: Rgba<u8>) -> Self {
+        color.to_luma_alpha()
+    }
+}
+
+impl FromRgba8 for Rgb<u8> {
+    fn from_rgba8(color: Rgba<u8>) -> Self {
+        color.to_rgb()
+    }
+}
+
+impl FromRgba8 for Rgba<u8> {
+    fn from_rgba8(color: Rgba<u8>) -> Self {
+        color
+    }
+}
+
+impl FromRgba8 for Bgr<u8> {
+    fn from_rgba8(color: Rgba<u8>) -> Self {
+        color.to_bgr()
+    }
+}
+
+impl FromRgba8 for Bgra<u8> {
+    fn from_rgba8(color: Rgba<u8>) -> Self {
+        color.to_bgra()
+    }
+}
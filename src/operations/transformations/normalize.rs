@@ -0,0 +1,256 @@
+// Tone adjustments derived from the histograms of an image, which normalize its exposure.
+//
+// Like the other tone adjustments, these map the color channels of each pixel, but not its
+// alpha channel, through a lookup table; here the table depends on the image itself.
+use image::{ColorType, DynamicImage, ImageBuffer, Pixel};
+
+use crate::analysis::{self, Histogram};
+use crate::operations::transformations::dynamic_map;
+
+// Stretches each color channel separately to the full range. `clip` is the percentage of the
+// pixels at each end of a channel which is clipped to black or white, so a few outliers don't
+// prevent the stretch. It should be at least 0 and smaller than 50.
+pub fn auto_levels(image: &DynamicImage, clip: f32) -> DynamicImage {
+    dynamic_map!(*image, ref buffer => auto_levels_buffer(buffer, clip))
+}
+
+// Equalizes the histogram of each color channel separately. This maximizes the contrast, but
+// shifts the hues when the channels have different distributions.
+pub fn equalize(image: &DynamicImage) -> DynamicImage {
+    dynamic_map!(*image, ref buffer => equalize_buffer(buffer))
+}
+
+// Equalizes the histogram of the luminance, and scales the color channels of each pixel with
+// the change of its luminance, which keeps its hue intact. Where the scaled color wouldn't fit
+// in the range of a channel, the pixel is brightened less instead.
+pub fn equalize_luminance(image: &DynamicImage) -> DynamicImage {
+    dynamic_map!(*image, ref buffer => equalize_luminance_buffer(buffer))
+}
+
+fn auto_levels_buffer<P>(buffer: &ImageBuffer<P, Vec<u8>>, clip: f32) -> ImageBuffer<P, Vec<u8>>
+where
+    P: Pixel<Subpixel = u8> + 'static,
+{
+    let fraction = f64::from(clip) / 100.0;
+    let tables: Vec<[u8; 256]> = color_histograms(buffer)
+        .iter()
+        .map(|histogram| match histogram.clipped_range(fraction) {
+            Some((low, high)) if low < high => stretch_table(low, high),
+            _ => identity_table(),
+        })
+        .collect();
+
+    map_channels(buffer, &tables)
+}
+
+fn equalize_buffer<P>(buffer: &ImageBuffer<P, Vec<u8>>) -> ImageBuffer<P, Vec<u8>>
+where
+    P: Pixel<Subpixel = u8> + 'static,
+{
+    let tables: Vec<[u8; 256]> = color_histograms(buffer)
+        .iter()
+        .map(equalization_table)
+        .collect();
+
+    map_channels(buffer, &tables)
+}
+
+fn equalize_luminance_buffer<P>(buffer: &ImageBuffer<P, Vec<u8>>) -> ImageBuffer<P, Vec<u8>>
+where
+    P: Pixel<Subpixel = u8> + 'static,
+{
+    let table = equalization_table(&analysis::buffer_luminance_histogram(buffer));
+    let channels = color_channel_count::<P>();
+    let mut result = buffer.clone();
+
+    for pixel in result.pixels_mut() {
+        let luminance = pixel.to_luma()[0];
+
+        // The darkest luminance always maps to 0, so black stays black.
+        if luminance == 0 {
+            continue;
+        }
+
+        let brightest = pixel.channels()[..channels]
+            .iter()
+            .copied()
+            .max()
+            .unwrap_or(0);
+        let scale = (f32::from(table[usize::from(luminance)]) / f32::from(luminance))
+            .min(255.0 / f32::from(brightest));
+
+        pixel.apply_without_alpha(|value| (f32::from(value) * scale).round().min(255.0) as u8);
+    }
+
+    result
+}
+
+// Histograms of the channels of the buffer which aren't the alpha channel.
+fn color_histograms<P>(buffer: &ImageBuffer<P, Vec<u8>>) -> Vec<Histogram>
+where
+    P: Pixel<Subpixel = u8> + 'static,
+{
+    let mut histograms = analysis::channel_histograms(buffer);
+    histograms.truncate(color_channel_count::<P>());
+
+    histograms
+}
+
+fn color_channel_count<P: Pixel>() -> usize {
+    let channels = usize::from(P::CHANNEL_COUNT);
+
+    match P::COLOR_TYPE {
+        ColorType::GrayA(_) | ColorType::RGBA(_) | ColorType::BGRA(_) => channels - 1,
+        _ => channels,
+    }
+}
+
+// Maps each color channel through the table at its index.
+fn map_channels<P>(
+    buffer: &ImageBuffer<P, Vec<u8>>,
+    tables: &[[u8; 256]],
+) -> ImageBuffer<P, Vec<u8>>
+where
+    P: Pixel<Subpixel = u8> + 'static,
+{
+    let mut result = buffer.clone();
+
+    for pixel in result.pixels_mut() {
+        for (value, table) in pixel.channels_mut().iter_mut().zip(tables) {
+            *value = table[usize::from(*value)];
+        }
+    }
+
+    result
+}
+
+fn identity_table() -> [u8; 256] {
+    let mut table = [0; 256];
+
+    for (value, entry) in table.iter_mut().enumerate() {
+        *entry = value as u8;
+    }
+
+    table
+}
+
+// Maps `low` and below to 0 and `high` and above to 255, and spreads the values in between.
+fn stretch_table(low: u8, high: u8) -> [u8; 256] {
+    let mut table = [0; 256];
+    let range = f32::from(high - low);
+
+    for (value, entry) in table.iter_mut().enumerate() {
+        let stretched = (value as f32 - f32::from(low)) / range * 255.0;
+        *entry = stretched.round().clamp(0.0, 255.0) as u8;
+    }
+
+    table
+}
+
+// Maps each value to its rank in the cumulative histogram, such that the values present in the
+// histogram are spread evenly over the full range. The lowest value present maps to 0.
+fn equalization_table(histogram: &Histogram) -> [u8; 256] {
+    let cumulative = histogram.cumulative();
+    let total = cumulative[255];
+    let lowest = histogram
+        .min()
+        .map_or(0, |value| cumulative[usize::from(value)]);
+
+    if total == lowest {
+        return identity_table();
+    }
+
+    let mut table = [0; 256];
+
+    for (value, entry) in table.iter_mut().enumerate() {
+        let rank = cumulative[value].saturating_sub(lowest) as f64 / (total - lowest) as f64;
+        *entry = (rank * 255.0).round() as u8;
+    }
+
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{GenericImageView, GrayImage, Luma, LumaA, Rgb, RgbImage};
+
+    use super::*;
+
+    #[test]
+    fn auto_levels_stretches_each_channel() {
+        let buffer = RgbImage::from_fn(101, 1, |x, _| {
+            let x = x as u8;
+            Rgb([50 + x, 100 + x / 2, 7])
+        });
+
+        let result = auto_levels_buffer(&buffer, 0.0);
+
+        assert_eq!(Rgb([0, 0, 7]), *result.get_pixel(0, 0));
+        assert_eq!(Rgb([255, 255, 7]), *result.get_pixel(100, 0));
+        assert_eq!(Rgb([128, 128, 7]), *result.get_pixel(50, 0));
+    }
+
+    #[test]
+    fn auto_levels_clips_outliers() {
+        let mut buffer = GrayImage::from_fn(100, 1, |x, _| Luma([100 + x as u8 / 2]));
+        buffer.put_pixel(0, 0, Luma([0]));
+        buffer.put_pixel(99, 0, Luma([255]));
+
+        let unclipped = auto_levels_buffer(&buffer, 0.0);
+        let clipped = auto_levels_buffer(&buffer, 2.0);
+
+        assert_eq!(buffer.into_raw(), unclipped.into_raw());
+        assert_eq!(Luma([0]), *clipped.get_pixel(1, 0));
+        assert_eq!(Luma([255]), *clipped.get_pixel(98, 0));
+    }
+
+    #[test]
+    fn equalize_spreads_values() {
+        let buffer = GrayImage::from_fn(4, 1, |x, _| Luma([100 + x as u8]));
+
+        let result = equalize_buffer(&buffer);
+
+        assert_eq!(vec![0, 85, 170, 255], result.into_raw());
+    }
+
+    #[test]
+    fn equalize_keeps_alpha_and_flat_images() {
+        let buffer = ImageBuffer::from_fn(2, 1, |x, _| LumaA([10 + x as u8, 50]));
+        let flat = GrayImage::from_pixel(2, 2, Luma([42]));
+
+        assert_eq!(vec![0, 50, 255, 50], equalize_buffer(&buffer).into_raw());
+        assert_eq!(flat.to_vec(), equalize_buffer(&flat).into_raw());
+    }
+
+    #[test]
+    fn equalize_luminance_keeps_hue() {
+        let buffer = RgbImage::from_fn(3, 1, |x, _| match x {
+            0 => Rgb([0, 0, 0]),
+            1 => Rgb([40, 20, 10]),
+            _ => Rgb([60, 30, 15]),
+        });
+
+        let result = DynamicImage::ImageRgb8(equalize_luminance_buffer(&buffer));
+
+        assert_eq!(image::Rgba([0, 0, 0, 255]), result.get_pixel(0, 0));
+
+        for x in 1..3 {
+            let image::Rgba([r, g, b, _]) = result.get_pixel(x, 0);
+            assert!((i32::from(r) - 2 * i32::from(g)).abs() <= 2, "{} {}", r, g);
+            assert!((i32::from(g) - 2 * i32::from(b)).abs() <= 2, "{} {}", g, b);
+        }
+
+        // The brightest pixel would be scaled beyond the range, so only its red channel is full.
+        assert_eq!(image::Rgba([255, 128, 64, 255]), result.get_pixel(2, 0));
+    }
+
+    #[test]
+    fn equalize_luminance_of_gray_image_equals_equalize() {
+        let buffer = GrayImage::from_fn(16, 16, |x, y| Luma([(x * y) as u8]));
+
+        assert_eq!(
+            equalize_buffer(&buffer).into_raw(),
+            equalize_luminance_buffer(&buffer).into_raw()
+        );
+    }
+}
@@ -0,0 +1,173 @@
+use image::{DynamicImage, GenericImageView, ImageBuffer, Pixel, Rgba, RgbaImage};
+
+use crate::operations::placement::Placement;
+use crate::operations::transformations::{dynamic_map, FromRgba8};
+
+// Draws `top` on top of `image`, at the given placement.
+//
+// The pixels of `top` are blended with the pixels below them using the alpha channel of `top`,
+// multiplied by `opacity` (between 0 and 1). Parts of `top` which fall outside of `image` are
+// ignored. The color type of `image` is preserved.
+pub fn overlay(
+    image: &DynamicImage,
+    top: &DynamicImage,
+    placement: Placement,
+    opacity: f32,
+) -> DynamicImage {
+    let top = top.to_rgba();
+    let (x, y) = placement.offset(image.dimensions(), top.dimensions());
+
+    dynamic_map!(*image, ref buffer => overlay_buffer(buffer, &top, (x, y), opacity))
+}
+
+fn overlay_buffer<P>(
+    image: &ImageBuffer<P, Vec<u8>>,
+    top: &RgbaImage,
+    offset: (i64, i64),
+    opacity: f32,
+) -> ImageBuffer<P, Vec<u8>>
+where
+    P: Pixel<Subpixel = u8> + FromRgba8 + 'static,
+{
+    let mut result = image.clone();
+    let (width, height) = image.dimensions();
+
+    for (tx, ty, pixel) in top.enumerate_pixels() {
+        let x = offset.0 + i64::from(tx);
+        let y = offset.1 + i64::from(ty);
+
+        if x < 0 || y < 0 || x >= i64::from(width) || y >= i64::from(height) {
+            continue;
+        }
+
+        let alpha = f32::from(pixel[3]) / 255.0 * opacity;
+
+        // Fully transparent pixels leave the pixel below untouched, which avoids a lossy round
+        // trip through RGBA for color types without an alpha channel.
+        if alpha <= 0.0 {
+            continue;
+        }
+
+        let below = result.get_pixel_mut(x as u32, y as u32);
+        *below = P::from_rgba8(blend(below.to_rgba(), *pixel, alpha));
+    }
+
+    result
+}
+
+// Source-over compositing of `top` with effective alpha `alpha` onto `below`.
+fn blend(below: Rgba<u8>, top: Rgba<u8>, alpha: f32) -> Rgba<u8> {
+    let below_alpha = f32::from(below[3]) / 255.0;
+    let out_alpha = alpha + below_alpha * (1.0 - alpha);
+
+    if out_alpha <= 0.0 {
+        return Rgba([0, 0, 0, 0]);
+    }
+
+    let channel = |i: usize| {
+        let value = (f32::from(top[i]) * alpha + f32::from(below[i]) * below_alpha * (1.0 - alpha))
+            / out_alpha;
+
+        value.round().clamp(0.0, 255.0) as u8
+    };
+
+    Rgba([
+        channel(0),
+        channel(1),
+        channel(2),
+        (out_alpha * 255.0).round().min(255.0) as u8,
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{Luma, Rgb};
+
+    use crate::operations::placement::Anchor;
+
+    use super::*;
+
+    fn setup_top(width: u32, height: u32, color: Rgba<u8>) -> DynamicImage {
+        DynamicImage::ImageRgba8(ImageBuffer::from_pixel(width, height, color))
+    }
+
+    #[test]
+    fn overlay_opaque_at_anchor() {
+        let base = DynamicImage::ImageRgb8(ImageBuffer::from_pixel(4, 4, Rgb([0, 0, 255])));
+        let top = setup_top(2, 2, Rgba([255, 0, 0, 255]));
+
+        let result = overlay(&base, &top, Placement::Anchor(Anchor::BottomRight), 1.0);
+
+        assert_eq!(base.color(), result.color());
+        assert_eq!(Rgba([0, 0, 255, 255]), result.get_pixel(1, 1));
+        assert_eq!(Rgba([255, 0, 0, 255]), result.get_pixel(2, 2));
+        assert_eq!(Rgba([255, 0, 0, 255]), result.get_pixel(3, 3));
+    }
+
+    #[test]
+    fn overlay_with_opacity_blends() {
+        let base = DynamicImage::ImageRgb8(ImageBuffer::from_pixel(2, 2, Rgb([0, 0, 0])));
+        let top = setup_top(2, 2, Rgba([200, 100, 0, 255]));
+
+        let result = overlay(&base, &top, Placement::Offset(0, 0), 0.5);
+
+        assert_eq!(Rgba([100, 50, 0, 255]), result.get_pixel(0, 0));
+    }
+
+    #[test]
+    fn overlay_uses_alpha_channel_of_top() {
+        let base = DynamicImage::ImageRgb8(ImageBuffer::from_pixel(2, 2, Rgb([0, 0, 0])));
+        let top = setup_top(2, 2, Rgba([200, 200, 200, 0]));
+
+        let result = overlay(&base, &top, Placement::Offset(0, 0), 1.0);
+
+        assert_eq!(base.raw_pixels(), result.raw_pixels());
+    }
+
+    #[test]
+    fn overlay_onto_transparent_base() {
+        let base = DynamicImage::ImageRgba8(ImageBuffer::from_pixel(1, 1, Rgba([0, 0, 0, 0])));
+        let top = setup_top(1, 1, Rgba([200, 100, 50, 255]));
+
+        let result = overlay(&base, &top, Placement::Offset(0, 0), 0.5);
+
+        // The color of a fully transparent pixel doesn't contribute.
+        assert_eq!(Rgba([200, 100, 50, 128]), result.get_pixel(0, 0));
+    }
+
+    #[test]
+    fn overlay_partially_outside() {
+        let base = DynamicImage::ImageLuma8(ImageBuffer::from_pixel(3, 3, Luma([0])));
+        let top = setup_top(2, 2, Rgba([255, 255, 255, 255]));
+
+        let result = overlay(&base, &top, Placement::Offset(-1, 2), 1.0);
+
+        assert_eq!(base.color(), result.color());
+        assert_eq!(Rgba([255, 255, 255, 255]), result.get_pixel(0, 2));
+        assert_eq!(Rgba([0, 0, 0, 255]), result.get_pixel(1, 2));
+        assert_eq!(Rgba([0, 0, 0, 255]), result.get_pixel(0, 1));
+    }
+
+    #[test]
+    fn overlay_all_color_types() {
+        let rgba = setup_top(3, 3, Rgba([10, 20, 30, 255]));
+        let top = setup_top(1, 1, Rgba([255, 255, 255, 255]));
+
+        let images = [
+            DynamicImage::ImageLuma8(rgba.to_luma()),
+            DynamicImage::ImageLumaA8(rgba.to_luma_alpha()),
+            DynamicImage::ImageRgb8(rgba.to_rgb()),
+            rgba.clone(),
+            DynamicImage::ImageBgr8(rgba.to_bgr()),
+            DynamicImage::ImageBgra8(rgba.to_bgra()),
+        ];
+
+        for image in images.iter() {
+            let result = overlay(image, &top, Placement::Anchor(Anchor::Center), 1.0);
+
+            assert_eq!(image.color(), result.color());
+            assert_eq!(Rgba([255, 255, 255, 255]), result.get_pixel(1, 1));
+            assert_eq!(image.get_pixel(0, 0), result.get_pixel(0, 0));
+        }
+    }
+}
@@ -0,0 +1,95 @@
+use image::{DynamicImage, GenericImage, ImageBuffer, Pixel, Rgba};
+
+use crate::operations::placement::Anchor;
+use crate::operations::transformations::{dynamic_map, FromRgba8};
+
+// Places `image` on a canvas with the given dimensions, with its top left corner at `offset`.
+// The rest of the canvas is filled with `fill`. The canvas has to be large enough to contain the
+// image at `offset`; see `margins_dimensions` and `anchor_offset`.
+pub fn pad(
+    image: &DynamicImage,
+    canvas: (u32, u32),
+    offset: (u32, u32),
+    fill: Rgba<u8>,
+) -> DynamicImage {
+    dynamic_map!(*image, ref buffer => pad_buffer(
+        buffer,
+        canvas,
+        offset,
+        FromRgba8::from_rgba8(fill),
+    ))
+}
+
+fn pad_buffer<P>(
+    image: &ImageBuffer<P, Vec<u8>>,
+    (width, height): (u32, u32),
+    (x, y): (u32, u32),
+    fill: P,
+) -> ImageBuffer<P, Vec<u8>>
+where
+    P: Pixel<Subpixel = u8> + 'static,
+{
+    let mut canvas = ImageBuffer::from_pixel(width, height, fill);
+    let fits = canvas.copy_from(image, x, y);
+
+    debug_assert!(fits, "The canvas should contain the image.");
+
+    canvas
+}
+
+// Dimensions of an image with the given dimensions after adding margins (top, right, bottom,
+// left) around it, or None if they wouldn't fit in an `u32`.
+pub(crate) fn margins_dimensions(
+    (width, height): (u32, u32),
+    (top, right, bottom, left): (u32, u32, u32, u32),
+) -> Option<(u32, u32)> {
+    let width = width.checked_add(left)?.checked_add(right)?;
+    let height = height.checked_add(top)?.checked_add(bottom)?;
+
+    Some((width, height))
+}
+
+// Offset of an image with the given dimensions, anchored within a canvas which contains it.
+pub(crate) fn anchor_offset(anchor: Anchor, canvas: (u32, u32), image: (u32, u32)) -> (u32, u32) {
+    let (x, y) = anchor.offset(canvas, image);
+
+    (x.max(0) as u32, y.max(0) as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{GenericImageView, GrayImage, Luma};
+
+    use super::*;
+
+    #[test]
+    fn pad_fills_around_image() {
+        let image = DynamicImage::ImageLuma8(GrayImage::from_pixel(2, 1, Luma([255])));
+        let (width, height) = (4, 3);
+        let (x, y) = anchor_offset(Anchor::BottomRight, (width, height), (2, 1));
+
+        let padded = pad(&image, (width, height), (x, y), Rgba([0, 0, 0, 255]));
+
+        assert_eq!((4, 3), padded.dimensions());
+        assert_eq!((2, 2), (x, y));
+        assert_eq!(Rgba([255, 255, 255, 255]), padded.get_pixel(2, 2));
+        assert_eq!(Rgba([255, 255, 255, 255]), padded.get_pixel(3, 2));
+        assert_eq!(Rgba([0, 0, 0, 255]), padded.get_pixel(1, 2));
+        assert_eq!(Rgba([0, 0, 0, 255]), padded.get_pixel(3, 1));
+    }
+
+    #[test]
+    fn pad_preserves_color_type() {
+        let image = DynamicImage::new_rgb8(3, 3);
+        let padded = pad(&image, (5, 5), (1, 1), Rgba([255, 0, 0, 0]));
+
+        assert_eq!(image.color(), padded.color());
+        assert_eq!(Rgba([255, 0, 0, 255]), padded.get_pixel(0, 0));
+    }
+
+    #[test]
+    fn margins() {
+        assert_eq!(Some((13, 24)), margins_dimensions((10, 20), (1, 2, 3, 1)));
+        assert_eq!(None, margins_dimensions((10, 20), (0, u32::MAX, 0, 0)));
+    }
+}
@@ -0,0 +1,356 @@
+// Color quantization: reduces the colors of an image to a palette of at most a given amount of
+// colors, e.g. for formats such as GIF which only support a limited amount of colors.
+use std::collections::BTreeMap;
+
+use image::{DynamicImage, Rgb};
+
+use crate::error::Error;
+use crate::operations::transformations::dither::{self, Dither};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+pub enum QuantizeMethod {
+    // Repeatedly splits the group of colors with the widest range of a channel at its median.
+    MedianCut,
+    // Refines the palette of median cut by k-means clustering; slower, but the palette colors
+    // lie closer to the colors of the image.
+    KMeans,
+}
+
+impl QuantizeMethod {
+    pub fn try_from_str(val: &str) -> Result<QuantizeMethod, Error> {
+        match val.to_lowercase().as_str() {
+            "median-cut" => Ok(QuantizeMethod::MedianCut),
+            "k-means" => Ok(QuantizeMethod::KMeans),
+            fail => Err(Error::UnknownQuantizeMethod(fail.to_string())),
+        }
+    }
+}
+
+const K_MEANS_ITERATIONS: usize = 16;
+// Each iteration of k-means compares every color to every palette color, so the colors of
+// e.g. photos are merged into at most this many colors first.
+const K_MEANS_MAX_COLORS: usize = 4096;
+
+// Maps the image to a palette of at most `colors` colors, chosen with `method`. The alpha
+// channel, if any, is left as it is.
+pub fn quantize(
+    image: &DynamicImage,
+    colors: usize,
+    method: QuantizeMethod,
+    dither: Option<Dither>,
+) -> DynamicImage {
+    let palette = palette(image, colors, method);
+
+    dither::map_to_palette(image, &palette, dither)
+}
+
+// A palette of at most `colors` colors which represents the colors of the image. The palette
+// has fewer colors if the image does, and is empty if the image has no pixels.
+pub fn palette(image: &DynamicImage, colors: usize, method: QuantizeMethod) -> Vec<Rgb<u8>> {
+    let mut counts = BTreeMap::new();
+
+    for pixel in image.to_rgb().pixels() {
+        *counts.entry(pixel.0).or_insert(0u64) += 1;
+    }
+
+    let histogram: Vec<([u8; 3], u64)> = counts.into_iter().collect();
+
+    if histogram.is_empty() || colors == 0 {
+        return Vec::new();
+    }
+
+    let palette = median_cut(&histogram, colors);
+
+    match method {
+        QuantizeMethod::MedianCut => palette,
+        QuantizeMethod::KMeans => k_means(&coarse_histogram(histogram), palette),
+    }
+}
+
+fn median_cut(histogram: &[([u8; 3], u64)], colors: usize) -> Vec<Rgb<u8>> {
+    let mut boxes = vec![histogram.to_vec()];
+
+    while boxes.len() < colors {
+        let widest = boxes
+            .iter()
+            .enumerate()
+            .filter(|(_, colors)| colors.len() > 1)
+            .map(|(index, colors)| (index, widest_channel(colors)))
+            .max_by_key(|&(_, (_, range))| range);
+
+        let (index, channel) = match widest {
+            Some((index, (channel, _))) => (index, channel),
+            None => break,
+        };
+
+        let mut lower = boxes.swap_remove(index);
+        lower.sort_by_key(|(color, _)| color[channel]);
+
+        // The weighted median, but with at least one color in each half.
+        let total: u64 = lower.iter().map(|(_, count)| count).sum();
+        let mut seen = 0;
+        let median = lower
+            .iter()
+            .position(|(_, count)| {
+                seen += count;
+                seen * 2 >= total
+            })
+            .unwrap_or(0);
+
+        let upper = lower.split_off((median + 1).min(lower.len() - 1));
+
+        boxes.push(lower);
+        boxes.push(upper);
+    }
+
+    boxes.iter().map(|colors| mean_color(colors)).collect()
+}
+
+// The channel with the widest range of values, and that range.
+fn widest_channel(colors: &[([u8; 3], u64)]) -> (usize, u8) {
+    (0..3)
+        .map(|channel| {
+            let values = colors.iter().map(|(color, _)| color[channel]);
+            let min = values.clone().min().unwrap_or(0);
+            let max = values.max().unwrap_or(0);
+
+            (channel, max - min)
+        })
+        .max_by_key(|&(_, range)| range)
+        .unwrap_or((0, 0))
+}
+
+fn mean_color(colors: &[([u8; 3], u64)]) -> Rgb<u8> {
+    let mut sums = [0.0f64; 3];
+    let mut total = 0.0;
+
+    for (color, count) in colors {
+        for (sum, &value) in sums.iter_mut().zip(color) {
+            *sum += f64::from(value) * *count as f64;
+        }
+
+        total += *count as f64;
+    }
+
+    Rgb([
+        (sums[0] / total).round() as u8,
+        (sums[1] / total).round() as u8,
+        (sums[2] / total).round() as u8,
+    ])
+}
+
+// Merges colors which only differ in their least significant bits, into the weighted mean of
+// their colors, until at most `K_MEANS_MAX_COLORS` colors remain.
+fn coarse_histogram(histogram: Vec<([u8; 3], u64)>) -> Vec<([u8; 3], u64)> {
+    let mut shift = 0;
+    let mut coarse = histogram;
+
+    while coarse.len() > K_MEANS_MAX_COLORS && shift < 8 {
+        shift += 1;
+
+        let mut buckets: BTreeMap<[u8; 3], Vec<([u8; 3], u64)>> = BTreeMap::new();
+
+        for (color, count) in coarse {
+            let bucket = [color[0] >> shift, color[1] >> shift, color[2] >> shift];
+            buckets.entry(bucket).or_default().push((color, count));
+        }
+
+        coarse = buckets
+            .values()
+            .map(|colors| {
+                let count = colors.iter().map(|(_, count)| count).sum();
+                (mean_color(colors).0, count)
+            })
+            .collect();
+    }
+
+    coarse
+}
+
+// Lloyd's algorithm, starting from `palette`. A palette color without any closest colors is
+// left as it is.
+fn k_means(histogram: &[([u8; 3], u64)], mut palette: Vec<Rgb<u8>>) -> Vec<Rgb<u8>> {
+    for _ in 0..K_MEANS_ITERATIONS {
+        let mut clusters = vec![Vec::new(); palette.len()];
+
+        for &(color, count) in histogram {
+            clusters[closest_index(&palette, color)].push((color, count));
+        }
+
+        let next: Vec<Rgb<u8>> = clusters
+            .iter()
+            .zip(&palette)
+            .map(|(cluster, &color)| {
+                if cluster.is_empty() {
+                    color
+                } else {
+                    mean_color(cluster)
+                }
+            })
+            .collect();
+
+        if next == palette {
+            break;
+        }
+
+        palette = next;
+    }
+
+    palette
+}
+
+fn closest_index(palette: &[Rgb<u8>], color: [u8; 3]) -> usize {
+    let distance = |candidate: &Rgb<u8>| -> u32 {
+        candidate
+            .0
+            .iter()
+            .zip(&color)
+            .map(|(&a, &b)| (i32::from(a) - i32::from(b)).pow(2) as u32)
+            .sum()
+    };
+
+    (0..palette.len())
+        .min_by_key(|&index| distance(&palette[index]))
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use image::{GenericImageView, RgbImage};
+
+    use super::*;
+
+    fn setup_image() -> DynamicImage {
+        image::open("resources/botanical.jpg").expect("Unable to open the test image.")
+    }
+
+    fn distinct_colors(image: &DynamicImage) -> usize {
+        let colors: HashSet<_> = image.to_rgb().pixels().map(|pixel| pixel.0).collect();
+
+        colors.len()
+    }
+
+    // Mean squared difference between the channels of both images.
+    fn error(a: &DynamicImage, b: &DynamicImage) -> f64 {
+        let (a, b) = (a.to_rgb().into_raw(), b.to_rgb().into_raw());
+        let sum: f64 = a
+            .iter()
+            .zip(&b)
+            .map(|(&x, &y)| (f64::from(x) - f64::from(y)).powi(2))
+            .sum();
+
+        sum / a.len() as f64
+    }
+
+    #[test]
+    fn quantize_reduces_colors() {
+        let image = setup_image().thumbnail(64, 64);
+
+        for &method in [QuantizeMethod::MedianCut, QuantizeMethod::KMeans].iter() {
+            let result = quantize(&image, 16, method, None);
+
+            assert_eq!(image.dimensions(), result.dimensions());
+            assert_eq!(image.color(), result.color());
+            assert!(distinct_colors(&result) <= 16);
+            assert_eq!(16, palette(&image, 16, method).len());
+        }
+    }
+
+    #[test]
+    fn k_means_improves_median_cut() {
+        let image = setup_image().thumbnail(64, 64);
+
+        let median_cut = quantize(&image, 8, QuantizeMethod::MedianCut, None);
+        let k_means = quantize(&image, 8, QuantizeMethod::KMeans, None);
+
+        assert!(error(&image, &k_means) <= error(&image, &median_cut));
+    }
+
+    #[test]
+    fn k_means_merges_many_colors() {
+        let buffer = RgbImage::from_fn(256, 256, |x, y| Rgb([x as u8, y as u8, (x ^ y) as u8]));
+        let mut histogram: Vec<_> = buffer.pixels().map(|pixel| (pixel.0, 1)).collect();
+        histogram.sort_unstable();
+
+        let coarse = coarse_histogram(histogram);
+
+        assert!(coarse.len() <= K_MEANS_MAX_COLORS);
+        assert_eq!(
+            256 * 256,
+            coarse.iter().map(|(_, count)| count).sum::<u64>()
+        );
+
+        let image = DynamicImage::ImageRgb8(buffer);
+        assert_eq!(8, palette(&image, 8, QuantizeMethod::KMeans).len());
+    }
+
+    #[test]
+    fn palette_of_few_colors() {
+        let buffer = RgbImage::from_fn(4, 4, |x, _| match x {
+            0 => Rgb([255, 0, 0]),
+            1 => Rgb([0, 255, 0]),
+            _ => Rgb([0, 0, 255]),
+        });
+        let image = DynamicImage::ImageRgb8(buffer);
+
+        let mut palette = palette(&image, 16, QuantizeMethod::MedianCut);
+        palette.sort_by_key(|color| color.0);
+
+        assert_eq!(
+            vec![Rgb([0, 0, 255]), Rgb([0, 255, 0]), Rgb([255, 0, 0])],
+            palette
+        );
+        assert_eq!(
+            image.raw_pixels(),
+            quantize(&image, 16, QuantizeMethod::KMeans, None).raw_pixels()
+        );
+    }
+
+    #[test]
+    fn median_cut_splits_at_weighted_median() {
+        let histogram = [([0, 0, 0], 1), ([10, 0, 0], 1), ([200, 0, 0], 2)];
+
+        let palette = median_cut(&histogram, 2);
+
+        assert!(palette.contains(&Rgb([5, 0, 0])));
+        assert!(palette.contains(&Rgb([200, 0, 0])));
+    }
+
+    #[test]
+    fn dithered_quantize_keeps_palette() {
+        let image = setup_image().thumbnail(32, 32);
+        let palette: HashSet<_> = palette(&image, 4, QuantizeMethod::MedianCut)
+            .iter()
+            .map(|color| color.0)
+            .collect();
+
+        for &dither in [Dither::FloydSteinberg, Dither::Bayer].iter() {
+            let result = quantize(&image, 4, QuantizeMethod::MedianCut, Some(dither));
+
+            assert!(result
+                .to_rgb()
+                .pixels()
+                .all(|pixel| palette.contains(&pixel.0)));
+        }
+    }
+
+    #[test]
+    fn try_from_str() {
+        assert_eq!(
+            QuantizeMethod::KMeans,
+            QuantizeMethod::try_from_str("k-means").unwrap()
+        );
+        assert_eq!(
+            QuantizeMethod::MedianCut,
+            QuantizeMethod::try_from_str("Median-Cut").unwrap()
+        );
+        assert!(QuantizeMethod::try_from_str("octree").is_err());
+    }
+}
@@ -0,0 +1,282 @@
+use image::{DynamicImage, FilterType, GenericImageView};
+
+// How the dimensions of a resized image are determined.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ResizeMode {
+    // Exactly the given width and height; the aspect ratio isn't preserved.
+    Exact(u32, u32),
+    // The largest dimensions which fit within the given width and height.
+    Fit(u32, u32),
+    // The smallest dimensions which cover the given width and height; the image is then cropped
+    // around its center to the given width and height.
+    Fill(u32, u32),
+    // A percentage of the width and height.
+    Percentage(f32),
+    // The given width; the height follows from the aspect ratio.
+    Width(u32),
+    // The given height; the width follows from the aspect ratio.
+    Height(u32),
+}
+
+// Resizes `image` as specified by `mode`. With `shrink_only`, an image which would become wider
+// or taller is left as it is.
+pub fn resize(
+    image: &DynamicImage,
+    mode: ResizeMode,
+    filter: FilterType,
+    shrink_only: bool,
+) -> DynamicImage {
+    let dimensions = image.dimensions();
+    let (width, height) = scaled_dimensions(dimensions, mode);
+
+    if shrink_only && enlarges(dimensions, (width, height)) {
+        return image.clone();
+    }
+
+    match mode {
+        ResizeMode::Fill(nwidth, nheight) => image.resize_to_fill(nwidth, nheight, filter),
+        _ => image.resize_exact(width, height, filter),
+    }
+}
+
+// Dimensions of the image produced by `resize`.
+pub(crate) fn output_dimensions(
+    dimensions: (u32, u32),
+    mode: ResizeMode,
+    shrink_only: bool,
+) -> (u32, u32) {
+    let scaled = scaled_dimensions(dimensions, mode);
+
+    if shrink_only && enlarges(dimensions, scaled) {
+        return dimensions;
+    }
+
+    match mode {
+        ResizeMode::Fill(nwidth, nheight) => (nwidth, nheight),
+        _ => scaled,
+    }
+}
+
+fn enlarges((width, height): (u32, u32), (nwidth, nheight): (u32, u32)) -> bool {
+    nwidth > width || nheight > height
+}
+
+// Dimensions the image is scaled to; for `Fill` prior to cropping.
+fn scaled_dimensions((width, height): (u32, u32), mode: ResizeMode) -> (u32, u32) {
+    match mode {
+        ResizeMode::Exact(nwidth, nheight) => (nwidth, nheight),
+        ResizeMode::Fit(nwidth, nheight) => {
+            aspect_dimensions((width, height), (nwidth, nheight), false)
+        }
+        ResizeMode::Fill(nwidth, nheight) => {
+            aspect_dimensions((width, height), (nwidth, nheight), true)
+        }
+        ResizeMode::Percentage(percentage) => {
+            let scale = f64::from(percentage) / 100.0;
+            (scale_side(width, scale), scale_side(height, scale))
+        }
+        ResizeMode::Width(nwidth) if width > 0 => (
+            nwidth,
+            scale_side(height, f64::from(nwidth) / f64::from(width)),
+        ),
+        ResizeMode::Height(nheight) if height > 0 => (
+            scale_side(width, f64::from(nheight) / f64::from(height)),
+            nheight,
+        ),
+        ResizeMode::Width(nwidth) => (nwidth, height),
+        ResizeMode::Height(nheight) => (width, nheight),
+    }
+}
+
+// Scales a side, rounded to the nearest pixel; a side never becomes smaller than a pixel.
+fn scale_side(side: u32, scale: f64) -> u32 {
+    (f64::from(side) * scale)
+        .round()
+        .clamp(1.0, f64::from(u32::MAX)) as u32
+}
+
+// The dimensions which preserve the aspect ratio of `dimensions` and either fit within `bounds`,
+// or, if `fill` is set, cover `bounds`; as computed by `DynamicImage::resize` and
+// `DynamicImage::resize_to_fill`, except that a side never becomes smaller than a pixel.
+fn aspect_dimensions(
+    (width, height): (u32, u32),
+    (nwidth, nheight): (u32, u32),
+    fill: bool,
+) -> (u32, u32) {
+    if width == 0 || height == 0 {
+        return (0, 0);
+    }
+
+    let ratio = u64::from(width) * u64::from(nheight);
+    let nratio = u64::from(nwidth) * u64::from(height);
+    let max = u64::from(u32::MAX);
+
+    let use_width = if fill {
+        nratio > ratio
+    } else {
+        nratio <= ratio
+    };
+
+    if use_width {
+        let intermediate = u64::from(height) * u64::from(nwidth) / u64::from(width);
+
+        if intermediate <= max {
+            (nwidth.max(1), (intermediate as u32).max(1))
+        } else {
+            (
+                ((u64::from(nwidth) * max / intermediate) as u32).max(1),
+                u32::MAX,
+            )
+        }
+    } else {
+        let intermediate = u64::from(width) * u64::from(nheight) / u64::from(height);
+
+        if intermediate <= max {
+            ((intermediate as u32).max(1), nheight.max(1))
+        } else {
+            (
+                u32::MAX,
+                ((u64::from(nheight) * max / intermediate) as u32).max(1),
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MODES: [ResizeMode; 10] = [
+        ResizeMode::Exact(10, 30),
+        ResizeMode::Fit(100, 100),
+        ResizeMode::Fit(1000, 10),
+        ResizeMode::Fill(100, 100),
+        ResizeMode::Fill(10, 40),
+        ResizeMode::Percentage(50.0),
+        ResizeMode::Percentage(150.0),
+        ResizeMode::Width(100),
+        ResizeMode::Height(1000),
+        ResizeMode::Height(1),
+    ];
+
+    #[test]
+    fn output_dimensions_match_resize() {
+        let image = DynamicImage::new_rgb8(217, 447);
+
+        for mode in MODES.iter() {
+            for shrink_only in [false, true].iter() {
+                let resized = resize(&image, *mode, FilterType::Nearest, *shrink_only);
+
+                assert_eq!(
+                    resized.dimensions(),
+                    output_dimensions((217, 447), *mode, *shrink_only),
+                    "{:?}",
+                    mode
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn fit_matches_image_resize() {
+        let image = DynamicImage::new_rgb8(217, 447);
+
+        for bounds in &[(100, 100), (1000, 10), (3, 3000), (217, 447), (20, 20)] {
+            let resized = image.resize(bounds.0, bounds.1, FilterType::Nearest);
+
+            assert_eq!(
+                resized.dimensions(),
+                output_dimensions((217, 447), ResizeMode::Fit(bounds.0, bounds.1), false)
+            );
+        }
+    }
+
+    #[test]
+    fn aspect_aware_dimensions() {
+        let dimensions = (200, 100);
+
+        assert_eq!(
+            (100, 50),
+            scaled_dimensions(dimensions, ResizeMode::Fit(100, 100))
+        );
+        assert_eq!(
+            (200, 100),
+            scaled_dimensions(dimensions, ResizeMode::Fill(100, 100))
+        );
+        assert_eq!(
+            (100, 100),
+            output_dimensions(dimensions, ResizeMode::Fill(100, 100), false)
+        );
+        assert_eq!(
+            (50, 25),
+            scaled_dimensions(dimensions, ResizeMode::Percentage(25.0))
+        );
+        assert_eq!(
+            (1, 1),
+            scaled_dimensions(dimensions, ResizeMode::Percentage(0.1))
+        );
+        assert_eq!(
+            (50, 25),
+            scaled_dimensions(dimensions, ResizeMode::Width(50))
+        );
+        assert_eq!(
+            (600, 300),
+            scaled_dimensions(dimensions, ResizeMode::Height(300))
+        );
+    }
+
+    #[test]
+    fn aspect_dimensions_at_least_a_pixel() {
+        assert_eq!((1, 1), scaled_dimensions((40, 30), ResizeMode::Fit(1, 1)));
+        assert_eq!(
+            (10, 1),
+            scaled_dimensions((1000, 1), ResizeMode::Fit(10, 10))
+        );
+        assert_eq!(
+            (1, 10),
+            scaled_dimensions((1, 1000), ResizeMode::Fit(10, 10))
+        );
+    }
+
+    #[test]
+    fn shrink_only_never_enlarges() {
+        let dimensions = (200, 100);
+
+        assert_eq!(
+            (200, 100),
+            output_dimensions(dimensions, ResizeMode::Width(400), true)
+        );
+        assert_eq!(
+            (200, 100),
+            output_dimensions(dimensions, ResizeMode::Exact(100, 200), true)
+        );
+        assert_eq!(
+            (200, 100),
+            output_dimensions(dimensions, ResizeMode::Fill(150, 150), true)
+        );
+        assert_eq!(
+            (100, 50),
+            output_dimensions(dimensions, ResizeMode::Fit(100, 100), true)
+        );
+        assert_eq!(
+            (50, 50),
+            output_dimensions(dimensions, ResizeMode::Fill(50, 50), true)
+        );
+    }
+
+    #[test]
+    fn fill_crops_center() {
+        use image::{ImageBuffer, Luma};
+
+        // left half black, right half white
+        let image = DynamicImage::ImageLuma8(ImageBuffer::from_fn(4, 2, |x, _| {
+            Luma([if x < 2 { 0 } else { 255 }])
+        }));
+
+        let filled = resize(&image, ResizeMode::Fill(2, 2), FilterType::Nearest, false);
+
+        assert_eq!((2, 2), filled.dimensions());
+        assert_eq!(0, filled.get_pixel(0, 0)[0]);
+        assert_eq!(255, filled.get_pixel(1, 0)[0]);
+    }
+}
@@ -0,0 +1,358 @@
+use image::{DynamicImage, FilterType, GenericImageView, ImageBuffer, Pixel, Primitive, Rgba};
+use num_traits::{Bounded, ToPrimitive};
+
+use crate::operations::transformations::{dynamic_map, FromRgba8};
+
+// Tolerance used when computing the dimensions of an expanded canvas, so floating point noise
+// (e.g. cos(90°) is not exactly zero) doesn't add an extra row or column.
+const CANVAS_EPSILON: f32 = 1e-3;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Interpolation {
+    Nearest,
+    Bilinear,
+    Bicubic,
+}
+
+impl From<FilterType> for Interpolation {
+    fn from(filter: FilterType) -> Self {
+        match filter {
+            FilterType::Nearest => Interpolation::Nearest,
+            FilterType::Triangle => Interpolation::Bilinear,
+            FilterType::CatmullRom | FilterType::Gaussian | FilterType::Lanczos3 => {
+                Interpolation::Bicubic
+            }
+        }
+    }
+}
+
+// Rotates an image clockwise by `degrees` around its center.
+//
+// If `expand_canvas` is true, the canvas grows so the complete rotated image fits within it,
+// otherwise the original bounds are kept and the corners of the rotated image are cut off.
+// Areas which are not covered by the rotated image are filled with `fill`.
+pub fn rotate(
+    image: &DynamicImage,
+    degrees: f32,
+    filter: FilterType,
+    expand_canvas: bool,
+    fill: Rgba<u8>,
+) -> DynamicImage {
+    let normalized = degrees.rem_euclid(360.0);
+
+    // Multiples of 90 degrees don't require any interpolation, if the canvas can take the
+    // rotated dimensions.
+    let (width, height) = image.dimensions();
+    if normalized == 0.0 {
+        return image.clone();
+    } else if normalized == 180.0 {
+        return image.rotate180();
+    } else if expand_canvas || width == height {
+        if normalized == 90.0 {
+            return image.rotate90();
+        } else if normalized == 270.0 {
+            return image.rotate270();
+        }
+    }
+
+    let interpolation = Interpolation::from(filter);
+
+    dynamic_map!(*image, ref buffer => rotate_buffer(
+        buffer,
+        normalized,
+        interpolation,
+        expand_canvas,
+        FromRgba8::from_rgba8(fill),
+    ))
+}
+
+// Dimensions of the image returned by `rotate`, for an input image of the given dimensions.
+pub(crate) fn output_dimensions(
+    (width, height): (u32, u32),
+    degrees: f32,
+    expand_canvas: bool,
+) -> (u32, u32) {
+    let normalized = degrees.rem_euclid(360.0);
+
+    if !expand_canvas || normalized == 0.0 || normalized == 180.0 {
+        (width, height)
+    } else if normalized == 90.0 || normalized == 270.0 {
+        (height, width)
+    } else {
+        rotated_dimensions(width, height, normalized.to_radians())
+    }
+}
+
+fn rotated_dimensions(width: u32, height: u32, radians: f32) -> (u32, u32) {
+    let (sin, cos) = radians.sin_cos();
+    let (w, h) = (width as f32, height as f32);
+
+    let new_width = (w * cos.abs() + h * sin.abs() - CANVAS_EPSILON).ceil();
+    let new_height = (w * sin.abs() + h * cos.abs() - CANVAS_EPSILON).ceil();
+
+    (new_width.max(1.0) as u32, new_height.max(1.0) as u32)
+}
+
+fn rotate_buffer<P>(
+    image: &ImageBuffer<P, Vec<P::Subpixel>>,
+    degrees: f32,
+    interpolation: Interpolation,
+    expand_canvas: bool,
+    fill: P,
+) -> ImageBuffer<P, Vec<P::Subpixel>>
+where
+    P: Pixel + 'static,
+    P::Subpixel: 'static,
+{
+    let (width, height) = image.dimensions();
+    let radians = degrees.to_radians();
+    let (sin, cos) = radians.sin_cos();
+
+    let (new_width, new_height) = if expand_canvas {
+        rotated_dimensions(width, height, radians)
+    } else {
+        (width, height)
+    };
+
+    // Pixel centers are located at integer coordinates.
+    let src_center = ((width as f32 - 1.0) / 2.0, (height as f32 - 1.0) / 2.0);
+    let dst_center = (
+        (new_width as f32 - 1.0) / 2.0,
+        (new_height as f32 - 1.0) / 2.0,
+    );
+
+    ImageBuffer::from_fn(new_width, new_height, |x, y| {
+        let dx = x as f32 - dst_center.0;
+        let dy = y as f32 - dst_center.1;
+
+        // Inverse of the clockwise rotation; maps the target pixel back onto the source image.
+        let sx = dx * cos + dy * sin + src_center.0;
+        let sy = -dx * sin + dy * cos + src_center.1;
+
+        match interpolation {
+            Interpolation::Nearest => sample_nearest(image, sx, sy, fill),
+            Interpolation::Bilinear => sample_bilinear(image, sx, sy, fill),
+            Interpolation::Bicubic => sample_bicubic(image, sx, sy, fill),
+        }
+    })
+}
+
+fn pixel_or_fill<P>(image: &ImageBuffer<P, Vec<P::Subpixel>>, x: i64, y: i64, fill: P) -> P
+where
+    P: Pixel + 'static,
+    P::Subpixel: 'static,
+{
+    let (width, height) = image.dimensions();
+
+    if x >= 0 && y >= 0 && x < i64::from(width) && y < i64::from(height) {
+        *image.get_pixel(x as u32, y as u32)
+    } else {
+        fill
+    }
+}
+
+fn sample_nearest<P>(image: &ImageBuffer<P, Vec<P::Subpixel>>, sx: f32, sy: f32, fill: P) -> P
+where
+    P: Pixel + 'static,
+    P::Subpixel: 'static,
+{
+    pixel_or_fill(image, sx.round() as i64, sy.round() as i64, fill)
+}
+
+fn sample_bilinear<P>(image: &ImageBuffer<P, Vec<P::Subpixel>>, sx: f32, sy: f32, fill: P) -> P
+where
+    P: Pixel + 'static,
+    P::Subpixel: 'static,
+{
+    let x0 = sx.floor();
+    let y0 = sy.floor();
+    let fx = sx - x0;
+    let fy = sy - y0;
+
+    let weights = [
+        (0, 0, (1.0 - fx) * (1.0 - fy)),
+        (1, 0, fx * (1.0 - fy)),
+        (0, 1, (1.0 - fx) * fy),
+        (1, 1, fx * fy),
+    ];
+
+    let samples = weights.iter().map(|&(ox, oy, weight)| {
+        let pixel = pixel_or_fill(image, x0 as i64 + ox, y0 as i64 + oy, fill);
+        (pixel, weight)
+    });
+
+    weighted_sum(samples)
+}
+
+fn sample_bicubic<P>(image: &ImageBuffer<P, Vec<P::Subpixel>>, sx: f32, sy: f32, fill: P) -> P
+where
+    P: Pixel + 'static,
+    P::Subpixel: 'static,
+{
+    let x0 = sx.floor();
+    let y0 = sy.floor();
+    let wx = catmull_rom_weights(sx - x0);
+    let wy = catmull_rom_weights(sy - y0);
+
+    let samples = (0..4).flat_map(|j| {
+        (0..4).map(move |i| {
+            let pixel = pixel_or_fill(image, x0 as i64 + i - 1, y0 as i64 + j - 1, fill);
+            (pixel, wx[i as usize] * wy[j as usize])
+        })
+    });
+
+    weighted_sum(samples)
+}
+
+// Weights of the four samples surrounding a point at offset `t` (between 0 and 1) from the
+// second sample.
+fn catmull_rom_weights(t: f32) -> [f32; 4] {
+    let t2 = t * t;
+    let t3 = t2 * t;
+
+    [
+        0.5 * (-t3 + 2.0 * t2 - t),
+        0.5 * (3.0 * t3 - 5.0 * t2 + 2.0),
+        0.5 * (-3.0 * t3 + 4.0 * t2 + t),
+        0.5 * (t3 - t2),
+    ]
+}
+
+fn weighted_sum<P, I>(samples: I) -> P
+where
+    P: Pixel + 'static,
+    P::Subpixel: 'static,
+    I: Iterator<Item = (P, f32)>,
+{
+    let channel_count = usize::from(P::CHANNEL_COUNT);
+    let mut accumulator = [0f32; 4];
+
+    for (pixel, weight) in samples {
+        for (acc, channel) in accumulator.iter_mut().zip(pixel.channels()) {
+            *acc += channel.to_f32().unwrap_or_default() * weight;
+        }
+    }
+
+    let max = <P::Subpixel as Bounded>::max_value()
+        .to_f32()
+        .unwrap_or(f32::MAX);
+
+    let channels: Vec<P::Subpixel> = accumulator[..channel_count]
+        .iter()
+        .map(|v| clamp_to_subpixel::<P::Subpixel>(*v, max))
+        .collect();
+
+    *P::from_slice(&channels)
+}
+
+fn clamp_to_subpixel<S: Primitive>(value: f32, max: f32) -> S {
+    num_traits::cast(value.round().max(0.0).min(max)).unwrap_or_else(S::zero)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FILL: Rgba<u8> = Rgba([255, 0, 0, 255]);
+
+    fn setup_gradient(width: u32, height: u32) -> DynamicImage {
+        DynamicImage::ImageRgba8(ImageBuffer::from_fn(width, height, |x, y| {
+            Rgba([(x * 10) as u8, (y * 10) as u8, 128, 255])
+        }))
+    }
+
+    #[test]
+    fn rotate_zero_is_identity() {
+        let img = setup_gradient(8, 6);
+        let rotated = rotate(&img, 0.0, FilterType::Triangle, false, FILL);
+
+        assert_eq!(img.raw_pixels(), rotated.raw_pixels());
+    }
+
+    #[test]
+    fn rotate_full_turn_is_identity() {
+        let img = setup_gradient(8, 6);
+        let rotated = rotate(&img, -360.0, FilterType::Triangle, true, FILL);
+
+        assert_eq!(img.raw_pixels(), rotated.raw_pixels());
+    }
+
+    #[test]
+    fn rotate_90_expanded_equals_rotate90() {
+        let img = setup_gradient(8, 6);
+        let rotated = rotate(&img, 90.0, FilterType::Triangle, true, FILL);
+
+        assert_eq!(img.rotate90().raw_pixels(), rotated.raw_pixels());
+    }
+
+    #[test]
+    fn rotate_negative_equals_positive_complement() {
+        let img = setup_gradient(8, 6);
+        let left = rotate(&img, -30.0, FilterType::Nearest, true, FILL);
+        let right = rotate(&img, 330.0, FilterType::Nearest, true, FILL);
+
+        assert_eq!(left.raw_pixels(), right.raw_pixels());
+    }
+
+    #[test]
+    fn rotate_45_expanded_dimensions() {
+        let img = setup_gradient(10, 10);
+        let rotated = rotate(&img, 45.0, FilterType::Triangle, true, FILL);
+
+        // 10 * cos(45°) + 10 * sin(45°) = 14.14..
+        assert_eq!((15, 15), rotated.dimensions());
+    }
+
+    #[test]
+    fn rotate_keep_bounds_dimensions() {
+        let img = setup_gradient(8, 6);
+        let rotated = rotate(&img, 1.7, FilterType::CatmullRom, false, FILL);
+
+        assert_eq!((8, 6), rotated.dimensions());
+    }
+
+    #[test]
+    fn rotate_fills_uncovered_corners() {
+        let img = setup_gradient(10, 10);
+
+        for filter in &[
+            FilterType::Nearest,
+            FilterType::Triangle,
+            FilterType::Lanczos3,
+        ] {
+            let rotated = rotate(&img, 45.0, *filter, true, FILL);
+
+            assert_eq!(FILL, rotated.get_pixel(0, 0));
+            assert_eq!(FILL, rotated.get_pixel(14, 14));
+        }
+    }
+
+    #[test]
+    fn output_dimensions_match_rotate() {
+        let img = setup_gradient(8, 5);
+
+        for degrees in &[0.0, 12.5, 90.0, 135.0, 180.0, -90.0, 300.0] {
+            for expand_canvas in &[false, true] {
+                let rotated = rotate(&img, *degrees, FilterType::Nearest, *expand_canvas, FILL);
+
+                assert_eq!(
+                    rotated.dimensions(),
+                    output_dimensions((8, 5), *degrees, *expand_canvas),
+                    "{} degrees, expand: {}",
+                    degrees,
+                    expand_canvas
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn rotate_keeps_color_type() {
+        let img = DynamicImage::ImageLuma8(ImageBuffer::from_pixel(4, 4, image::Luma([200])));
+        let rotated = rotate(&img, 10.0, FilterType::Triangle, false, FILL);
+
+        assert_eq!(img.color(), rotated.color());
+        // the center is covered by the source image
+        assert_eq!(Rgba([200, 200, 200, 255]), rotated.get_pixel(2, 2));
+    }
+}
@@ -0,0 +1,145 @@
+use image::{DynamicImage, FilterType, GenericImageView, ImageBuffer, Pixel};
+
+use crate::operations::transformations::dynamic_map;
+
+// Downscales `image` to exactly the given dimensions, for thumbnails.
+//
+// Resizing a large image directly with a wide filter is slow, since every output pixel samples
+// many input pixels. Instead, the image is first halved repeatedly with a 2x2 box filter, for as
+// long as it remains at least as large as the thumbnail. The remaining downscale (by a factor
+// smaller than 2) is done by a single resize with `filter`, which keeps the result close to that
+// of a direct resize.
+pub fn thumbnail(
+    image: &DynamicImage,
+    (width, height): (u32, u32),
+    filter: FilterType,
+) -> DynamicImage {
+    let mut halved: Option<DynamicImage> = None;
+
+    loop {
+        let current = halved.as_ref().unwrap_or(image);
+        let (current_width, current_height) = current.dimensions();
+
+        if current_width / 2 >= width.max(1) && current_height / 2 >= height.max(1) {
+            halved = Some(dynamic_map!(*current, ref buffer => halve(buffer)));
+        } else {
+            break;
+        }
+    }
+
+    match halved {
+        Some(halved) if halved.dimensions() == (width, height) => halved,
+        Some(halved) => halved.resize_exact(width, height, filter),
+        None if image.dimensions() == (width, height) => image.clone(),
+        None => image.resize_exact(width, height, filter),
+    }
+}
+
+// Halves both sides of `buffer`, rounded down; each output pixel is the (rounded) average of a
+// block of 2x2 input pixels.
+fn halve<P>(buffer: &ImageBuffer<P, Vec<u8>>) -> ImageBuffer<P, Vec<u8>>
+where
+    P: Pixel<Subpixel = u8> + 'static,
+{
+    let (width, height) = buffer.dimensions();
+    let (nwidth, nheight) = (width / 2, height / 2);
+    let channels = usize::from(P::CHANNEL_COUNT);
+
+    let input: &[u8] = buffer;
+    let stride = width as usize * channels;
+    let mut output = Vec::with_capacity(nwidth as usize * nheight as usize * channels);
+
+    for y in 0..nheight as usize {
+        let upper = &input[2 * y * stride..(2 * y + 1) * stride];
+        let lower = &input[(2 * y + 1) * stride..(2 * y + 2) * stride];
+
+        for x in 0..nwidth as usize {
+            let left = 2 * x * channels;
+            let right = left + channels;
+
+            for c in 0..channels {
+                let sum = u16::from(upper[left + c])
+                    + u16::from(upper[right + c])
+                    + u16::from(lower[left + c])
+                    + u16::from(lower[right + c]);
+
+                output.push(((sum + 2) / 4) as u8);
+            }
+        }
+    }
+
+    ImageBuffer::from_raw(nwidth, nheight, output)
+        .expect("The halved buffer has the size of its dimensions.")
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{GrayImage, Luma};
+
+    use crate::operations::mod_test_includes::*;
+
+    use super::*;
+
+    #[test]
+    fn halve_averages_blocks() {
+        let buffer = GrayImage::from_raw(
+            5,
+            3,
+            vec![0, 10, 20, 30, 99, 2, 12, 21, 31, 99, 99, 99, 99, 99, 99],
+        )
+        .unwrap();
+
+        let halved = halve(&buffer);
+
+        assert_eq!((2, 1), halved.dimensions());
+        assert_eq!(Luma([6]), *halved.get_pixel(0, 0));
+        assert_eq!(Luma([26]), *halved.get_pixel(1, 0));
+    }
+
+    #[test]
+    fn thumbnail_dimensions() {
+        let image = DynamicImage::new_rgb8(217, 447);
+
+        for &dimensions in &[
+            (100, 200),
+            (50, 50),
+            (1, 1),
+            (108, 223),
+            (217, 447),
+            (300, 500),
+        ] {
+            assert_eq!(
+                dimensions,
+                thumbnail(&image, dimensions, FilterType::Triangle).dimensions()
+            );
+        }
+    }
+
+    fn mean_difference(a: &DynamicImage, b: &DynamicImage) -> f64 {
+        let (a, b) = (a.to_rgb(), b.to_rgb());
+
+        let sum: u64 = a
+            .iter()
+            .zip(b.iter())
+            .map(|(a, b)| u64::from((i16::from(*a) - i16::from(*b)).unsigned_abs()))
+            .sum();
+
+        sum as f64 / a.len() as f64
+    }
+
+    #[test]
+    fn thumbnail_close_to_resize() {
+        let image = setup_test_image("resources/botanical.jpg");
+        let (width, height) = image.dimensions();
+        let (nwidth, nheight) = (width / 7, height / 7);
+
+        let exact = image.resize_exact(nwidth, nheight, FilterType::Triangle);
+        let fast = thumbnail(&image, (nwidth, nheight), FilterType::Triangle);
+        let nearest = image.resize_exact(nwidth, nheight, FilterType::Nearest);
+
+        let difference = mean_difference(&fast, &exact);
+
+        assert!(difference < 5.0, "mean difference: {}", difference);
+        assert!(difference * 3.0 < mean_difference(&nearest, &exact));
+    }
+}
@@ -0,0 +1,379 @@
+// Tone adjustments: gamma correction, levels and curves.
+//
+// Each adjustment maps the color channels of a pixel (but not its alpha channel) through a tone
+// function on values between 0 and 1. The buffer functions are generic over the subpixel type,
+// so 16-bit buffers are adjusted with their full precision; the tone function is evaluated once
+// per possible subpixel value, and the pixels are mapped through the resulting lookup table.
+use image::{DynamicImage, ImageBuffer, Pixel};
+use num_traits::Bounded;
+
+use crate::operations::transformations::dynamic_map;
+
+pub fn gamma(image: &DynamicImage, gamma: f32) -> DynamicImage {
+    dynamic_map!(*image, ref buffer => gamma_buffer(buffer, gamma))
+}
+
+pub fn levels(image: &DynamicImage, levels: Levels) -> DynamicImage {
+    dynamic_map!(*image, ref buffer => levels_buffer(buffer, levels))
+}
+
+pub fn curve(image: &DynamicImage, curve: &Curve) -> DynamicImage {
+    dynamic_map!(*image, ref buffer => curve_buffer(buffer, curve))
+}
+
+// Raises each value to the power `1 / gamma`; a gamma larger than 1 brightens the midtones, a
+// gamma smaller than 1 darkens them. The gamma should be larger than 0.
+pub fn gamma_buffer<P>(
+    buffer: &ImageBuffer<P, Vec<P::Subpixel>>,
+    gamma: f32,
+) -> ImageBuffer<P, Vec<P::Subpixel>>
+where
+    P: Pixel + 'static,
+    P::Subpixel: Into<u16> + 'static,
+{
+    let exponent = 1.0 / gamma;
+
+    map_tones(buffer, |value| value.powf(exponent))
+}
+
+pub fn levels_buffer<P>(
+    buffer: &ImageBuffer<P, Vec<P::Subpixel>>,
+    levels: Levels,
+) -> ImageBuffer<P, Vec<P::Subpixel>>
+where
+    P: Pixel + 'static,
+    P::Subpixel: Into<u16> + 'static,
+{
+    map_tones(buffer, |value| levels.tone(value))
+}
+
+pub fn curve_buffer<P>(
+    buffer: &ImageBuffer<P, Vec<P::Subpixel>>,
+    curve: &Curve,
+) -> ImageBuffer<P, Vec<P::Subpixel>>
+where
+    P: Pixel + 'static,
+    P::Subpixel: Into<u16> + 'static,
+{
+    map_tones(buffer, |value| curve.tone(value))
+}
+
+// Maps the color channels of each pixel through `tone`. The `Into<u16>` bound limits the
+// subpixels to 8 and 16 bits, for which the lookup table stays small.
+fn map_tones<P, F>(
+    buffer: &ImageBuffer<P, Vec<P::Subpixel>>,
+    tone: F,
+) -> ImageBuffer<P, Vec<P::Subpixel>>
+where
+    P: Pixel + 'static,
+    P::Subpixel: Into<u16> + 'static,
+    F: Fn(f32) -> f32,
+{
+    let max: u16 = P::Subpixel::max_value().into();
+    let scale = f32::from(max);
+
+    let table: Vec<P::Subpixel> = (0..=max)
+        .map(|value| {
+            let mapped = tone(f32::from(value) / scale).clamp(0.0, 1.0) * scale;
+
+            num_traits::cast(mapped.round()).unwrap_or_else(P::Subpixel::max_value)
+        })
+        .collect();
+
+    let mut result = buffer.clone();
+
+    for pixel in result.pixels_mut() {
+        pixel.apply_without_alpha(|value| table[usize::from(value.into())]);
+    }
+
+    result
+}
+
+// Input and output levels. Values at or below the input black point become the output black
+// point, values at or above the input white point become the output white point, and the
+// values in between are spread out with `midtone` as gamma. All points are between 0 and 1.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Levels {
+    pub input_black: f32,
+    pub input_white: f32,
+    pub midtone: f32,
+    pub output_black: f32,
+    pub output_white: f32,
+}
+
+impl Levels {
+    // The output points may be swapped, which inverts the image.
+    pub fn is_valid(&self) -> bool {
+        let unit = |value: f32| (0.0..=1.0).contains(&value);
+
+        unit(self.input_black)
+            && unit(self.input_white)
+            && unit(self.output_black)
+            && unit(self.output_white)
+            && self.input_black < self.input_white
+            && self.midtone.is_finite()
+            && self.midtone > 0.0
+    }
+
+    fn tone(&self, value: f32) -> f32 {
+        let normalized = ((value - self.input_black) / (self.input_white - self.input_black))
+            .clamp(0.0, 1.0)
+            .powf(1.0 / self.midtone);
+
+        self.output_black + normalized * (self.output_white - self.output_black)
+    }
+}
+
+// A smooth tone curve through control points, interpolated with a monotone cubic spline, so the
+// curve doesn't overshoot between the points. Left of the first point and right of the last
+// point, the curve is flat.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Curve {
+    points: Vec<(f32, f32)>,
+    // Slope of the curve at each of the points.
+    tangents: Vec<f32>,
+}
+
+impl Curve {
+    // None unless there are at least two points, all between 0 and 1, with strictly increasing
+    // x coordinates.
+    pub fn new(points: &[(f32, f32)]) -> Option<Curve> {
+        let unit = |value: f32| (0.0..=1.0).contains(&value);
+
+        let valid = points.len() >= 2
+            && points.iter().all(|&(x, y)| unit(x) && unit(y))
+            && points.windows(2).all(|pair| pair[0].0 < pair[1].0);
+
+        if !valid {
+            return None;
+        }
+
+        let slopes: Vec<f32> = points
+            .windows(2)
+            .map(|pair| (pair[1].1 - pair[0].1) / (pair[1].0 - pair[0].0))
+            .collect();
+
+        let last = slopes.len() - 1;
+        let mut tangents: Vec<f32> = (0..points.len())
+            .map(|i| match i {
+                0 => slopes[0],
+                i if i > last => slopes[last],
+                i if slopes[i - 1] * slopes[i] <= 0.0 => 0.0,
+                i => (slopes[i - 1] + slopes[i]) / 2.0,
+            })
+            .collect();
+
+        // Fritsch-Carlson: limit the tangents, so each segment is monotone.
+        for (i, slope) in slopes.iter().enumerate() {
+            if *slope == 0.0 {
+                tangents[i] = 0.0;
+                tangents[i + 1] = 0.0;
+                continue;
+            }
+
+            let a = tangents[i] / slope;
+            let b = tangents[i + 1] / slope;
+            let magnitude = a * a + b * b;
+
+            if magnitude > 9.0 {
+                let t = 3.0 / magnitude.sqrt();
+                tangents[i] = t * a * slope;
+                tangents[i + 1] = t * b * slope;
+            }
+        }
+
+        Some(Curve {
+            points: points.to_vec(),
+            tangents,
+        })
+    }
+
+    fn tone(&self, value: f32) -> f32 {
+        let (first, last) = (self.points[0], self.points[self.points.len() - 1]);
+
+        if value <= first.0 {
+            return first.1;
+        } else if value >= last.0 {
+            return last.1;
+        }
+
+        let i = self
+            .points
+            .windows(2)
+            .position(|pair| value <= pair[1].0)
+            .unwrap_or(self.points.len() - 2);
+
+        let ((x0, y0), (x1, y1)) = (self.points[i], self.points[i + 1]);
+        let (m0, m1) = (self.tangents[i], self.tangents[i + 1]);
+
+        let h = x1 - x0;
+        let t = (value - x0) / h;
+        let (t2, t3) = (t * t, t * t * t);
+
+        (2.0 * t3 - 3.0 * t2 + 1.0) * y0
+            + (t3 - 2.0 * t2 + t) * h * m0
+            + (-2.0 * t3 + 3.0 * t2) * y1
+            + (t3 - t2) * h * m1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{Rgb, Rgba};
+
+    use super::*;
+
+    type Rgb16Image = ImageBuffer<Rgb<u16>, Vec<u16>>;
+
+    fn gradient16() -> Rgb16Image {
+        ImageBuffer::from_fn(256, 1, |x, _| {
+            let value = (x * 257) as u16;
+            Rgb([value, value / 2, u16::MAX - value])
+        })
+    }
+
+    const IDENTITY: Levels = Levels {
+        input_black: 0.0,
+        input_white: 1.0,
+        midtone: 1.0,
+        output_black: 0.0,
+        output_white: 1.0,
+    };
+
+    #[test]
+    fn gamma_keeps_16_bit_precision() {
+        let buffer: Rgb16Image = ImageBuffer::from_pixel(1, 1, Rgb([1000, 1001, 32768]));
+
+        let result = gamma_buffer(&buffer, 2.0);
+        let expected = |value: u16| ((f32::from(value) / 65535.0).sqrt() * 65535.0).round() as u16;
+
+        // 1000 and 1001 would be the same value in 8 bits
+        assert_eq!(
+            Rgb([expected(1000), expected(1001), expected(32768)]),
+            *result.get_pixel(0, 0)
+        );
+        assert_ne!(result.get_pixel(0, 0)[0], result.get_pixel(0, 0)[1]);
+    }
+
+    #[test]
+    fn gamma_of_one_is_identity() {
+        let buffer = gradient16();
+
+        assert_eq!(buffer.to_vec(), gamma_buffer(&buffer, 1.0).into_raw());
+    }
+
+    #[test]
+    fn gamma_keeps_alpha() {
+        let buffer = ImageBuffer::from_pixel(1, 1, Rgba([64u8, 128, 255, 100]));
+
+        let result = gamma_buffer(&buffer, 0.5);
+
+        assert_eq!(Rgba([16, 64, 255, 100]), *result.get_pixel(0, 0));
+    }
+
+    #[test]
+    fn levels_16_bit() {
+        let buffer: Rgb16Image = ImageBuffer::from_pixel(1, 1, Rgb([6000, 32768, 60000]));
+        let stretch = Levels {
+            input_black: 0.1,
+            input_white: 0.9,
+            ..IDENTITY
+        };
+
+        let result = levels_buffer(&buffer, stretch);
+
+        assert_eq!(Rgb([0, 32768, 65535]), *result.get_pixel(0, 0));
+        assert_eq!(
+            gradient16().into_raw(),
+            levels_buffer(&gradient16(), IDENTITY).into_raw()
+        );
+    }
+
+    #[test]
+    fn levels_output_range() {
+        let buffer = ImageBuffer::from_pixel(1, 1, Rgb([0u8, 255, 51]));
+        let compress = Levels {
+            output_black: 0.2,
+            output_white: 0.6,
+            ..IDENTITY
+        };
+
+        let result = levels_buffer(&buffer, compress);
+
+        assert_eq!(Rgb([51, 153, 71]), *result.get_pixel(0, 0));
+    }
+
+    #[test]
+    fn levels_validity() {
+        assert!(IDENTITY.is_valid());
+        assert!(Levels {
+            output_black: 1.0,
+            output_white: 0.0,
+            ..IDENTITY
+        }
+        .is_valid());
+        assert!(!Levels {
+            input_black: 0.5,
+            input_white: 0.5,
+            ..IDENTITY
+        }
+        .is_valid());
+        assert!(!Levels {
+            midtone: 0.0,
+            ..IDENTITY
+        }
+        .is_valid());
+        assert!(!Levels {
+            output_white: 1.5,
+            ..IDENTITY
+        }
+        .is_valid());
+    }
+
+    #[test]
+    fn curve_through_points() {
+        let curve = Curve::new(&[(0.0, 0.0), (0.25, 0.1), (0.5, 0.5), (1.0, 0.8)]).unwrap();
+
+        assert_eq!(0.1, curve.tone(0.25));
+        assert_eq!(0.5, curve.tone(0.5));
+        assert_eq!(0.8, curve.tone(1.0));
+
+        // monotone between the points
+        let tones: Vec<f32> = (0..=100).map(|i| curve.tone(i as f32 / 100.0)).collect();
+        assert!(tones.windows(2).all(|pair| pair[0] <= pair[1]));
+    }
+
+    #[test]
+    fn curve_is_flat_outside_points() {
+        let curve = Curve::new(&[(0.2, 0.3), (0.8, 0.9)]).unwrap();
+
+        assert_eq!(0.3, curve.tone(0.0));
+        assert_eq!(0.9, curve.tone(1.0));
+        assert!((curve.tone(0.5) - 0.6).abs() < 1e-6);
+    }
+
+    #[test]
+    fn curve_16_bit() {
+        let identity = Curve::new(&[(0.0, 0.0), (1.0, 1.0)]).unwrap();
+        assert_eq!(
+            gradient16().into_raw(),
+            curve_buffer(&gradient16(), &identity).into_raw()
+        );
+
+        let invert = Curve::new(&[(0.0, 1.0), (1.0, 0.0)]).unwrap();
+        let buffer: Rgb16Image = ImageBuffer::from_pixel(1, 1, Rgb([0, 1, 65535]));
+
+        assert_eq!(
+            Rgb([65535, 65534, 0]),
+            *curve_buffer(&buffer, &invert).get_pixel(0, 0)
+        );
+    }
+
+    #[test]
+    fn curve_invalid_points() {
+        assert_eq!(None, Curve::new(&[(0.0, 0.0)]));
+        assert_eq!(None, Curve::new(&[(0.0, 0.0), (0.0, 1.0)]));
+        assert_eq!(None, Curve::new(&[(0.5, 0.0), (0.2, 1.0)]));
+        assert_eq!(None, Curve::new(&[(0.0, 0.0), (1.0, 1.5)]));
+    }
+}
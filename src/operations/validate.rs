@@ -0,0 +1,493 @@
+// Dry run of a `Program`, which tracks the dimensions and color type of the image through each
+// statement without touching any pixels. This way, invalid statements (e.g. a crop selection
+// outside of the image) are found before any (possibly expensive) operation has been applied.
+use image::ColorType;
+
+use crate::error::Error;
+use crate::operations::engine::{Environment, EnvironmentKind, Program, Statement, Verify};
+use crate::operations::registry::Registry;
+use crate::operations::transformations::resize::{self, ResizeMode};
+use crate::operations::transformations::rotate;
+use crate::operations::transformations::tone::Levels;
+use crate::operations::Operation;
+
+// The properties of an image which are tracked by `validate`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ImageSignature {
+    pub dimensions: (u32, u32),
+    // None if the color type isn't known, i.e. it's the unchanged color type of the input image.
+    pub color_type: Option<ColorType>,
+}
+
+// A statement which would fail if the program were run.
+#[derive(Debug)]
+pub struct InvalidStatement {
+    // Index of the statement within the program.
+    pub index: usize,
+    pub error: Error,
+}
+
+// Validates `program`, which uses built-in operations only, for an input image with the given
+// dimensions. Returns the signature of the image the program would produce, or every invalid
+// statement.
+pub fn validate(
+    program: &Program,
+    input_dimensions: (u32, u32),
+) -> Result<ImageSignature, Vec<InvalidStatement>> {
+    validate_signature(
+        program,
+        ImageSignature {
+            dimensions: input_dimensions,
+            color_type: None,
+        },
+        &Registry::default(),
+    )
+}
+
+// Like `validate`, but for an input image of which the color type may be known as well, and for
+// a program which may use the operations of `registry`.
+//
+// An invalid statement leaves the image unchanged, like it does when the engine runs it, so the
+// statements which follow it are validated as if it were skipped.
+pub fn validate_signature(
+    program: &Program,
+    input: ImageSignature,
+    registry: &Registry,
+) -> Result<ImageSignature, Vec<InvalidStatement>> {
+    let mut environment = Environment::default();
+    let mut signature = input;
+    let mut invalid = Vec::new();
+
+    for (index, statement) in program.iter().enumerate() {
+        match statement {
+            Statement::Operation(operation) => {
+                match next_signature(signature, operation, &mut environment, registry) {
+                    Ok(next) => signature = next,
+                    Err(error) => invalid.push(InvalidStatement { index, error }),
+                }
+            }
+            Statement::RegisterEnvironmentItem(item) => {
+                environment.insert_or_update(item.clone());
+            }
+            Statement::DeregisterEnvironmentItem(key) => {
+                environment.remove(*key);
+            }
+        }
+    }
+
+    if invalid.is_empty() {
+        Ok(signature)
+    } else {
+        Err(invalid)
+    }
+}
+
+fn next_signature(
+    signature: ImageSignature,
+    operation: &Operation,
+    environment: &mut Environment,
+    registry: &Registry,
+) -> Result<ImageSignature, Error> {
+    let dimensions = signature.dimensions;
+
+    let next = match *operation {
+        Operation::AutoLevels(clip) => {
+            Verify::auto_levels_clip_within_range(clip)?;
+            signature
+        }
+        Operation::Crop(lx, ly, rx, ry) => {
+            Verify::crop_selection_box_can_exist(lx, ly, rx, ry)?;
+            Verify::crop_selection_within_image_bounds(dimensions, lx, ly, rx, ry)?;
+
+            ImageSignature {
+                dimensions: (rx - lx, ry - ly),
+                ..signature
+            }
+        }
+        // The color type produced by a custom operation isn't known.
+        Operation::Custom(ref name, ref arg) => ImageSignature {
+            dimensions: registry
+                .resolve(name, arg)?
+                .output_dimensions(dimensions, arg),
+            color_type: None,
+        },
+        Operation::Curve(ref points) => {
+            Verify::curve_from_points(points)?;
+            signature
+        }
+        Operation::Gamma(gamma) => {
+            Verify::gamma_is_positive(gamma)?;
+            signature
+        }
+        Operation::GrayScale => ImageSignature {
+            color_type: Some(ColorType::Gray(8)),
+            ..signature
+        },
+        Operation::Levels(input_black, input_white, midtone, output_black, output_white) => {
+            Verify::levels_are_valid(Levels {
+                input_black,
+                input_white,
+                midtone,
+                output_black,
+                output_white,
+            })?;
+            signature
+        }
+        Operation::Overlay(_, _, opacity) => {
+            Verify::overlay_opacity_within_range(opacity)?;
+            signature
+        }
+        Operation::Pad(top, right, bottom, left) => ImageSignature {
+            dimensions: Verify::pad_margins_within_range(dimensions, (top, right, bottom, left))?,
+            ..signature
+        },
+        Operation::PadTo(width, height, _) => {
+            Verify::pad_canvas_contains_image(dimensions, (width, height))?;
+
+            ImageSignature {
+                dimensions: (width, height),
+                ..signature
+            }
+        }
+        Operation::Dither(_, levels) => {
+            Verify::dither_levels_within_range(levels)?;
+            signature
+        }
+        Operation::Quantize(colors) => {
+            Verify::quantize_colors_within_range(colors)?;
+            signature
+        }
+        Operation::Resize(width, height) => {
+            let mode = if environment
+                .get(EnvironmentKind::OptResizePreserveAspectRatio)
+                .is_some()
+            {
+                ResizeMode::Fit(width, height)
+            } else {
+                ResizeMode::Exact(width, height)
+            };
+
+            resize_signature(signature, mode, environment)
+        }
+        Operation::ResizeFill(width, height) => {
+            Verify::resize_target_is_positive("resize-fill", &[width, height])?;
+            resize_signature(signature, ResizeMode::Fill(width, height), environment)
+        }
+        Operation::ResizeFit(width, height) => {
+            Verify::resize_target_is_positive("resize-fit", &[width, height])?;
+            resize_signature(signature, ResizeMode::Fit(width, height), environment)
+        }
+        Operation::ResizeHeight(height) => {
+            Verify::resize_target_is_positive("resize-height", &[height])?;
+            resize_signature(signature, ResizeMode::Height(height), environment)
+        }
+        Operation::ResizePercent(percentage) => {
+            Verify::resize_percentage_is_positive(percentage)?;
+            resize_signature(signature, ResizeMode::Percentage(percentage), environment)
+        }
+        Operation::ResizeWidth(width) => {
+            Verify::resize_target_is_positive("resize-width", &[width])?;
+            resize_signature(signature, ResizeMode::Width(width), environment)
+        }
+        Operation::Rotate(degrees) => {
+            Verify::rotate_angle_is_finite(degrees)?;
+
+            let expand_canvas = environment
+                .get(EnvironmentKind::OptRotateExpandCanvas)
+                .is_some();
+
+            ImageSignature {
+                dimensions: rotate::output_dimensions(dimensions, degrees, expand_canvas),
+                ..signature
+            }
+        }
+        Operation::Rotate90 | Operation::Rotate270 => ImageSignature {
+            dimensions: (dimensions.1, dimensions.0),
+            ..signature
+        },
+        Operation::Thumbnail(width, height) => {
+            Verify::resize_target_is_positive("thumbnail", &[width, height])?;
+            resize_signature(signature, ResizeMode::Fit(width, height), environment)
+        }
+        Operation::Blur(_)
+        | Operation::Brighten(_)
+        | Operation::Contrast(_)
+        | Operation::Equalize
+        | Operation::EqualizeLuminance
+        | Operation::Filter3x3(_)
+        | Operation::FlipHorizontal
+        | Operation::FlipVertical
+        | Operation::HueRotate(_)
+        | Operation::Invert
+        | Operation::Rotate180
+        | Operation::Unsharpen(..) => signature,
+    };
+
+    Ok(next)
+}
+
+fn resize_signature(
+    signature: ImageSignature,
+    mode: ResizeMode,
+    environment: &mut Environment,
+) -> ImageSignature {
+    let shrink_only = environment
+        .get(EnvironmentKind::OptResizeShrinkOnly)
+        .is_some();
+
+    ImageSignature {
+        dimensions: resize::output_dimensions(signature.dimensions, mode, shrink_only),
+        ..signature
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{DynamicImage, GenericImageView};
+
+    use crate::operations::engine::{EnvironmentItem, ImageEngine};
+    use crate::operations::mod_test_includes::*;
+    use crate::operations::placement::{Anchor, Placement};
+    use crate::operations::registry::ImageOperation;
+    use crate::operations::{OpArg, OpArgKind};
+
+    use super::*;
+
+    #[test]
+    fn validate_matches_engine() {
+        let img: DynamicImage = setup_default_test_image();
+        let program = vec![
+            Statement::Operation(Operation::Crop(10, 10, 210, 400)),
+            Statement::RegisterEnvironmentItem(EnvironmentItem::PreserveAspectRatio),
+            Statement::Operation(Operation::Resize(100, 100)),
+            Statement::RegisterEnvironmentItem(EnvironmentItem::RotateExpandCanvas),
+            Statement::Operation(Operation::Rotate(30.0)),
+            Statement::Operation(Operation::Rotate90),
+            Statement::DeregisterEnvironmentItem(EnvironmentKind::OptResizePreserveAspectRatio),
+            Statement::Operation(Operation::Resize(60, 50)),
+            Statement::Operation(Operation::Crop(0, 0, 30, 20)),
+            Statement::Operation(Operation::GrayScale),
+        ];
+
+        let signature = validate(&program, img.dimensions()).unwrap();
+
+        let mut engine = ImageEngine::new(img);
+        let result = engine.ignite(program).unwrap();
+
+        assert_eq!(result.dimensions(), signature.dimensions);
+        assert_eq!(Some(result.color()), signature.color_type);
+    }
+
+    #[test]
+    fn validate_reports_every_invalid_statement() {
+        let program = vec![
+            Statement::Operation(Operation::Crop(0, 0, 300, 10)),
+            Statement::Operation(Operation::Resize(50, 50)),
+            Statement::Operation(Operation::Crop(10, 10, 5, 20)),
+            Statement::Operation(Operation::Overlay(
+                "a.png".to_string(),
+                Placement::Offset(0, 0),
+                2.0,
+            )),
+            // valid for 50x50, but not for the 217x447 input
+            Statement::Operation(Operation::Crop(0, 0, 50, 50)),
+            Statement::Operation(Operation::Crop(0, 0, 51, 50)),
+        ];
+
+        let invalid = validate(&program, (217, 447)).unwrap_err();
+
+        let indices: Vec<_> = invalid.iter().map(|i| i.index).collect();
+        assert_eq!(vec![0, 2, 3, 5], indices);
+
+        assert!(matches!(
+            invalid[0].error,
+            Error::CropOutOfBounds {
+                dimensions: (217, 447),
+                ..
+            }
+        ));
+        assert!(matches!(
+            invalid[1].error,
+            Error::CropInvalidSelection { .. }
+        ));
+        assert!(matches!(invalid[2].error, Error::OverlayInvalidOpacity(_)));
+        assert!(matches!(
+            invalid[3].error,
+            Error::CropOutOfBounds {
+                dimensions: (50, 50),
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn validate_color_type() {
+        let input = ImageSignature {
+            dimensions: (10, 10),
+            color_type: Some(ColorType::RGBA(8)),
+        };
+
+        let unchanged = validate_signature(
+            &vec![Statement::Operation(Operation::Blur(1.0))],
+            input,
+            &Registry::default(),
+        );
+        assert_eq!(Some(ColorType::RGBA(8)), unchanged.unwrap().color_type);
+
+        let gray = validate(&vec![Statement::Operation(Operation::GrayScale)], (10, 10));
+        assert_eq!(Some(ColorType::Gray(8)), gray.unwrap().color_type);
+    }
+
+    #[test]
+    fn validate_resize_variants_match_engine() {
+        let img: DynamicImage = setup_default_test_image();
+        let program = vec![
+            Statement::Operation(Operation::ResizeFit(200, 200)),
+            Statement::Operation(Operation::ResizePercent(75.0)),
+            Statement::Operation(Operation::ResizeWidth(60)),
+            Statement::RegisterEnvironmentItem(EnvironmentItem::ResizeShrinkOnly),
+            Statement::Operation(Operation::ResizeHeight(500)),
+            Statement::Operation(Operation::ResizeFill(40, 40)),
+            Statement::DeregisterEnvironmentItem(EnvironmentKind::OptResizeShrinkOnly),
+            Statement::Operation(Operation::ResizeHeight(50)),
+        ];
+
+        let signature = validate(&program, img.dimensions()).unwrap();
+
+        let mut engine = ImageEngine::new(img);
+        let result = engine.ignite(program).unwrap();
+
+        assert_eq!((50, 50), signature.dimensions);
+        assert_eq!(result.dimensions(), signature.dimensions);
+    }
+
+    #[test]
+    fn validate_pad() {
+        let program = vec![
+            Statement::Operation(Operation::Pad(1, 2, 3, 4)),
+            Statement::Operation(Operation::Pad(0, u32::MAX, 0, 0)),
+            Statement::Operation(Operation::PadTo(20, 10, Anchor::Center)),
+            Statement::Operation(Operation::PadTo(30, 20, Anchor::Center)),
+        ];
+
+        let invalid = validate(&program, (10, 10)).unwrap_err();
+
+        let indices: Vec<_> = invalid.iter().map(|i| i.index).collect();
+        assert_eq!(vec![1, 2], indices);
+        assert!(matches!(invalid[0].error, Error::PadOutOfRange { .. }));
+        assert!(matches!(
+            invalid[1].error,
+            Error::PadCanvasTooSmall {
+                dimensions: (16, 14),
+                canvas: (20, 10),
+            }
+        ));
+
+        let valid = validate(&program[3..].to_vec(), (10, 10)).unwrap();
+        assert_eq!((30, 20), valid.dimensions);
+    }
+
+    #[test]
+    fn validate_resize_percentage() {
+        let program = vec![Statement::Operation(Operation::ResizePercent(0.0))];
+
+        let invalid = validate(&program, (10, 10)).unwrap_err();
+
+        assert!(matches!(
+            invalid[0].error,
+            Error::ResizeInvalidPercentage(_)
+        ));
+    }
+
+    #[test]
+    fn validate_resize_zero_target() {
+        let program = vec![
+            Statement::Operation(Operation::ResizeFill(10, 0)),
+            Statement::Operation(Operation::ResizeFit(0, 10)),
+            Statement::Operation(Operation::ResizeWidth(0)),
+            Statement::Operation(Operation::ResizeHeight(0)),
+            Statement::Operation(Operation::Thumbnail(10, 0)),
+        ];
+
+        let invalid = validate(&program, (40, 30)).unwrap_err();
+
+        assert_eq!(5, invalid.len());
+        assert!(invalid
+            .iter()
+            .all(|invalid| matches!(invalid.error, Error::ResizeInvalidTarget(_))));
+    }
+
+    #[test]
+    fn validate_rotate_angle() {
+        let program = vec![
+            Statement::RegisterEnvironmentItem(EnvironmentItem::RotateExpandCanvas),
+            Statement::Operation(Operation::Rotate(f32::NAN)),
+            Statement::Operation(Operation::Rotate(f32::INFINITY)),
+        ];
+
+        let invalid = validate(&program, (10, 10)).unwrap_err();
+
+        let indices: Vec<_> = invalid.iter().map(|i| i.index).collect();
+        assert_eq!(vec![1, 2], indices);
+        assert!(matches!(invalid[0].error, Error::RotateInvalidAngle(_)));
+    }
+
+    struct Tile;
+
+    impl ImageOperation for Tile {
+        fn name(&self) -> &str {
+            "tile"
+        }
+
+        fn arg_kind(&self) -> OpArgKind {
+            OpArgKind::UnsignedIntegerTuple2
+        }
+
+        fn output_dimensions(&self, (width, height): (u32, u32), arg: &OpArg) -> (u32, u32) {
+            match *arg {
+                OpArg::UnsignedIntegerTuple2(x, y) => (width * x, height * y),
+                _ => (width, height),
+            }
+        }
+
+        fn apply(
+            &self,
+            image: &DynamicImage,
+            _arg: &OpArg,
+        ) -> Result<DynamicImage, Box<dyn std::error::Error + Send + Sync>> {
+            Ok(image.clone())
+        }
+    }
+
+    #[test]
+    fn validate_custom_operation() {
+        let mut registry = Registry::new();
+        registry.register(Tile).unwrap();
+
+        let tile = |name: &str| {
+            Statement::Operation(Operation::Custom(
+                name.to_string(),
+                OpArg::UnsignedIntegerTuple2(2, 3),
+            ))
+        };
+
+        let program = vec![
+            Statement::Operation(Operation::GrayScale),
+            tile("tile"),
+            Statement::Operation(Operation::Crop(0, 0, 20, 30)),
+        ];
+
+        let input = ImageSignature {
+            dimensions: (10, 10),
+            color_type: None,
+        };
+
+        let signature = validate_signature(&program, input, &registry).unwrap();
+        assert_eq!((20, 30), signature.dimensions);
+        assert_eq!(None, signature.color_type);
+
+        // without the registry, the operation is unknown
+        let invalid = validate(&program, (10, 10)).unwrap_err();
+        assert!(matches!(invalid[0].error, Error::UnknownOperation { .. }));
+    }
+}
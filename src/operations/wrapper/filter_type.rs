@@ -1,6 +1,7 @@
-use std::error::Error;
 use std::fmt::{Debug, Formatter};
 
+use crate::error::Error;
+
 // Wrapper for image::FilterType.
 // Does only exists, because image::FilterType does not implement PartialEq and Debug.
 pub enum FilterTypeWrap {
@@ -78,14 +79,64 @@ impl From<FilterTypeWrap> for image::FilterType {
 }
 
 impl FilterTypeWrap {
-    pub fn try_from_str(val: &str) -> Result<FilterTypeWrap, Box<dyn Error>> {
+    pub fn try_from_str(val: &str) -> Result<FilterTypeWrap, Error> {
         match val.to_lowercase().as_str() {
             "catmullrom" | "cubic" => Ok(FilterTypeWrap::Inner(image::FilterType::CatmullRom)),
             "gaussian" => Ok(FilterTypeWrap::Inner(image::FilterType::Gaussian)),
             "lanczos3" => Ok(FilterTypeWrap::Inner(image::FilterType::Lanczos3)),
             "nearest" => Ok(FilterTypeWrap::Inner(image::FilterType::Nearest)),
             "triangle" => Ok(FilterTypeWrap::Inner(image::FilterType::Triangle)),
-            fail => Err(format!("No such sampling filter: {}", fail).into()),
+            fail => Err(Error::UnknownSamplingFilter(fail.to_string())),
+        }
+    }
+
+    // Canonical name of the wrapped filter; accepted by `try_from_str`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            FilterTypeWrap::Inner(image::FilterType::CatmullRom) => "catmullrom",
+            FilterTypeWrap::Inner(image::FilterType::Gaussian) => "gaussian",
+            FilterTypeWrap::Inner(image::FilterType::Lanczos3) => "lanczos3",
+            FilterTypeWrap::Inner(image::FilterType::Nearest) => "nearest",
+            FilterTypeWrap::Inner(image::FilterType::Triangle) => "triangle",
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for FilterTypeWrap {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.name())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for FilterTypeWrap {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = <std::borrow::Cow<str> as serde::Deserialize>::deserialize(deserializer)?;
+
+        FilterTypeWrap::try_from_str(&name).map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn name_round_trip() {
+        let filters = [
+            image::FilterType::CatmullRom,
+            image::FilterType::Gaussian,
+            image::FilterType::Lanczos3,
+            image::FilterType::Nearest,
+            image::FilterType::Triangle,
+        ];
+
+        for filter in filters.iter() {
+            let wrap = FilterTypeWrap::Inner(*filter);
+            let parsed = FilterTypeWrap::try_from_str(wrap.name()).unwrap();
+
+            assert_eq!(wrap, parsed);
         }
     }
 }
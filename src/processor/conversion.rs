@@ -1,7 +1,12 @@
 use std::io::{self, Write};
 use std::path::Path;
 
-use crate::config::Config;
+use crate::config::{
+    BitmapConversion, Config, FormatEncodingSettings, PNGCompression, PNGEncodingSettings,
+    PNGFilterType,
+};
+use crate::error::Error;
+use crate::operations::transformations::dither;
 use crate::processor::ProcessWithConfig;
 
 pub struct ConversionProcessor<'a> {
@@ -43,7 +48,12 @@ impl<'a> ConversionProcessor<'a> {
 
         match output_format {
             image::ImageOutputFormat::PNM(image::pnm::PNMSubtype::Bitmap(_)) => {
-                Some(image.grayscale())
+                let bitmap = match config.encoding_settings.pnm_settings.bitmap_conversion {
+                    BitmapConversion::Threshold(level) => dither::threshold(image, level),
+                    BitmapConversion::Dither(method) => dither::black_and_white(image, method),
+                };
+
+                Some(image::DynamicImage::ImageLuma8(bitmap))
             }
             image::ImageOutputFormat::PNM(image::pnm::PNMSubtype::Graymap(_)) => {
                 Some(image.grayscale())
@@ -58,34 +68,110 @@ impl<'a> ConversionProcessor<'a> {
     fn save_to_file<P: AsRef<Path>>(
         buffer: &image::DynamicImage,
         format: image::ImageOutputFormat,
+        settings: &FormatEncodingSettings,
         path: P,
-    ) -> Result<(), String> {
-        let mut out = std::fs::File::create(path).map_err(|err| err.to_string())?;
+    ) -> Result<(), Error> {
+        let mut out = std::fs::File::create(path)?;
 
-        buffer
-            .write_to(&mut out, format)
-            .map_err(|err| err.to_string())
+        ConversionProcessor::encode(buffer, format, settings, &mut out)
     }
 
     fn export_to_stdout(
         buffer: &image::DynamicImage,
         format: image::ImageOutputFormat,
-    ) -> Result<(), String> {
+        settings: &FormatEncodingSettings,
+    ) -> Result<(), Error> {
         let mut write_buffer = Vec::new();
 
-        buffer
-            .write_to(&mut write_buffer, format)
-            .map_err(|err| err.to_string())?;
+        ConversionProcessor::encode(buffer, format, settings, &mut write_buffer)?;
+
+        io::stdout().write_all(&write_buffer).map_err(Error::Io)
+    }
 
-        io::stdout()
-            .write(&write_buffer)
-            .map(|_| ())
-            .map_err(|err| err.to_string())
+    // The image crate doesn't expose the settings of its PNG encoder, so PNG images are encoded
+    // with the png crate directly. We can't use the png version the image crate depends on
+    // (0.15), since its encoder corrupts images which use the up, avg or paeth filters: each row
+    // is filtered against the filtered, instead of the original, previous row. The png types
+    // stay out of the settings, so this dependency isn't part of our API.
+    fn encode<W: Write>(
+        buffer: &image::DynamicImage,
+        format: image::ImageOutputFormat,
+        settings: &FormatEncodingSettings,
+        writer: &mut W,
+    ) -> Result<(), Error> {
+        match format {
+            image::ImageOutputFormat::PNG => {
+                ConversionProcessor::encode_png(buffer, &settings.png_settings, writer)
+            }
+            format => buffer.write_to(writer, format).map_err(Error::Encode),
+        }
+    }
+
+    fn encode_png<W: Write>(
+        buffer: &image::DynamicImage,
+        settings: &PNGEncodingSettings,
+        writer: &mut W,
+    ) -> Result<(), Error> {
+        use image::GenericImageView;
+
+        // PNG has no BGR(A) color types.
+        let (color_type, bytes) = match buffer {
+            image::DynamicImage::ImageLuma8(image) => (png::ColorType::Grayscale, image.to_vec()),
+            image::DynamicImage::ImageLumaA8(image) => {
+                (png::ColorType::GrayscaleAlpha, image.to_vec())
+            }
+            image::DynamicImage::ImageRgb8(image) => (png::ColorType::RGB, image.to_vec()),
+            image::DynamicImage::ImageRgba8(image) => (png::ColorType::RGBA, image.to_vec()),
+            image::DynamicImage::ImageBgr8(_) => (png::ColorType::RGB, buffer.to_rgb().into_raw()),
+            image::DynamicImage::ImageBgra8(_) => {
+                (png::ColorType::RGBA, buffer.to_rgba().into_raw())
+            }
+        };
+
+        let (width, height) = buffer.dimensions();
+        let mut encoder = png::Encoder::new(writer, width, height);
+        encoder.set_color(color_type);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.set_compression(png_compression(settings.compression));
+        encoder.set_filter(png_filter_type(settings.filter));
+
+        encoder
+            .write_header()
+            .and_then(|mut writer| writer.write_image_data(&bytes))
+            .map_err(|err| Error::Encode(png_encoding_error(err)))
+    }
+}
+
+// Keeps the kind of a png error, the same way the image crate reports errors of its own encoders.
+fn png_encoding_error(err: png::EncodingError) -> image::ImageError {
+    match err {
+        png::EncodingError::IoError(err) => image::ImageError::IoError(err),
+        png::EncodingError::Format(msg) => image::ImageError::FormatError(msg.into_owned()),
+    }
+}
+
+fn png_compression(compression: PNGCompression) -> png::Compression {
+    match compression {
+        PNGCompression::Fast => png::Compression::Fast,
+        PNGCompression::Default => png::Compression::Default,
+        PNGCompression::Best => png::Compression::Best,
+        PNGCompression::Huffman => png::Compression::Huffman,
+        PNGCompression::Rle => png::Compression::Rle,
     }
 }
 
-impl<'a> ProcessWithConfig<Result<(), String>> for ConversionProcessor<'a> {
-    fn process(&self, config: &Config) -> Result<(), String> {
+fn png_filter_type(filter: PNGFilterType) -> png::FilterType {
+    match filter {
+        PNGFilterType::NoFilter => png::FilterType::NoFilter,
+        PNGFilterType::Sub => png::FilterType::Sub,
+        PNGFilterType::Up => png::FilterType::Up,
+        PNGFilterType::Avg => png::FilterType::Avg,
+        PNGFilterType::Paeth => png::FilterType::Paeth,
+    }
+}
+
+impl<'a> ProcessWithConfig<Result<(), Error>> for ConversionProcessor<'a> {
+    fn process(&self, config: &Config) -> Result<(), Error> {
         let output_format = self.output_format.clone();
         let color_processing =
             &ConversionProcessor::preprocess_color_type(&config, &self.image, &output_format);
@@ -97,9 +183,18 @@ impl<'a> ProcessWithConfig<Result<(), String>> for ConversionProcessor<'a> {
 
         match &config.output {
             // Some() => write to file
-            Some(v) => ConversionProcessor::save_to_file(&export_buffer, output_format, v),
+            Some(v) => ConversionProcessor::save_to_file(
+                export_buffer,
+                output_format,
+                &config.encoding_settings,
+                v,
+            ),
             // None => write to stdout
-            None => ConversionProcessor::export_to_stdout(&export_buffer, output_format),
+            None => ConversionProcessor::export_to_stdout(
+                export_buffer,
+                output_format,
+                &config.encoding_settings,
+            ),
         }
     }
 }
@@ -111,7 +206,8 @@ mod tests {
     use crate::config::ConfigItem;
 
     use crate::config::{
-        Config, FormatEncodingSettings, JPEGEncodingSettings, PNMEncodingSettings,
+        BitmapConversion, Config, FormatEncodingSettings, JPEGEncodingSettings,
+        PNGEncodingSettings, PNMEncodingSettings,
     };
     use crate::processor::mod_test_includes::*;
 
@@ -126,12 +222,15 @@ mod tests {
         Config {
             tool_name: env!("CARGO_PKG_NAME"),
             licenses: vec![],
+            forced_input_format: None,
             forced_output_format: None,
             disable_automatic_color_type_adjustment: false,
 
             encoding_settings: FormatEncodingSettings {
                 jpeg_settings: JPEGEncodingSettings::new_result((false, None))
                     .expect("Invalid jpeg settings"),
+                png_settings: PNGEncodingSettings::new_result((false, None), (false, None))
+                    .expect("Invalid png settings"),
                 pnm_settings: PNMEncodingSettings::new(false),
             },
 
@@ -296,4 +395,142 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn pbm_bitmap_conversion() {
+        let gradient =
+            image::DynamicImage::ImageRgb8(image::ImageBuffer::from_fn(64, 8, |x, _| {
+                image::Rgb([x as u8 * 4; 3])
+            }));
+        let bitmap = image::ImageOutputFormat::PNM(image::pnm::PNMSubtype::Bitmap(
+            image::pnm::SampleEncoding::Binary,
+        ));
+
+        let convert = |conversion| {
+            let mut config = setup_dummy_config("unused.pbm");
+            config.encoding_settings.pnm_settings.bitmap_conversion = conversion;
+
+            let converted = ConversionProcessor::preprocess_color_type(&config, &gradient, &bitmap)
+                .expect("Bitmaps should be preprocessed.");
+
+            let mut bytes = Vec::new();
+            ConversionProcessor::encode(
+                &converted,
+                bitmap.clone(),
+                &config.encoding_settings,
+                &mut bytes,
+            )
+            .expect("Unable to encode pbm.");
+
+            image::load_from_memory(&bytes)
+                .unwrap()
+                .to_luma()
+                .into_raw()
+        };
+
+        let thresholded = convert(BitmapConversion::Threshold(100));
+        assert!(thresholded
+            .iter()
+            .enumerate()
+            .all(|(i, &v)| (v == 255) == (i % 64 >= 25)));
+
+        for &dither in [dither::Dither::FloydSteinberg, dither::Dither::Bayer].iter() {
+            let dithered = convert(BitmapConversion::Dither(dither));
+            let white = dithered.iter().filter(|&&v| v == 255).count() as f32;
+
+            assert!(dithered.iter().all(|&v| v == 0 || v == 255));
+            assert!((white / dithered.len() as f32 - 0.5).abs() < 0.05);
+        }
+    }
+
+    #[test]
+    fn png_encoding_settings_are_lossless() {
+        let buffer = image::open(setup_test_image("botanical.jpg")).expect("Can't open test file.");
+        let bgra = image::DynamicImage::ImageBgra8(buffer.to_bgra());
+
+        let encode = |image: &image::DynamicImage, compression, filter| {
+            let mut settings = setup_dummy_config("unused.png").encoding_settings;
+            settings.png_settings =
+                PNGEncodingSettings::new_result((true, Some(compression)), (true, Some(filter)))
+                    .expect("Invalid png settings");
+
+            let mut bytes = Vec::new();
+            ConversionProcessor::encode(
+                image,
+                image::ImageOutputFormat::PNG,
+                &settings,
+                &mut bytes,
+            )
+            .expect("Unable to encode png.");
+            bytes
+        };
+
+        let fast = encode(&buffer, "fast", "sub");
+        let best = encode(&buffer, "best", "sub");
+
+        assert!(best.len() < fast.len());
+
+        let filtered = ["none", "sub", "up", "avg", "paeth"]
+            .iter()
+            .map(|filter| encode(&buffer, "default", filter));
+
+        for bytes in filtered.chain(vec![fast, best]) {
+            let decoded = image::load_from_memory_with_format(&bytes, image::ImageFormat::PNG)
+                .expect("Unable to decode png.");
+            assert_eq!(buffer.raw_pixels(), decoded.raw_pixels());
+        }
+
+        let decoded = image::load_from_memory(&encode(&bgra, "default", "sub")).unwrap();
+        assert_eq!(bgra.to_rgba().into_raw(), decoded.to_rgba().into_raw());
+    }
+
+    #[test]
+    fn png_filters_which_use_the_previous_row_are_lossless() {
+        // Each row differs from the one above it, so a row filtered against the filtered,
+        // instead of the original, previous row doesn't decode to the same pixels.
+        let buffer = image::DynamicImage::ImageRgb8(image::ImageBuffer::from_fn(9, 7, |x, y| {
+            image::Rgb([
+                (x * 29 + y * 7) as u8,
+                (x * y * 13) as u8,
+                (200 - x * y * 3) as u8,
+            ])
+        }));
+
+        for &filter in [PNGFilterType::Up, PNGFilterType::Avg, PNGFilterType::Paeth].iter() {
+            let mut settings = setup_dummy_config("unused.png").encoding_settings;
+            settings.png_settings = PNGEncodingSettings {
+                compression: PNGCompression::Default,
+                filter,
+            };
+
+            let mut bytes = Vec::new();
+            ConversionProcessor::encode(
+                &buffer,
+                image::ImageOutputFormat::PNG,
+                &settings,
+                &mut bytes,
+            )
+            .expect("Unable to encode png.");
+
+            let decoded = image::load_from_memory_with_format(&bytes, image::ImageFormat::PNG)
+                .expect("Unable to decode png.");
+            assert_eq!(buffer.raw_pixels(), decoded.raw_pixels(), "{:?}", filter);
+        }
+    }
+
+    #[test]
+    fn png_encoding_error_keeps_its_kind() {
+        let format = png_encoding_error(png::EncodingError::Format("invalid width".into()));
+        assert!(
+            matches!(format, image::ImageError::FormatError(ref msg) if msg == "invalid width")
+        );
+
+        let io = png_encoding_error(png::EncodingError::IoError(io::Error::new(
+            io::ErrorKind::WriteZero,
+            "full",
+        )));
+        assert!(
+            matches!(io, image::ImageError::IoError(ref err) if err.kind() == io::ErrorKind::WriteZero)
+        );
+    }
 }
@@ -1,6 +1,7 @@
 use std::path::Path;
 
 use crate::config::Config;
+use crate::error::Error;
 use crate::processor::ProcessWithConfig;
 
 const DEFAULT_PIPED_OUTPUT_FORMAT: image::ImageOutputFormat = image::ImageOutputFormat::BMP;
@@ -10,7 +11,7 @@ pub struct EncodingFormatDecider;
 
 impl EncodingFormatDecider {
     // return: Ok: valid extension, err: invalid i.e. no extension or no valid output path
-    fn get_output_extension(config: &Config) -> Result<String, String> {
+    fn get_output_extension(config: &Config) -> Result<String, Error> {
         match &config.output {
             Some(v) => {
                 let path = &Path::new(v);
@@ -18,10 +19,10 @@ impl EncodingFormatDecider {
 
                 extension
                     .and_then(std::ffi::OsStr::to_str)
-                    .ok_or_else(|| "No extension was found".into())
+                    .ok_or(Error::MissingExtension)
                     .map(str::to_lowercase)
             }
-            None => Err("No valid output path found (type: efd/ext)".into()),
+            None => Err(Error::MissingOutputPath),
         }
     }
 
@@ -34,7 +35,7 @@ impl EncodingFormatDecider {
     }
 
     // <output format type as String, error message as String>
-    fn determine_format_string(config: &Config) -> Result<String, String> {
+    fn determine_format_string(config: &Config) -> Result<String, Error> {
         if let Some(v) = &config.forced_output_format {
             Ok(v.to_lowercase())
         } else {
@@ -45,7 +46,7 @@ impl EncodingFormatDecider {
     fn determine_format_from_str(
         config: &Config,
         identifier: &str,
-    ) -> Result<image::ImageOutputFormat, String> {
+    ) -> Result<image::ImageOutputFormat, Error> {
         match identifier {
             "bmp" => Ok(image::ImageOutputFormat::BMP),
             "gif" => Ok(image::ImageOutputFormat::GIF),
@@ -78,14 +79,11 @@ impl EncodingFormatDecider {
             "pam" => Ok(image::ImageOutputFormat::PNM(
                 image::pnm::PNMSubtype::ArbitraryMap,
             )),
-            _ => Err(format!(
-                "No supported image output format was found, input: {}.",
-                identifier
-            )),
+            _ => Err(Error::UnsupportedOutputFormat(identifier.to_string())),
         }
     }
 
-    fn compute_format(config: &Config) -> Result<image::ImageOutputFormat, String> {
+    fn compute_format(config: &Config) -> Result<image::ImageOutputFormat, Error> {
         if config.output.is_none() && config.forced_output_format.is_none() {
             return Ok(DEFAULT_PIPED_OUTPUT_FORMAT);
         }
@@ -105,16 +103,17 @@ impl EncodingFormatDecider {
     }
 }
 
-impl ProcessWithConfig<Result<image::ImageOutputFormat, String>> for EncodingFormatDecider {
-    fn process(&self, config: &Config) -> Result<image::ImageOutputFormat, String> {
-        EncodingFormatDecider::compute_format(&config)
+impl ProcessWithConfig<Result<image::ImageOutputFormat, Error>> for EncodingFormatDecider {
+    fn process(&self, config: &Config) -> Result<image::ImageOutputFormat, Error> {
+        EncodingFormatDecider::compute_format(config)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use crate::config::{
-        Config, ConfigItem, FormatEncodingSettings, JPEGEncodingSettings, PNMEncodingSettings,
+        Config, ConfigItem, FormatEncodingSettings, JPEGEncodingSettings, PNGEncodingSettings,
+        PNMEncodingSettings,
     };
     use crate::processor::mod_test_includes::*;
 
@@ -152,12 +151,15 @@ mod tests {
         Config {
             tool_name: env!("CARGO_PKG_NAME"),
             licenses: vec![],
+            forced_input_format: None,
             forced_output_format: force_format,
             disable_automatic_color_type_adjustment: false,
 
             encoding_settings: FormatEncodingSettings {
                 jpeg_settings: JPEGEncodingSettings::new_result((false, None))
                     .expect("Invalid jpeg settings"),
+                png_settings: PNGEncodingSettings::new_result((false, None), (false, None))
+                    .expect("Invalid png settings"),
                 pnm_settings: PNMEncodingSettings::new(pnm_ascii),
             },
 
@@ -315,12 +317,15 @@ mod tests {
         let jpeg_conf = Config {
             tool_name: env!("CARGO_PKG_NAME"),
             licenses: vec![],
+            forced_input_format: None,
             forced_output_format: None,
             disable_automatic_color_type_adjustment: false,
 
             encoding_settings: FormatEncodingSettings {
                 jpeg_settings: JPEGEncodingSettings::new_result((true, Some("40")))
                     .expect("Invalid jpeg settings"),
+                png_settings: PNGEncodingSettings::new_result((false, None), (false, None))
+                    .expect("Invalid png settings"),
                 pnm_settings: PNMEncodingSettings::new(false),
             },
 
@@ -348,11 +353,14 @@ mod tests {
         let jpeg_conf = Config {
             tool_name: env!("CARGO_PKG_NAME"),
             licenses: vec![],
+            forced_input_format: None,
             forced_output_format: None,
             disable_automatic_color_type_adjustment: false,
 
             encoding_settings: FormatEncodingSettings {
                 jpeg_settings: JPEGEncodingSettings { quality: 90 },
+                png_settings: PNGEncodingSettings::new_result((false, None), (false, None))
+                    .expect("Invalid png settings"),
                 pnm_settings: PNMEncodingSettings::new(false),
             },
 
@@ -378,11 +386,14 @@ mod tests {
         let jpeg_conf = Config {
             tool_name: env!("CARGO_PKG_NAME"),
             licenses: vec![],
+            forced_input_format: None,
             forced_output_format: None,
             disable_automatic_color_type_adjustment: false,
 
             encoding_settings: FormatEncodingSettings {
                 jpeg_settings: JPEGEncodingSettings { quality: 90 },
+                png_settings: PNGEncodingSettings::new_result((false, None), (false, None))
+                    .expect("Invalid png settings"),
                 pnm_settings: PNMEncodingSettings::new(false),
             },
 
@@ -408,11 +419,14 @@ mod tests {
         let jpeg_conf = Config {
             tool_name: env!("CARGO_PKG_NAME"),
             licenses: vec![],
+            forced_input_format: None,
             forced_output_format: Some("OiOi".into()), // unsupported format
             disable_automatic_color_type_adjustment: false,
 
             encoding_settings: FormatEncodingSettings {
                 jpeg_settings: JPEGEncodingSettings { quality: 90 },
+                png_settings: PNGEncodingSettings::new_result((false, None), (false, None))
+                    .expect("Invalid png settings"),
                 pnm_settings: PNMEncodingSettings::new(false),
             },
 
@@ -438,11 +452,14 @@ mod tests {
         let jpeg_conf = Config {
             tool_name: env!("CARGO_PKG_NAME"),
             licenses: vec![],
+            forced_input_format: None,
             forced_output_format: Some("OiOi".into()), // unsupported format
             disable_automatic_color_type_adjustment: false,
 
             encoding_settings: FormatEncodingSettings {
                 jpeg_settings: JPEGEncodingSettings { quality: 90 },
+                png_settings: PNGEncodingSettings::new_result((false, None), (false, None))
+                    .expect("Invalid png settings"),
                 pnm_settings: PNMEncodingSettings::new(false),
             },
 
@@ -462,5 +479,23 @@ mod tests {
             .expect("Unable to save file to the test computer");
     }
 
+    #[test]
+    fn test_output_extension_without_output_path() {
+        let mut config = setup_dummy_config("encoding_processing_no_output", "png", None, false);
+        config.output = None;
+
+        assert!(matches!(
+            EncodingFormatDecider::get_output_extension(&config),
+            Err(Error::MissingOutputPath)
+        ));
+
+        config.output = Some("encoding_processing_no_ext".into());
+
+        assert!(matches!(
+            EncodingFormatDecider::get_output_extension(&config),
+            Err(Error::MissingExtension)
+        ));
+    }
+
     // TODO{}: test bad cases, edges
 }
@@ -1,6 +1,7 @@
-use std::process;
+use std::io::{self, Write};
 
 use crate::config::{Config, SelectedLicenses};
+use crate::error::Error;
 use crate::processor::ProcessWithConfig;
 
 #[derive(Debug, Default)]
@@ -19,24 +20,98 @@ impl<'a> LicenseDisplayProcessor<'a> {
 }
 
 impl<'a> LicenseDisplayProcessor<'a> {
-    fn print_licenses(&self, slice: &[SelectedLicenses], tool_name: &str) {
+    // Writes the selected licenses to `writer`.
+    // Returns whether any license was written.
+    pub fn write_licenses<W: Write>(
+        &self,
+        writer: &mut W,
+        slice: &[SelectedLicenses],
+        tool_name: &str,
+    ) -> Result<bool, Error> {
         for item in slice {
             match item {
-                SelectedLicenses::ThisSoftware => {
-                    println!("{} image tools license:\n\n{}\n\n", tool_name, &self.self_license);
+                SelectedLicenses::ThisSoftware => writeln!(
+                    writer,
+                    "{} image tools license:\n\n{}\n\n",
+                    tool_name, &self.self_license
+                )?,
+                SelectedLicenses::Dependencies => {
+                    writeln!(writer, "{}", &self.dependency_licenses)?
                 }
-                SelectedLicenses::Dependencies => println!("{}", &self.dependency_licenses),
             };
         }
 
-        if !slice.is_empty() {
-            process::exit(0);
-        }
+        Ok(!slice.is_empty())
+    }
+}
+
+// Writes the licenses selected in the config to stdout.
+// Returns whether any license was displayed. Front-ends which only display licenses
+// when requested can use this to decide whether they should exit.
+impl ProcessWithConfig<Result<bool, Error>> for LicenseDisplayProcessor<'_> {
+    fn process(&self, config: &Config) -> Result<bool, Error> {
+        let stdout = io::stdout();
+        let mut handle = stdout.lock();
+
+        self.write_licenses(&mut handle, &config.licenses, config.tool_name)
     }
 }
 
-impl ProcessWithConfig<()> for LicenseDisplayProcessor<'_> {
-    fn process(&self, config: &Config) {
-        self.print_licenses(&config.licenses, &config.tool_name);
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SELF_LICENSE: &str = "this software license";
+    const DEPENDENCY_LICENSES: &str = "dependency licenses";
+
+    fn write_to_string(slice: &[SelectedLicenses]) -> (bool, String) {
+        let processor = LicenseDisplayProcessor::new(SELF_LICENSE, DEPENDENCY_LICENSES);
+        let mut output = Vec::new();
+
+        let displayed = processor
+            .write_licenses(&mut output, slice, "stew")
+            .expect("Unable to write licenses.");
+
+        (displayed, String::from_utf8(output).unwrap())
+    }
+
+    #[test]
+    fn nothing_selected() {
+        let (displayed, output) = write_to_string(&[]);
+
+        assert!(!displayed);
+        assert!(output.is_empty());
+    }
+
+    #[test]
+    fn this_software() {
+        let (displayed, output) = write_to_string(&[SelectedLicenses::ThisSoftware]);
+
+        assert!(displayed);
+        assert_eq!(
+            "stew image tools license:\n\nthis software license\n\n\n",
+            output
+        );
+    }
+
+    #[test]
+    fn dependencies() {
+        let (displayed, output) = write_to_string(&[SelectedLicenses::Dependencies]);
+
+        assert!(displayed);
+        assert_eq!("dependency licenses\n", output);
+    }
+
+    #[test]
+    fn both_in_selected_order() {
+        let (_, output) = write_to_string(&[
+            SelectedLicenses::Dependencies,
+            SelectedLicenses::ThisSoftware,
+        ]);
+
+        let dependencies = output.find(DEPENDENCY_LICENSES).unwrap();
+        let this_software = output.find(SELF_LICENSE).unwrap();
+
+        assert!(dependencies < this_software);
     }
 }